@@ -0,0 +1,23 @@
+-- Add migration script here
+
+CREATE TABLE
+    IF NOT EXISTS clip_revisions (
+        clip_id TEXT NOT NULL,
+        revision INTEGER NOT NULL,
+        content TEXT NOT NULL,
+        title TEXT,
+        posted DATETIME NOT NULL,
+        PRIMARY KEY (clip_id, revision),
+        FOREIGN KEY (clip_id) REFERENCES clips (clip_id) ON DELETE CASCADE
+    );
+
+INSERT INTO
+    clip_revisions (clip_id, revision, content, title, posted)
+SELECT
+    clip_id,
+    1,
+    content,
+    title,
+    posted
+FROM
+    clips;
@@ -0,0 +1,5 @@
+-- Add migration script here
+
+ALTER TABLE clips ADD COLUMN max_views INTEGER;
+
+ALTER TABLE clips ADD COLUMN views BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,43 @@
+-- Add migration script here
+
+-- Only clips that anyone can read are indexed: password-protected and
+-- view-limited clips never enter the search index.
+CREATE VIRTUAL TABLE
+    IF NOT EXISTS clips_fts USING fts5 (
+        shortcode UNINDEXED,
+        title,
+        content,
+        tokenize = 'unicode61'
+    );
+
+INSERT INTO
+    clips_fts (shortcode, title, content)
+SELECT
+    shortcode,
+    title,
+    content
+FROM
+    clips
+WHERE
+    password IS NULL
+    AND max_views IS NULL;
+
+CREATE TRIGGER IF NOT EXISTS clips_fts_insert AFTER INSERT ON clips
+WHEN new.password IS NULL AND new.max_views IS NULL
+BEGIN
+    INSERT INTO clips_fts (shortcode, title, content)
+    VALUES (new.shortcode, new.title, new.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_fts_delete AFTER DELETE ON clips
+BEGIN
+    DELETE FROM clips_fts WHERE shortcode = old.shortcode;
+END;
+
+CREATE TRIGGER IF NOT EXISTS clips_fts_update AFTER UPDATE OF shortcode, title, content, password, max_views ON clips
+BEGIN
+    DELETE FROM clips_fts WHERE shortcode = old.shortcode;
+    INSERT INTO clips_fts (shortcode, title, content)
+    SELECT new.shortcode, new.title, new.content
+    WHERE new.password IS NULL AND new.max_views IS NULL;
+END;
@@ -0,0 +1,3 @@
+-- Add migration script here
+
+ALTER TABLE clips ADD COLUMN language TEXT;
@@ -0,0 +1,14 @@
+-- Add migration script here
+
+CREATE TABLE
+    IF NOT EXISTS clip_attachments (
+        attachment_id TEXT PRIMARY KEY NOT NULL,
+        clip_id TEXT NOT NULL,
+        filename TEXT NOT NULL,
+        mime_type TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        FOREIGN KEY (clip_id) REFERENCES clips (clip_id) ON DELETE CASCADE
+    );
+
+CREATE INDEX IF NOT EXISTS clip_attachments_clip_id ON clip_attachments (clip_id);
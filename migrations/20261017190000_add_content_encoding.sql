@@ -0,0 +1,12 @@
+-- Add migration script here
+
+-- Content may now be stored compressed, as marked by content_encoding. The search
+-- index needs the plain text, so it is maintained by the queries writing clips
+-- instead of by triggers reading the stored content.
+ALTER TABLE clips ADD COLUMN content_encoding TEXT;
+
+ALTER TABLE clip_revisions ADD COLUMN content_encoding TEXT;
+
+DROP TRIGGER IF EXISTS clips_fts_insert;
+
+DROP TRIGGER IF EXISTS clips_fts_update;
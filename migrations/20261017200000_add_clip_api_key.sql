@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE clips ADD COLUMN api_key BLOB REFERENCES api_keys (api_key) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS clips_api_key ON clips (api_key);
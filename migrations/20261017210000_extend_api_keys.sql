@@ -0,0 +1,12 @@
+-- Add migration script here
+ALTER TABLE api_keys ADD COLUMN name TEXT;
+
+ALTER TABLE api_keys ADD COLUMN created_at DATETIME NOT NULL DEFAULT 0;
+
+ALTER TABLE api_keys ADD COLUMN last_used_at DATETIME;
+
+ALTER TABLE api_keys ADD COLUMN expires_at DATETIME;
+
+ALTER TABLE api_keys ADD COLUMN scopes TEXT NOT NULL DEFAULT 'clip:read clip:write';
+
+UPDATE api_keys SET created_at = CAST(strftime('%s', 'now') AS INTEGER);
@@ -0,0 +1,13 @@
+-- Add migration script here
+
+CREATE TABLE
+    IF NOT EXISTS clip_views (
+        clip_id TEXT NOT NULL,
+        hour INTEGER NOT NULL,
+        source TEXT NOT NULL,
+        agent TEXT NOT NULL,
+        referrer TEXT NOT NULL DEFAULT '',
+        views INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (clip_id, hour, source, agent, referrer),
+        FOREIGN KEY (clip_id) REFERENCES clips (clip_id) ON DELETE CASCADE
+    );
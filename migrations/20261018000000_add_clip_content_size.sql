@@ -0,0 +1,8 @@
+-- Add migration script here
+
+-- The size of clip content before compression, which is what API key quotas count.
+-- Compressed content written before this column existed cannot be measured here, so
+-- it counts at its stored size until `--recompress` fills the column in.
+ALTER TABLE clips ADD COLUMN content_size INTEGER;
+
+UPDATE clips SET content_size = length(CAST(content AS BLOB)) WHERE content_encoding IS NULL;
@@ -1,8 +1,15 @@
-use clipstash::domain::clip::field::{Content, Expires, Password, ShortCode, Title};
+use clipstash::domain::clip::field::{
+    Content, EditToken, Expires, Format, Language, MaxViews, Password, ShortCode, Title,
+    VanityShortCode,
+};
+use clipstash::domain::clip::ClipSort;
 use clipstash::service::ask::{GetClip, NewClip, UpdateClip};
-use clipstash::web::api::{ApiKey, API_KEY_HEADER};
-use clipstash::Clip;
+use clipstash::web::api::{ApiKey, NewClipResponse, API_KEY_HEADER, EDIT_TOKEN_HEADER};
+use clipstash::web::{ClipList, PublicClip};
+use reqwest::multipart::{Form, Part};
+use rocket::http::ContentType;
 use std::error::Error;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(StructOpt, Debug)]
@@ -11,23 +18,63 @@ enum Command {
         shortcode: ShortCode,
         #[structopt(short, long, help = "password")]
         password: Option<String>,
+        #[structopt(
+            long,
+            help = "only fetch the metadata of the clip, without its content"
+        )]
+        metadata: bool,
     },
     New {
         #[structopt(help = "content")]
         clip: String,
         #[structopt(short, long, help = "password")]
         password: Option<Password>,
-        #[structopt(short, long, help = "expiration date")]
+        #[structopt(
+            short,
+            long,
+            help = "expiration: 10m, 2h, 7d, 1w, YYYY-MM-DD or an RFC 3339 timestamp"
+        )]
         expires: Option<Expires>,
         #[structopt(short, long, help = "title")]
         title: Option<Title>,
+        #[structopt(long, help = "number of views before the clip is deleted")]
+        max_views: Option<MaxViews>,
+        #[structopt(short, long, help = "language, detected from the content when omitted")]
+        language: Option<Language>,
+        #[structopt(short, long, help = "how the clip is displayed: text or markdown")]
+        format: Option<Format>,
+        #[structopt(
+            long,
+            help = "shortcode to publish the clip under, such as deploy-notes"
+        )]
+        shortcode: Option<VanityShortCode>,
+        #[structopt(
+            short,
+            long,
+            help = "file to attach to the clip, can be given more than once"
+        )]
+        attach: Vec<PathBuf>,
+    },
+    List {
+        #[structopt(short, long, help = "number of clips to list")]
+        limit: Option<u32>,
+        #[structopt(short, long, help = "sort order: posted, hits or expires")]
+        sort: Option<ClipSort>,
+        #[structopt(short, long, help = "cursor returned by a previous listing")]
+        cursor: Option<String>,
     },
     Update {
         shortcode: ShortCode,
         clip: String,
+        #[structopt(long, help = "edit token returned when the clip was created")]
+        edit_token: EditToken,
         #[structopt(short, long, help = "password")]
         password: Option<Password>,
-        #[structopt(short, long, help = "expiration date")]
+        #[structopt(
+            short,
+            long,
+            help = "expiration: 10m, 2h, 7d, 1w, YYYY-MM-DD or an RFC 3339 timestamp"
+        )]
         expires: Option<Expires>,
         #[structopt(short, long, help = "title")]
         title: Option<Title>,
@@ -47,10 +94,15 @@ struct Opt {
     api_key: ApiKey,
 }
 
-async fn get_clip(addr: &str, ask_svc: GetClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
+async fn get_clip(
+    addr: &str,
+    ask_svc: GetClip,
+    metadata: bool,
+    api_key: ApiKey,
+) -> Result<PublicClip, Box<dyn Error>> {
     let client = reqwest::Client::builder().build()?;
     let addr = format!("{}/api/clip/{}", addr, ask_svc.shortcode.into_inner());
-    let mut request = client.get(addr);
+    let mut request = client.get(addr).query(&[("metadata", metadata)]);
     request = match ask_svc.password.into_inner() {
         Some(password) => request.header(reqwest::header::COOKIE, format!("password={}", password)),
         None => request,
@@ -59,7 +111,11 @@ async fn get_clip(addr: &str, ask_svc: GetClip, api_key: ApiKey) -> Result<Clip,
     Ok(request.send().await?.json().await?)
 }
 
-async fn new_clip(addr: &str, ask_svc: NewClip, api_key: ApiKey) -> Result<Clip, Box<dyn Error>> {
+async fn new_clip(
+    addr: &str,
+    ask_svc: NewClip,
+    api_key: ApiKey,
+) -> Result<NewClipResponse, Box<dyn Error>> {
     let client = reqwest::Client::builder().build()?;
     let addr = format!("{}/api/clip", addr);
     let mut request = client.post(addr);
@@ -67,15 +123,91 @@ async fn new_clip(addr: &str, ask_svc: NewClip, api_key: ApiKey) -> Result<Clip,
     Ok(request.json(&ask_svc).send().await?.json().await?)
 }
 
+async fn upload_clip(
+    addr: &str,
+    ask_svc: NewClip,
+    attachments: Vec<PathBuf>,
+    api_key: ApiKey,
+) -> Result<NewClipResponse, Box<dyn Error>> {
+    let client = reqwest::Client::builder().build()?;
+    let addr = format!("{}/api/clip", addr);
+    let mut form = Form::new()
+        .text("content", ask_svc.content.into_inner())
+        .text("format", ask_svc.format.to_string());
+    if let Some(title) = ask_svc.title.into_inner() {
+        form = form.text("title", title);
+    }
+    if let Some(expires) = ask_svc.expires.into_inner() {
+        form = form.text("expires", expires.into_inner().to_rfc3339());
+    }
+    if let Some(password) = ask_svc.password.into_inner() {
+        form = form.text("password", password);
+    }
+    if let Some(max_views) = ask_svc.max_views.into_inner() {
+        form = form.text("max_views", max_views.to_string());
+    }
+    if let Some(language) = ask_svc.language.into_inner() {
+        form = form.text("language", language);
+    }
+    if let Some(shortcode) = ask_svc.shortcode.into_inner() {
+        form = form.text("shortcode", shortcode.into_inner());
+    }
+    for path in attachments {
+        let mime_type = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(ContentType::from_extension)
+            .unwrap_or(ContentType::Binary);
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let part = Part::bytes(std::fs::read(&path)?)
+            .file_name(filename)
+            .mime_str(&mime_type.to_string())?;
+        form = form.part("attachments", part);
+    }
+    let mut request = client.post(addr);
+    request = request.header(API_KEY_HEADER, api_key.to_base64());
+    Ok(request.multipart(form).send().await?.json().await?)
+}
+
+async fn list_clips(
+    addr: &str,
+    limit: Option<u32>,
+    sort: Option<ClipSort>,
+    cursor: Option<String>,
+    api_key: ApiKey,
+) -> Result<ClipList, Box<dyn Error>> {
+    let client = reqwest::Client::builder().build()?;
+    let addr = format!("{}/api/clip", addr);
+    let mut query = vec![];
+    if let Some(limit) = limit {
+        query.push(("limit", limit.to_string()));
+    }
+    if let Some(sort) = sort {
+        query.push(("sort", sort.to_string()));
+    }
+    if let Some(cursor) = cursor {
+        query.push(("cursor", cursor));
+    }
+    let mut request = client.get(addr).query(&query);
+    request = request.header(API_KEY_HEADER, api_key.to_base64());
+    Ok(request.send().await?.json().await?)
+}
+
 async fn update_clip(
     addr: &str,
     ask_svc: UpdateClip,
     api_key: ApiKey,
-) -> Result<Clip, Box<dyn Error>> {
+) -> Result<PublicClip, Box<dyn Error>> {
     let client = reqwest::Client::builder().build()?;
     let addr = format!("{}/api/clip", addr);
     let mut request = client.put(addr);
     request = request.header(API_KEY_HEADER, api_key.to_base64());
+    if let Some(edit_token) = ask_svc.edit_token.as_str() {
+        request = request.header(EDIT_TOKEN_HEADER, edit_token);
+    }
     Ok(request.json(&ask_svc).send().await?.json().await?)
 }
 
@@ -84,12 +216,13 @@ async fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
         Command::Get {
             shortcode,
             password,
+            metadata,
         } => {
             let req = GetClip {
                 password: Password::new(password.unwrap_or_default())?,
                 shortcode,
             };
-            let clip = get_clip(opt.addr.as_str(), req, opt.api_key).await?;
+            let clip = get_clip(opt.addr.as_str(), req, metadata, opt.api_key).await?;
             println!("{:#?}", clip);
             Ok(())
         }
@@ -98,36 +231,64 @@ async fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
             password,
             expires,
             title,
+            max_views,
+            language,
+            format,
+            shortcode,
+            attach,
         } => {
             let req = NewClip {
                 content: Content::new(clip.as_str())?,
                 title: title.unwrap_or_default(),
                 expires: expires.unwrap_or_default(),
                 password: password.unwrap_or_default(),
+                max_views: max_views.unwrap_or_default(),
+                language: language.unwrap_or_default(),
+                format: format.unwrap_or_default(),
+                shortcode: shortcode.unwrap_or_default(),
+                attachments: vec![],
+                api_key: None,
+            };
+            let clip = if attach.is_empty() {
+                new_clip(opt.addr.as_str(), req, opt.api_key).await?
+            } else {
+                upload_clip(opt.addr.as_str(), req, attach, opt.api_key).await?
             };
-            let clip = new_clip(opt.addr.as_str(), req, opt.api_key).await?;
             println!("{:#?}", clip);
             Ok(())
         }
+        Command::List {
+            limit,
+            sort,
+            cursor,
+        } => {
+            let list = list_clips(opt.addr.as_str(), limit, sort, cursor, opt.api_key).await?;
+            println!("{:#?}", list);
+            Ok(())
+        }
         Command::Update {
             clip,
             password,
             expires,
             title,
             shortcode,
+            edit_token,
         } => {
             let password = password.unwrap_or_default();
             let svc_req = GetClip {
                 password: password.clone(),
                 shortcode: shortcode.clone(),
             };
-            let original_clip = get_clip(opt.addr.as_str(), svc_req, opt.api_key.clone()).await?;
+            let original_clip =
+                get_clip(opt.addr.as_str(), svc_req, true, opt.api_key.clone()).await?;
             let svc_req = UpdateClip {
                 content: Content::new(clip.as_str())?,
                 expires: expires.unwrap_or(original_clip.expires),
                 title: title.unwrap_or(original_clip.title),
                 password,
                 shortcode,
+                edit_token,
+                keep_password: false,
             };
             let clip = update_clip(opt.addr.as_str(), svc_req, opt.api_key).await?;
             println!("{:#?}", clip);
@@ -0,0 +1,279 @@
+use clipstash::data::AppDatabase;
+use clipstash::domain::clip::field::{Expires, ShortCode};
+use clipstash::service::{action, ask};
+use clipstash::web::api::scope::Scopes;
+use clipstash::web::api::ApiKey;
+use clipstash::{data::query::RevocationStatus, Time};
+use dotenv::dotenv;
+use std::error::Error;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+enum KeyCommand {
+    /// Creates an API key and prints it; it cannot be shown again.
+    Create {
+        #[structopt(help = "name of the key")]
+        name: String,
+        #[structopt(
+            short,
+            long,
+            default_value = "clip:read clip:write",
+            help = "scopes: clip:read, clip:write and key:admin"
+        )]
+        scopes: Scopes,
+        #[structopt(
+            short,
+            long,
+            help = "expiration: 10m, 2h, 7d, 1w, YYYY-MM-DD or an RFC 3339 timestamp"
+        )]
+        expires: Option<Expires>,
+    },
+    /// Lists the API keys, without revealing them.
+    List,
+    /// Revokes an API key.
+    Revoke {
+        #[structopt(help = "id of the key as listed, or the key itself")]
+        key: KeyRef,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+enum ClipCommand {
+    /// Lists the stored clips, newest first, including protected and expired ones.
+    List {
+        #[structopt(short, long, default_value = "50", help = "number of clips to list")]
+        limit: u32,
+    },
+    /// Prints a clip with its metadata, without checking its password or counting a view.
+    Inspect { shortcode: ShortCode },
+    /// Deletes a clip without its edit token.
+    Delete { shortcode: ShortCode },
+}
+
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Manages API keys.
+    Key(KeyCommand),
+    /// Manages clips.
+    Clip(ClipCommand),
+    /// Deletes expired clips now rather than waiting for the server to.
+    Purge,
+    /// Prints the number of clips, their stored size and hits.
+    Stats,
+    /// Rebuilds the database file to reclaim the space of deleted clips.
+    Vacuum,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "clipstash-admin", about = "ClipStash database administration")]
+struct Opt {
+    #[structopt(subcommand)]
+    command: Command,
+
+    #[structopt(
+        short,
+        long,
+        default_value = "sqlite:data.db",
+        help = "The database connection string"
+    )]
+    connection_string: String,
+}
+
+/// An API key, or the id it is listed under.
+#[derive(Debug)]
+enum KeyRef {
+    Id(String),
+    Key(ApiKey),
+}
+
+impl FromStr for KeyRef {
+    type Err = String;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        if key.len() == 12 && key.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Self::Id(key.to_owned()))
+        } else {
+            ApiKey::from_str(key)
+                .map(Self::Key)
+                .map_err(|_| format!("not a key id or API key: {}", key))
+        }
+    }
+}
+
+fn format_time(time: Time) -> String {
+    time.into_inner().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+fn format_optional_time(time: Option<Time>, none: &str) -> String {
+    time.map(format_time).unwrap_or_else(|| none.to_owned())
+}
+
+async fn run_key(command: KeyCommand, database: &AppDatabase) -> Result<(), Box<dyn Error>> {
+    action::hash_plaintext_api_keys(database.get_pool()).await?;
+    match command {
+        KeyCommand::Create {
+            name,
+            scopes,
+            expires,
+        } => {
+            let req = ask::NewApiKey {
+                name: Some(name),
+                scopes,
+                expires: expires.unwrap_or_default(),
+            };
+            let api_key = action::generate_api_key(req, database).await?;
+            let info = action::get_api_key(&api_key, database).await?;
+            println!("{}", api_key.to_base64());
+            eprintln!("Created key {}; it cannot be shown again.", info.id);
+        }
+        KeyCommand::List => {
+            println!(
+                "{:<12}  {:<20}  {:<19}  {:<19}  {:<19}  scopes",
+                "id", "name", "created", "last used", "expires"
+            );
+            for key in action::list_api_keys(database).await? {
+                println!(
+                    "{:<12}  {:<20}  {:<19}  {:<19}  {:<19}  {}",
+                    key.id,
+                    key.name.unwrap_or_default(),
+                    format_time(key.created_at),
+                    format_optional_time(key.last_used_at, "never"),
+                    format_optional_time(key.expires_at, "never"),
+                    key.scopes
+                );
+            }
+        }
+        KeyCommand::Revoke { key } => {
+            let status = match key {
+                KeyRef::Id(id) => action::revoke_api_key_by_id(&id, database).await?,
+                KeyRef::Key(api_key) => action::revoke_api_key(api_key, database).await?,
+            };
+            match status {
+                RevocationStatus::Revoked => println!("Revoked"),
+                RevocationStatus::NotFound => return Err("no such API key".into()),
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_clip(command: ClipCommand, database: &AppDatabase) -> Result<(), Box<dyn Error>> {
+    match command {
+        ClipCommand::List { limit } => {
+            println!(
+                "{:<12}  {:<19}  {:<19}  {:>8}  {:>9}  {:>8}  title",
+                "shortcode", "posted", "expires", "hits", "views", "bytes"
+            );
+            for clip in action::list_all_clips(limit, database).await? {
+                let views = match clip.max_views.into_inner() {
+                    Some(max) => format!("{}/{}", clip.views.into_inner(), max),
+                    None => clip.views.into_inner().to_string(),
+                };
+                let title = clip.title.into_inner().unwrap_or_default();
+                let lock = if clip.password.has_password() {
+                    "[protected] "
+                } else {
+                    ""
+                };
+                println!(
+                    "{:<12}  {:<19}  {:<19}  {:>8}  {:>9}  {:>8}  {}{}",
+                    clip.shortcode.as_str(),
+                    format_time(clip.posted.into_inner()),
+                    format_optional_time(clip.expires.into_inner(), "never"),
+                    clip.hits.into_inner(),
+                    views,
+                    clip.content.as_str().len(),
+                    lock,
+                    title
+                );
+            }
+        }
+        ClipCommand::Inspect { shortcode } => {
+            let clip = action::find_clip(shortcode, database).await?;
+            let attachments = action::list_attachments(&clip, database).await?;
+            println!("shortcode: {}", clip.shortcode.as_str());
+            println!("id:        {}", clip.clip_id.into_inner());
+            println!("title:     {}", clip.title.into_inner().unwrap_or_default());
+            println!("posted:    {}", format_time(clip.posted.into_inner()));
+            println!(
+                "expires:   {}",
+                format_optional_time(clip.expires.into_inner(), "never")
+            );
+            println!("protected: {}", clip.password.has_password());
+            println!("hits:      {}", clip.hits.into_inner());
+            match clip.max_views.into_inner() {
+                Some(max) => println!("views:     {} of {}", clip.views.into_inner(), max),
+                None => println!("views:     {}", clip.views.into_inner()),
+            }
+            println!(
+                "language:  {}",
+                clip.language.as_str().unwrap_or("detected")
+            );
+            println!("format:    {}", clip.format);
+            for attachment in attachments {
+                println!(
+                    "attached:  {} ({}, {} bytes)",
+                    attachment.filename.as_str(),
+                    attachment.mime_type.as_str(),
+                    attachment.size
+                );
+            }
+            println!();
+            println!("{}", clip.content.as_str());
+        }
+        ClipCommand::Delete { shortcode } => {
+            action::purge_clip(shortcode, database).await?;
+            println!("Deleted");
+        }
+    }
+    Ok(())
+}
+
+async fn run(opt: Opt) -> Result<(), Box<dyn Error>> {
+    let database = AppDatabase::new(&opt.connection_string).await;
+    let pool = database.get_pool();
+    match opt.command {
+        Command::Key(command) => run_key(command, &database).await?,
+        Command::Clip(command) => run_clip(command, &database).await?,
+        Command::Purge => {
+            let deleted = action::delete_expired(&database).await?;
+            println!("Deleted {} expired clips", deleted);
+        }
+        Command::Stats => {
+            let stats = action::stats(&database).await?;
+            println!(
+                "clips:       {} ({} expired, {} protected)",
+                stats.clips(),
+                stats.expired(),
+                stats.protected()
+            );
+            println!("content:     {} bytes stored", stats.content_bytes());
+            println!(
+                "attachments: {} ({} bytes)",
+                stats.attachments(),
+                stats.attachment_bytes()
+            );
+            println!("hits:        {}", stats.hits());
+            println!("API keys:    {}", stats.api_keys());
+            println!("database:    {} bytes", action::database_size(pool).await?);
+        }
+        Command::Vacuum => {
+            let before = action::database_size(pool).await?;
+            action::vacuum(pool).await?;
+            let after = action::database_size(pool).await?;
+            println!("Vacuumed: {} -> {} bytes", before, after);
+        }
+    }
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() {
+    dotenv().ok();
+    let opt = Opt::from_args();
+    if let Err(e) = run(opt).await {
+        eprintln!("An error occurred: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -1,46 +1,283 @@
-use clipstash::data::AppDatabase;
+use clipstash::config::{Config, ConfigError};
+use clipstash::data::{AppDatabase, MemoryStore, Store};
+use clipstash::domain::clip::field::shortcode::Generator;
 use clipstash::domain::maintenance::Maintenance;
+use clipstash::domain::quota::Quota;
+use clipstash::service::{action, ask};
+use clipstash::web::api::scope::{Scope, Scopes};
 use clipstash::web::hitcounter::HitCounter;
+use clipstash::web::ratelimit::{Rate, RateLimiter};
 use clipstash::web::renderer::Renderer;
 use dotenv::dotenv;
+use rocket::figment::providers::Serialized;
+use serde::Serialize;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
-#[derive(Debug, StructOpt)]
+/// Command-line flags. Flags left unset fall back to the environment, the configuration
+/// file and the defaults, in that order, so only set ones are serialized.
+#[derive(Debug, StructOpt, Serialize)]
 #[structopt(name = "httpd", about = "A simple HTTP daemon")]
 pub struct Httpd {
     #[structopt(
-        short,
         long,
-        default_value = "sqlite:data.db",
-        help = "The database connection string"
+        parse(from_os_str),
+        env = "CLIPSTASH_CONFIG",
+        help = "The configuration file [default: clipstash.toml, if it exists]"
+    )]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
+    #[structopt(long, help = "Print the effective configuration, then exit")]
+    #[serde(skip)]
+    pub print_config: bool,
+
+    #[structopt(short, long, help = "The database connection string")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_string: Option<String>,
+
+    #[structopt(
+        long,
+        help = "Keep clips and API keys in memory instead of the database; nothing is persisted"
     )]
-    pub connection_string: String,
+    #[serde(skip_serializing_if = "is_false")]
+    pub in_memory: bool,
+
+    #[structopt(
+        long,
+        help = "Do not apply pending database migrations or rewrite stored data at startup"
+    )]
+    #[serde(skip_serializing_if = "is_false")]
+    pub no_migrate: bool,
+
+    #[structopt(long, help = "The most database connections to keep open")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_size: Option<u32>,
 
     #[structopt(
         short,
         long,
-        default_value = "templates/",
         parse(from_os_str),
         help = "The directory containing HTML templates"
     )]
-    pub template_directory: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_directory: Option<PathBuf>,
+
+    #[structopt(long, help = "Compress clip content larger than this many bytes")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compress_above: Option<usize>,
+
+    #[structopt(
+        long,
+        help = "Recompress stored clip content with the current threshold, then exit"
+    )]
+    #[serde(skip)]
+    pub recompress: bool,
+
+    #[structopt(long, help = "The most live clips a single API key may own")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_clips: Option<u64>,
+
+    #[structopt(
+        long,
+        help = "The most bytes of clip content and attachments a single API key may store"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_bytes: Option<u64>,
+
+    #[structopt(
+        long,
+        help = "Clips posted and passwords tried per minute from one IP address, 0 for no limit"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_rate: Option<u32>,
+
+    #[structopt(
+        long,
+        help = "The most web requests one IP address may send in a burst"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub web_burst: Option<u32>,
+
+    #[structopt(
+        long,
+        help = "API requests per minute with one API key, 0 for no limit"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_rate: Option<u32>,
+
+    #[structopt(long, help = "The most API requests one API key may send in a burst")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_burst: Option<u32>,
+
+    #[structopt(
+        long,
+        help = "Take client addresses from the X-Real-IP header; only set this behind a reverse proxy that sets it"
+    )]
+    #[serde(skip_serializing_if = "is_false")]
+    pub trust_proxy_headers: bool,
+
+    #[structopt(long, help = "Seconds between sweeps for expired clips")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maintenance_interval: Option<u64>,
+
+    #[structopt(long, help = "Seconds between writes of buffered hit counts")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hit_flush_interval: Option<u64>,
+
+    #[structopt(
+        long,
+        possible_values = &["random", "words"],
+        help = "Generate shortcodes from random characters or dictionary words"
+    )]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shortcode_mode: Option<String>,
+
+    #[structopt(long, help = "The number of characters in generated shortcodes")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shortcode_length: Option<usize>,
+
+    #[structopt(long, help = "The characters generated shortcodes are drawn from")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shortcode_alphabet: Option<String>,
+
+    #[structopt(long, help = "The number of words in word-based shortcodes")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shortcode_words: Option<usize>,
+
+    #[structopt(long, help = "The name of the cookie remembering clip passwords")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_cookie: Option<String>,
+
+    #[structopt(long, help = "The name of the cookie holding clip edit tokens")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edit_token_cookie: Option<String>,
+}
+
+fn is_false(flag: &bool) -> bool {
+    !flag
+}
+
+/// Layers the configuration file, the environment and the flags.
+fn load_config(opt: &Httpd) -> Result<Config, ConfigError> {
+    let figment = Config::figment(opt.config.as_deref())?.merge(Serialized::defaults(opt));
+    Config::load(figment)
+}
+
+/// Creates an empty in-memory store, along with an API key allowed to do anything, as
+/// there is no other way to mint one.
+async fn in_memory_store(shortcodes: Generator) -> Store {
+    let store = Store::new(MemoryStore::with_shortcodes(shortcodes));
+    let req = ask::NewApiKey {
+        name: Some("in-memory".to_owned()),
+        scopes: Scopes::new([Scope::ClipRead, Scope::ClipWrite, Scope::KeyAdmin]),
+        ..Default::default()
+    };
+    match action::generate_api_key(req, store.get()).await {
+        Ok(api_key) => println!("In-memory store API key: {}", api_key.to_base64()),
+        Err(e) => {
+            eprintln!("Failed to create API key: {}", e);
+            std::process::exit(1);
+        }
+    }
+    store
 }
 
 #[rocket::launch]
 async fn rocket() -> _ {
     dotenv().ok();
     let opt = Httpd::from_args();
-    let renderer = Renderer::new(opt.template_directory.clone());
-    let database = AppDatabase::new(&opt.connection_string).await;
-    let hit_counter = HitCounter::new(database.get_pool());
-    let maintenance = Maintenance::spawn(database.get_pool());
+    let config = match load_config(&opt) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Configuration error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if opt.print_config {
+        print!("{}", config.to_toml());
+        std::process::exit(0);
+    }
+    let renderer = Renderer::new(config.template_directory.clone());
+    let store = if config.in_memory {
+        let shortcodes = config
+            .shortcode_generator()
+            .expect("shortcode settings are checked when loading the configuration");
+        in_memory_store(shortcodes).await
+    } else {
+        let options = config.database_options();
+        let database = match AppDatabase::connect(&config.connection_string, options).await {
+            Ok(database) => database,
+            Err(e) => {
+                eprintln!("Failed to open database: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let schema = if config.no_migrate {
+            database.check_schema().await
+        } else {
+            database.migrate().await
+        };
+        if let Err(e) = schema {
+            eprintln!("Failed to prepare database: {}", e);
+            std::process::exit(1);
+        }
+        if opt.recompress {
+            match action::recompress_clips(database.compress_above(), database.get_pool()).await {
+                Ok(outcome) => {
+                    println!(
+                        "Recompressed {} rows: {} -> {} bytes, saved {} bytes (run VACUUM to reclaim file space)",
+                        outcome.rewritten(),
+                        outcome.bytes_before(),
+                        outcome.bytes_after(),
+                        outcome.bytes_saved()
+                    );
+                    std::process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("Failed to recompress clips: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        if !config.no_migrate {
+            match action::rehash_plaintext_passwords(database.get_pool()).await {
+                Ok(0) => (),
+                Ok(rehashed) => println!("Rehashed {} plaintext clip passwords", rehashed),
+                Err(e) => {
+                    eprintln!("Failed to rehash clip passwords: {}", e);
+                    std::process::exit(1);
+                }
+            }
+            match action::hash_plaintext_api_keys(database.get_pool()).await {
+                Ok(0) => (),
+                Ok(hashed) => println!("Hashed {} plaintext API keys", hashed),
+                Err(e) => {
+                    eprintln!("Failed to hash API keys: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Store::new(database)
+    };
+    let hit_counter = HitCounter::new(&store, config.hit_flush_interval());
+    let maintenance = Maintenance::spawn(&store, config.maintenance_interval());
 
-    let config = clipstash::RocketConfig {
+    let rocket_config = clipstash::RocketConfig {
         renderer,
-        database,
+        store,
         hit_counter,
         maintenance,
+        quota: Quota {
+            max_clips: config.quota_clips,
+            max_bytes: config.quota_bytes,
+        },
+        rate_limiter: RateLimiter::new(
+            Rate::new(config.web_rate, config.web_burst),
+            Rate::new(config.api_rate, config.api_burst),
+            config.trust_proxy_headers,
+        ),
+        cookies: config.cookie_names(),
     };
-    clipstash::rocket(config)
+    clipstash::rocket(rocket_config)
 }
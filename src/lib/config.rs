@@ -0,0 +1,315 @@
+//! Server configuration.
+//!
+//! Settings are layered: built-in defaults, then the TOML configuration file, then
+//! `CLIPSTASH_`-prefixed environment variables, then command-line flags, each overriding
+//! the ones before it. Keys are named as the `httpd` flags are, with underscores, so
+//! `--pool-size`, `pool_size = 4` and `CLIPSTASH_POOL_SIZE=4` all set the same value.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use rocket::figment::providers::{Env, Format, Serialized, Toml};
+use rocket::figment::{Figment, Provider};
+use serde::{Deserialize, Serialize};
+
+use crate::data::{compression, DatabaseOptions};
+use crate::domain::clip::field::shortcode::{self, Generator};
+use crate::web::cookies::{self, CookieNames};
+use crate::ClipError;
+
+/// The configuration file read when none is given.
+pub const DEFAULT_PATH: &str = "clipstash.toml";
+
+/// The prefix of the environment variables that override the configuration file.
+pub const ENV_PREFIX: &str = "CLIPSTASH_";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("{0}")]
+    Load(Box<rocket::figment::Error>),
+    #[error("configuration file not found: {0}")]
+    Missing(PathBuf),
+    #[error("invalid {0}: {1}")]
+    Invalid(&'static str, String),
+    #[error("invalid shortcode settings: {0}")]
+    ShortCode(#[from] ClipError),
+}
+
+impl From<rocket::figment::Error> for ConfigError {
+    fn from(err: rocket::figment::Error) -> Self {
+        Self::Load(Box::new(err))
+    }
+}
+
+/// The effective settings of the server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The database connection string.
+    pub connection_string: String,
+    /// Keep clips and API keys in memory instead of the database.
+    pub in_memory: bool,
+    /// Leave pending database migrations unapplied and stored data unrewritten at startup.
+    pub no_migrate: bool,
+    /// The most database connections to keep open.
+    pub pool_size: u32,
+    /// The directory containing HTML templates.
+    pub template_directory: PathBuf,
+    /// Clip content larger than this many bytes is compressed.
+    pub compress_above: usize,
+    /// The most live clips a single API key may own.
+    pub quota_clips: Option<u64>,
+    /// The most bytes of clip content and attachments a single API key may store.
+    pub quota_bytes: Option<u64>,
+    /// Clips posted and passwords tried per minute from one IP address, 0 for no limit.
+    pub web_rate: u32,
+    /// The most web requests one IP address may send in a burst.
+    pub web_burst: u32,
+    /// API requests per minute with one API key, 0 for no limit.
+    pub api_rate: u32,
+    /// The most API requests one API key may send in a burst.
+    pub api_burst: u32,
+    /// Take client addresses from the `X-Real-IP` header set by a reverse proxy.
+    pub trust_proxy_headers: bool,
+    /// Seconds between sweeps for expired clips.
+    pub maintenance_interval: u64,
+    /// Seconds between writes of buffered hit counts to the store.
+    pub hit_flush_interval: u64,
+    /// Whether shortcodes are random characters or dictionary words.
+    pub shortcode_mode: shortcode::Mode,
+    /// The number of characters in generated shortcodes.
+    pub shortcode_length: usize,
+    /// The characters generated shortcodes are drawn from.
+    pub shortcode_alphabet: String,
+    /// The number of words in word-based shortcodes.
+    pub shortcode_words: usize,
+    /// The name of the cookie remembering clip passwords.
+    pub password_cookie: String,
+    /// The name of the cookie holding clip edit tokens.
+    pub edit_token_cookie: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            connection_string: "sqlite:data.db".to_owned(),
+            in_memory: false,
+            no_migrate: false,
+            pool_size: DatabaseOptions::default().pool_size,
+            template_directory: PathBuf::from("templates/"),
+            compress_above: compression::DEFAULT_THRESHOLD,
+            quota_clips: None,
+            quota_bytes: None,
+            web_rate: 30,
+            web_burst: 10,
+            api_rate: 300,
+            api_burst: 60,
+            trust_proxy_headers: false,
+            maintenance_interval: 10,
+            hit_flush_interval: 5,
+            shortcode_mode: shortcode::Mode::default(),
+            shortcode_length: shortcode::DEFAULT_LENGTH,
+            shortcode_alphabet: shortcode::DEFAULT_ALPHABET.to_owned(),
+            shortcode_words: shortcode::DEFAULT_WORDS,
+            password_cookie: cookies::DEFAULT_PASSWORD_COOKIE.to_owned(),
+            edit_token_cookie: cookies::DEFAULT_EDIT_TOKEN_COOKIE.to_owned(),
+        }
+    }
+}
+
+impl Config {
+    /// Layers the defaults, the configuration file at `path` and the environment.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The configuration file; when `None`, [`DEFAULT_PATH`] is read if it exists.
+    ///
+    /// # Returns
+    ///
+    /// Returns the layered `Figment`, to which command-line flags can be merged,
+    /// or a `ConfigError` if an explicitly given file does not exist.
+    pub fn figment(path: Option<&Path>) -> Result<Figment, ConfigError> {
+        let path = match path {
+            Some(path) if !path.exists() => return Err(ConfigError::Missing(path.to_owned())),
+            Some(path) => path,
+            None => Path::new(DEFAULT_PATH),
+        };
+        Ok(Figment::from(Serialized::defaults(Config::default()))
+            .merge(Toml::file(path))
+            .merge(Env::prefixed(ENV_PREFIX).ignore(&["config"])))
+    }
+
+    /// Extracts and checks the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `provider` - The layered settings, typically from [`Config::figment`].
+    ///
+    /// # Returns
+    ///
+    /// Returns the `Config` if every setting is valid, or a `ConfigError` naming the
+    /// first one that is not.
+    pub fn load<P: Provider>(provider: P) -> Result<Self, ConfigError> {
+        let config: Config = Figment::from(provider).extract()?;
+        config.shortcode_generator()?;
+        if config.pool_size == 0 {
+            return Err(ConfigError::Invalid(
+                "pool_size",
+                "must be at least 1".into(),
+            ));
+        }
+        if config.maintenance_interval == 0 {
+            return Err(ConfigError::Invalid(
+                "maintenance_interval",
+                "must be at least 1 second".into(),
+            ));
+        }
+        if config.hit_flush_interval == 0 {
+            return Err(ConfigError::Invalid(
+                "hit_flush_interval",
+                "must be at least 1 second".into(),
+            ));
+        }
+        for (name, cookie) in [
+            ("password_cookie", &config.password_cookie),
+            ("edit_token_cookie", &config.edit_token_cookie),
+        ] {
+            let valid = !cookie.is_empty()
+                && cookie
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c));
+            if !valid {
+                return Err(ConfigError::Invalid(
+                    name,
+                    format!("{:?} is not a valid cookie name", cookie),
+                ));
+            }
+        }
+        if config.password_cookie == config.edit_token_cookie {
+            return Err(ConfigError::Invalid(
+                "edit_token_cookie",
+                "must differ from password_cookie".into(),
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Renders the configuration as TOML, in the format the configuration file is read in.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("configuration should serialize to TOML")
+    }
+
+    pub fn database_options(&self) -> DatabaseOptions {
+        DatabaseOptions {
+            pool_size: self.pool_size,
+            create_if_missing: true,
+            compress_above: self.compress_above,
+            shortcodes: self
+                .shortcode_generator()
+                .expect("shortcode settings are checked when loading the configuration"),
+        }
+    }
+
+    pub fn shortcode_generator(&self) -> Result<Generator, ClipError> {
+        match self.shortcode_mode {
+            shortcode::Mode::Random => {
+                Generator::random(self.shortcode_length, &self.shortcode_alphabet)
+            }
+            shortcode::Mode::Words => Generator::words(self.shortcode_words),
+        }
+    }
+
+    pub fn cookie_names(&self) -> CookieNames {
+        CookieNames {
+            password: self.password_cookie.clone(),
+            edit_token: self.edit_token_cookie.clone(),
+        }
+    }
+
+    pub fn maintenance_interval(&self) -> Duration {
+        Duration::from_secs(self.maintenance_interval)
+    }
+
+    pub fn hit_flush_interval(&self) -> Duration {
+        Duration::from_secs(self.hit_flush_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn figment(toml: &str) -> Figment {
+        Figment::from(Serialized::defaults(Config::default())).merge(Toml::string(toml))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = Config::load(Serialized::defaults(Config::default())).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.cookie_names(), CookieNames::default());
+        assert_eq!(config.shortcode_generator().unwrap(), Generator::default());
+    }
+
+    #[test]
+    fn test_layers() {
+        let file = figment(
+            r#"
+            pool_size = 4
+            web_rate = 12
+            shortcode_alphabet = "xyz"
+            "#,
+        );
+        let flags = Serialized::defaults(serde_json::json!({ "web_rate": 99 }));
+        let config = Config::load(file.merge(flags)).unwrap();
+        assert_eq!(config.pool_size, 4);
+        assert_eq!(config.web_rate, 99);
+        assert_eq!(config.shortcode_alphabet, "xyz");
+        assert_eq!(config.api_rate, Config::default().api_rate);
+    }
+
+    #[test]
+    fn test_word_shortcodes() {
+        let config =
+            Config::load(figment("shortcode_mode = \"words\"\nshortcode_words = 2")).unwrap();
+        assert_eq!(
+            config.shortcode_generator().unwrap(),
+            Generator::words(2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_to_toml_round_trips() {
+        let config = Config {
+            quota_clips: Some(7),
+            ..Default::default()
+        };
+        let parsed = Config::load(figment(&config.to_toml())).unwrap();
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn test_invalid() {
+        for toml in [
+            "pool_size = 0",
+            "maintenance_interval = 0",
+            "shortcode_length = 0",
+            "shortcode_alphabet = \"a\"",
+            "shortcode_mode = \"emoji\"",
+            "shortcode_mode = \"words\"\nshortcode_words = 0",
+            "password_cookie = \"pass word\"",
+            "edit_token_cookie = \"password\"",
+            "web_rate = \"fast\"",
+        ] {
+            assert!(Config::load(figment(toml)).is_err(), "{}", toml);
+        }
+    }
+
+    #[test]
+    fn test_missing_file() {
+        assert!(matches!(
+            Config::figment(Some(Path::new("/nonexistent/clipstash.toml"))),
+            Err(ConfigError::Missing(_))
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+//! Compression of clip content at rest.
+//!
+//! Content larger than the threshold set in `DatabaseOptions::compress_above` is stored
+//! zstd-compressed, with the
+//! `content_encoding` column of the row set to [`ZSTD`]. Rows without an encoding
+//! hold their content as plain text, so rows written before compression was
+//! introduced read as they always did.
+
+/// The marker stored in `content_encoding` for zstd-compressed content.
+pub const ZSTD: &str = "zstd";
+
+/// The size, in bytes, above which content is compressed unless configured otherwise.
+pub const DEFAULT_THRESHOLD: usize = 4096;
+
+/// Content as stored in the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredContent {
+    pub data: Vec<u8>,
+    pub encoding: Option<String>,
+}
+
+/// Prepares `content` for storage, compressing it if it is larger than `threshold`
+/// bytes and compression actually makes it smaller.
+pub fn encode(content: &str, threshold: usize) -> StoredContent {
+    if content.len() > threshold {
+        if let Ok(compressed) = zstd::encode_all(content.as_bytes(), 0) {
+            if compressed.len() < content.len() {
+                return StoredContent {
+                    data: compressed,
+                    encoding: Some(ZSTD.to_owned()),
+                };
+            }
+        }
+    }
+    StoredContent {
+        data: content.as_bytes().to_vec(),
+        encoding: None,
+    }
+}
+
+/// Restores the text of content read from the database.
+pub fn decode(data: Vec<u8>, encoding: Option<&str>) -> Result<String, String> {
+    let data = match encoding {
+        None => data,
+        Some(ZSTD) => zstd::decode_all(data.as_slice()).map_err(|e| e.to_string())?,
+        Some(other) => return Err(format!("unknown content encoding: {}", other)),
+    };
+    String::from_utf8(data).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_content_is_plain() {
+        let stored = encode("Hello, world!", 16);
+        assert_eq!(stored.encoding, None);
+        assert_eq!(stored.data, b"Hello, world!");
+        assert_eq!(decode(stored.data, None).unwrap(), "Hello, world!");
+    }
+
+    #[test]
+    fn test_large_content_is_compressed() {
+        let content = "GET /index.html 200\n".repeat(500);
+        let stored = encode(&content, 16);
+        assert_eq!(stored.encoding.as_deref(), Some(ZSTD));
+        assert!(stored.data.len() < content.len());
+        assert_eq!(decode(stored.data, Some(ZSTD)).unwrap(), content);
+    }
+
+    #[test]
+    fn test_incompressible_content_is_plain() {
+        let content: String = (0..64).map(|i| char::from(b'!' + i)).collect();
+        assert_eq!(encode(&content, 0).encoding, None);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(decode(b"abc".to_vec(), Some("gzip")).is_err());
+        assert!(decode(b"abc".to_vec(), Some(ZSTD)).is_err());
+        assert!(decode(vec![0xff, 0xfe], None).is_err());
+    }
+}
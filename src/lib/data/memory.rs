@@ -0,0 +1,895 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{NaiveDateTime, Utc};
+use parking_lot::Mutex;
+
+use super::{model, query::RevocationStatus, store::ClipStore, DataError, SHORTCODE_ATTEMPTS};
+use crate::{
+    domain::clip::{
+        field::shortcode::Generator,
+        views::{self, Agent, ViewSource, OTHER_REFERRER},
+        ClipSort, ListCursor, View, MATCH_END, MATCH_START,
+    },
+    domain::quota::{Quota, Usage},
+    web::api::{ApiKey, KeyDigest},
+    ShortCode,
+};
+
+type Result<T> = std::result::Result<T, DataError>;
+
+/// The number of words in a search snippet.
+const SNIPPET_WORDS: usize = 16;
+
+/// A `ClipStore` keeping everything in memory, for tests and ephemeral deployments.
+///
+/// Nothing is persisted: the data is gone once the store is dropped. Content is never
+/// compressed, and search matches every term as a word prefix, much like the full-text
+/// index of the SQLite store.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: Mutex<State>,
+    shortcodes: Generator,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty store drawing the shortcodes of new clips from `shortcodes`.
+    pub fn with_shortcodes(shortcodes: Generator) -> Self {
+        Self {
+            shortcodes,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    clips: HashMap<String, StoredClip>,
+    api_keys: HashMap<Vec<u8>, StoredKey>,
+}
+
+#[derive(Debug)]
+struct StoredClip {
+    clip_id: String,
+    shortcode: String,
+    content: String,
+    title: Option<String>,
+    posted: i64,
+    expires: Option<i64>,
+    password: Option<String>,
+    hits: i64,
+    edit_token: String,
+    max_views: Option<i64>,
+    views: i64,
+    language: Option<String>,
+    format: String,
+    api_key: Option<Vec<u8>>,
+    revisions: Vec<StoredRevision>,
+    attachments: Vec<model::NewAttachment>,
+    view_buckets: HashMap<ViewKey, i64>,
+}
+
+/// The hour, source, agent and referrer host views are counted under.
+type ViewKey = (i64, ViewSource, Agent, String);
+
+#[derive(Debug)]
+struct StoredRevision {
+    content: String,
+    title: Option<String>,
+    posted: i64,
+}
+
+#[derive(Debug)]
+struct StoredKey {
+    name: Option<String>,
+    created_at: i64,
+    last_used_at: Option<i64>,
+    expires_at: Option<i64>,
+    scopes: String,
+}
+
+fn now() -> i64 {
+    Utc::now().timestamp()
+}
+
+fn datetime(seconds: i64) -> NaiveDateTime {
+    NaiveDateTime::from_timestamp_opt(seconds, 0).unwrap_or_default()
+}
+
+fn not_found<T>() -> Result<T> {
+    Err(sqlx::Error::RowNotFound.into())
+}
+
+/// The id a key is listed under: the start of its digest in hex, as in the SQLite store.
+fn key_id(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .take(6)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl State {
+    /// What the live clips created with an API key store.
+    fn usage(&self, digest: &[u8]) -> model::Usage {
+        let now = now();
+        let owned: Vec<&StoredClip> = self
+            .clips
+            .values()
+            .filter(|clip| clip.api_key.as_deref() == Some(digest) && !clip.is_expired(now))
+            .collect();
+        model::Usage {
+            clips: owned.len() as i64,
+            bytes: owned
+                .iter()
+                .map(|clip| clip.content_bytes() + clip.attachment_bytes())
+                .sum(),
+        }
+    }
+}
+
+impl StoredClip {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires.map(|expires| now > expires).unwrap_or(false)
+    }
+
+    fn is_public(&self, now: i64) -> bool {
+        self.password.is_none() && self.max_views.is_none() && !self.is_expired(now)
+    }
+
+    fn content_bytes(&self) -> i64 {
+        self.content.len() as i64
+    }
+
+    fn attachment_bytes(&self) -> i64 {
+        self.attachments.iter().map(|a| a.size).sum()
+    }
+
+    /// The key clips are listed by, which must agree with `ClipSort::key`.
+    fn sort_key(&self, sort: ClipSort) -> i64 {
+        match sort {
+            ClipSort::Posted => -self.posted,
+            ClipSort::Hits => -self.hits,
+            ClipSort::Expires => self.expires.unwrap_or(i64::MAX),
+        }
+    }
+
+    fn to_model(&self) -> model::Clip {
+        model::Clip {
+            clip_id: self.clip_id.clone(),
+            shortcode: self.shortcode.clone(),
+            content: self.content.clone().into_bytes(),
+            title: self.title.clone(),
+            posted: datetime(self.posted),
+            expires: self.expires.map(datetime),
+            password: self.password.clone(),
+            hits: self.hits,
+            edit_token: Some(self.edit_token.clone()),
+            max_views: self.max_views,
+            views: self.views,
+            language: self.language.clone(),
+            format: self.format.clone(),
+            content_encoding: None,
+        }
+    }
+
+    fn revision(&self, index: usize) -> model::Revision {
+        let revision = &self.revisions[index];
+        model::Revision {
+            revision: index as i64 + 1,
+            content: revision.content.clone().into_bytes(),
+            title: revision.title.clone(),
+            posted: datetime(revision.posted),
+            content_encoding: None,
+        }
+    }
+}
+
+impl StoredKey {
+    fn to_model(&self, digest: &[u8]) -> model::KeyInfo {
+        model::KeyInfo {
+            id: key_id(digest),
+            name: self.name.clone(),
+            created_at: datetime(self.created_at),
+            last_used_at: self.last_used_at.map(datetime),
+            expires_at: self.expires_at.map(datetime),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+/// Splits text into the words search terms are matched against.
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+}
+
+fn matches_term(word: &str, terms: &[String]) -> bool {
+    words(word).any(|word| {
+        let word = word.to_lowercase();
+        terms.iter().any(|term| word.starts_with(term.as_str()))
+    })
+}
+
+/// Counts the words of `clip` matching a term, or returns `None` unless every term matches.
+fn match_count(clip: &StoredClip, terms: &[String]) -> Option<usize> {
+    let text = format!(
+        "{} {}",
+        clip.title.as_deref().unwrap_or_default(),
+        clip.content
+    );
+    let matched: Vec<String> = words(&text).map(str::to_lowercase).collect();
+    let all_match = terms
+        .iter()
+        .all(|term| matched.iter().any(|word| word.starts_with(term.as_str())));
+    all_match.then(|| {
+        matched
+            .iter()
+            .filter(|word| terms.iter().any(|term| word.starts_with(term.as_str())))
+            .count()
+    })
+}
+
+/// Excerpts the content around the first matching word, marking every matching word.
+fn snippet(content: &str, terms: &[String]) -> String {
+    let words: Vec<&str> = content.split_whitespace().collect();
+    let first = words
+        .iter()
+        .position(|word| matches_term(word, terms))
+        .unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_WORDS / 4);
+    let end = (start + SNIPPET_WORDS).min(words.len());
+    let mut parts: Vec<String> = words[start..end]
+        .iter()
+        .map(|word| {
+            if matches_term(word, terms) {
+                format!("{}{}{}", MATCH_START, word, MATCH_END)
+            } else {
+                word.to_string()
+            }
+        })
+        .collect();
+    if start > 0 {
+        parts.insert(0, "…".to_owned());
+    }
+    if end < words.len() {
+        parts.push("…".to_owned());
+    }
+    parts.join(" ")
+}
+
+#[rocket::async_trait]
+impl ClipStore for MemoryStore {
+    async fn get_clip(&self, shortcode: &ShortCode) -> Result<model::Clip> {
+        match self.state.lock().clips.get(shortcode.as_str()) {
+            Some(clip) => Ok(clip.to_model()),
+            None => not_found(),
+        }
+    }
+
+    async fn insert_clip(&self, clip: model::NewClip, quota: &Quota) -> Result<model::Clip> {
+        let mut state = self.state.lock();
+        if let Some(digest) = &clip.api_key {
+            let bytes =
+                clip.content.len() as i64 + clip.attachments.iter().map(|a| a.size).sum::<i64>();
+            quota.check(state.usage(digest).into(), 1, bytes as u64)?;
+        }
+        let mut shortcode = match &clip.shortcode {
+            Some(requested) => requested.clone(),
+            None => self.shortcodes.generate(),
+        };
+        let mut attempts = 1;
+        while state.clips.contains_key(&shortcode) {
+            if attempts == SHORTCODE_ATTEMPTS || clip.shortcode.is_some() {
+                return Err(DataError::ShortCodeTaken(shortcode));
+            }
+            shortcode = self.shortcodes.generate();
+            attempts += 1;
+        }
+        let stored = StoredClip {
+            clip_id: clip.clip_id,
+            shortcode: shortcode.clone(),
+            revisions: vec![StoredRevision {
+                content: clip.content.clone(),
+                title: clip.title.clone(),
+                posted: clip.posted,
+            }],
+            content: clip.content,
+            title: clip.title,
+            posted: clip.posted,
+            expires: clip.expires,
+            password: clip.password,
+            hits: 0,
+            edit_token: clip.edit_token,
+            max_views: clip.max_views.map(i64::from),
+            views: 0,
+            language: clip.language,
+            format: clip.format,
+            api_key: clip.api_key,
+            attachments: clip.attachments,
+            view_buckets: HashMap::new(),
+        };
+        let model = stored.to_model();
+        state.clips.insert(shortcode, stored);
+        Ok(model)
+    }
+
+    async fn update_clip(&self, clip: model::UpdateClip, quota: &Quota) -> Result<model::Clip> {
+        let mut state = self.state.lock();
+        let owner = match state.clips.get(&clip.shortcode) {
+            Some(stored) => stored
+                .api_key
+                .clone()
+                .map(|key| (key, stored.content_bytes())),
+            None => return not_found(),
+        };
+        if let Some((digest, old_bytes)) = owner {
+            let mut usage: Usage = state.usage(&digest).into();
+            usage.bytes = usage.bytes.saturating_sub(old_bytes as u64);
+            quota.check(usage, 0, clip.content.len() as u64)?;
+        }
+        let stored = match state.clips.get_mut(&clip.shortcode) {
+            Some(stored) => stored,
+            None => return not_found(),
+        };
+        stored.content = clip.content;
+        stored.title = clip.title;
+        stored.expires = clip.expires;
+        if !clip.keep_password {
+            stored.password = clip.password;
+        }
+        stored.revisions.push(StoredRevision {
+            content: stored.content.clone(),
+            title: stored.title.clone(),
+            posted: now(),
+        });
+        Ok(stored.to_model())
+    }
+
+    async fn delete_clip(&self, shortcode: &ShortCode) -> Result<()> {
+        match self.state.lock().clips.remove(shortcode.as_str()) {
+            Some(_) => Ok(()),
+            None => not_found(),
+        }
+    }
+
+    async fn delete_expired(&self) -> Result<u64> {
+        let now = now();
+        let mut state = self.state.lock();
+        let before = state.clips.len();
+        state.clips.retain(|_, clip| !clip.is_expired(now));
+        Ok((before - state.clips.len()) as u64)
+    }
+
+    async fn record_views(&self, views: &[(View, u32)]) -> Result<()> {
+        let mut state = self.state.lock();
+        let mut known: HashMap<ShortCode, HashSet<String>> = HashMap::new();
+        for (view, _) in views {
+            if let Some(clip) = state.clips.get(view.shortcode.as_str()) {
+                let hosts = clip
+                    .view_buckets
+                    .keys()
+                    .map(|(_, _, _, referrer)| referrer)
+                    .filter(|referrer| !referrer.is_empty() && *referrer != OTHER_REFERRER)
+                    .cloned();
+                known.insert(view.shortcode.clone(), hosts.collect());
+            }
+        }
+        let mut views = views.to_vec();
+        views::fold_referrers(&mut views, known);
+        for (view, count) in &views {
+            if let Some(clip) = state.clips.get_mut(view.shortcode.as_str()) {
+                clip.hits += i64::from(*count);
+                let key = (
+                    view.hour,
+                    view.source,
+                    view.agent,
+                    view.referrer.clone().unwrap_or_default(),
+                );
+                *clip.view_buckets.entry(key).or_insert(0) += i64::from(*count);
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_old_views(&self, before: i64) -> Result<u64> {
+        let mut state = self.state.lock();
+        let mut deleted = 0;
+        for clip in state.clips.values_mut() {
+            let kept = clip.view_buckets.len();
+            clip.view_buckets.retain(|(hour, ..), _| *hour >= before);
+            deleted += (kept - clip.view_buckets.len()) as u64;
+        }
+        Ok(deleted)
+    }
+
+    async fn clip_views(
+        &self,
+        shortcode: &ShortCode,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<model::ViewBucket>> {
+        let state = self.state.lock();
+        let clip = match state.clips.get(shortcode.as_str()) {
+            Some(clip) => clip,
+            None => return Ok(vec![]),
+        };
+        let mut buckets: Vec<_> = clip
+            .view_buckets
+            .iter()
+            .filter(|((hour, ..), _)| (from..=to).contains(hour))
+            .map(
+                |((hour, source, agent, referrer), views)| model::ViewBucket {
+                    hour: *hour,
+                    source: source.to_string(),
+                    agent: agent.to_string(),
+                    referrer: referrer.clone(),
+                    views: *views,
+                },
+            )
+            .collect();
+        buckets.sort_by_key(|bucket| bucket.hour);
+        Ok(buckets)
+    }
+
+    async fn record_view(&self, shortcode: &ShortCode) -> Result<()> {
+        let mut state = self.state.lock();
+        let clip = match state.clips.get_mut(shortcode.as_str()) {
+            Some(clip) => clip,
+            None => return not_found(),
+        };
+        match clip.max_views {
+            Some(max_views) if clip.views >= max_views => return not_found(),
+            _ => clip.views += 1,
+        }
+        if clip.max_views.map(|max| clip.views >= max).unwrap_or(false) {
+            state.clips.remove(shortcode.as_str());
+        }
+        Ok(())
+    }
+
+    async fn list_clips(
+        &self,
+        sort: ClipSort,
+        cursor: Option<&ListCursor>,
+        limit: u32,
+    ) -> Result<Vec<model::Clip>> {
+        let now = now();
+        let state = self.state.lock();
+        let mut clips: Vec<&StoredClip> = state
+            .clips
+            .values()
+            .filter(|clip| clip.is_public(now))
+            .filter(|clip| match cursor {
+                Some(cursor) => {
+                    (clip.sort_key(sort), clip.shortcode.as_str())
+                        > (cursor.key, cursor.shortcode.as_str())
+                }
+                None => true,
+            })
+            .collect();
+        clips.sort_by(|a, b| {
+            (a.sort_key(sort), &a.shortcode).cmp(&(b.sort_key(sort), &b.shortcode))
+        });
+        Ok(clips
+            .into_iter()
+            .take(limit as usize)
+            .map(StoredClip::to_model)
+            .collect())
+    }
+
+    async fn list_all_clips(&self, limit: u32) -> Result<Vec<model::Clip>> {
+        let state = self.state.lock();
+        let mut clips: Vec<&StoredClip> = state.clips.values().collect();
+        clips.sort_by(|a, b| (-a.posted, &a.shortcode).cmp(&(-b.posted, &b.shortcode)));
+        Ok(clips
+            .into_iter()
+            .take(limit as usize)
+            .map(StoredClip::to_model)
+            .collect())
+    }
+
+    async fn search_clips(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<model::SearchHit>> {
+        let terms: Vec<String> = words(query).map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+        let now = now();
+        let state = self.state.lock();
+        let mut hits: Vec<(usize, &StoredClip)> = state
+            .clips
+            .values()
+            .filter(|clip| clip.is_public(now))
+            .filter_map(|clip| match_count(clip, &terms).map(|count| (count, clip)))
+            .collect();
+        hits.sort_by(|(a_count, a), (b_count, b)| {
+            b_count
+                .cmp(a_count)
+                .then_with(|| a.shortcode.cmp(&b.shortcode))
+        });
+        Ok(hits
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .map(|(count, clip)| model::SearchHit {
+                shortcode: clip.shortcode.clone(),
+                title: clip.title.clone(),
+                snippet: snippet(&clip.content, &terms),
+                rank: -(count as f64),
+            })
+            .collect())
+    }
+
+    async fn list_revisions(&self, shortcode: &ShortCode) -> Result<Vec<model::Revision>> {
+        Ok(match self.state.lock().clips.get(shortcode.as_str()) {
+            Some(clip) => (0..clip.revisions.len())
+                .map(|index| clip.revision(index))
+                .collect(),
+            None => vec![],
+        })
+    }
+
+    async fn get_revision(&self, shortcode: &ShortCode, revision: u32) -> Result<model::Revision> {
+        let state = self.state.lock();
+        match state.clips.get(shortcode.as_str()) {
+            Some(clip) if revision >= 1 && revision as usize <= clip.revisions.len() => {
+                Ok(clip.revision(revision as usize - 1))
+            }
+            _ => not_found(),
+        }
+    }
+
+    async fn list_attachments(&self, shortcode: &ShortCode) -> Result<Vec<model::Attachment>> {
+        Ok(match self.state.lock().clips.get(shortcode.as_str()) {
+            Some(clip) => clip
+                .attachments
+                .iter()
+                .map(|attachment| model::Attachment {
+                    attachment_id: attachment.attachment_id.clone(),
+                    filename: attachment.filename.clone(),
+                    mime_type: attachment.mime_type.clone(),
+                    size: attachment.size,
+                })
+                .collect(),
+            None => vec![],
+        })
+    }
+
+    async fn get_attachment(
+        &self,
+        attachment: model::GetAttachment,
+    ) -> Result<model::AttachmentFile> {
+        let state = self.state.lock();
+        let file = state.clips.get(&attachment.shortcode).and_then(|clip| {
+            clip.attachments
+                .iter()
+                .find(|stored| stored.attachment_id == attachment.attachment_id)
+        });
+        match file {
+            Some(file) => Ok(model::AttachmentFile {
+                attachment_id: file.attachment_id.clone(),
+                filename: file.filename.clone(),
+                mime_type: file.mime_type.clone(),
+                size: file.size,
+                data: file.data.clone(),
+            }),
+            None => not_found(),
+        }
+    }
+
+    async fn key_usage(&self, digest: &KeyDigest) -> Result<model::Usage> {
+        Ok(self.state.lock().usage(&digest.clone().into_inner()))
+    }
+
+    async fn stats(&self) -> Result<model::Stats> {
+        let now = now();
+        let state = self.state.lock();
+        let clips = state.clips.values();
+        Ok(model::Stats {
+            clips: state.clips.len() as i64,
+            expired: clips.clone().filter(|clip| clip.is_expired(now)).count() as i64,
+            protected: clips.clone().filter(|clip| clip.password.is_some()).count() as i64,
+            content_bytes: clips.clone().map(StoredClip::content_bytes).sum(),
+            attachments: clips
+                .clone()
+                .map(|clip| clip.attachments.len() as i64)
+                .sum(),
+            attachment_bytes: clips.clone().map(StoredClip::attachment_bytes).sum(),
+            hits: clips.map(|clip| clip.hits).sum(),
+            api_keys: state.api_keys.len() as i64,
+        })
+    }
+
+    async fn save_api_key(&self, api_key: &ApiKey, details: model::NewApiKey) -> Result<()> {
+        let key = StoredKey {
+            name: details.name,
+            created_at: details.created_at,
+            last_used_at: None,
+            expires_at: details.expires_at,
+            scopes: details.scopes,
+        };
+        self.state
+            .lock()
+            .api_keys
+            .insert(api_key.digest().into_inner(), key);
+        Ok(())
+    }
+
+    async fn get_api_key(&self, api_key: &ApiKey) -> Result<model::KeyInfo> {
+        let digest = api_key.digest().into_inner();
+        match self.state.lock().api_keys.get(&digest) {
+            Some(key) => Ok(key.to_model(&digest)),
+            None => not_found(),
+        }
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<model::KeyInfo>> {
+        let state = self.state.lock();
+        let mut keys: Vec<(&Vec<u8>, &StoredKey)> = state.api_keys.iter().collect();
+        keys.sort_by(|(a_digest, a), (b_digest, b)| {
+            (a.created_at, a_digest).cmp(&(b.created_at, b_digest))
+        });
+        Ok(keys
+            .into_iter()
+            .map(|(digest, key)| key.to_model(digest))
+            .collect())
+    }
+
+    async fn touch_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        let now = now();
+        if let Some(key) = self
+            .state
+            .lock()
+            .api_keys
+            .get_mut(&api_key.digest().into_inner())
+        {
+            if key.last_used_at.map(|used| used < now - 60).unwrap_or(true) {
+                key.last_used_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+
+    async fn api_key_is_valid(&self, api_key: &ApiKey) -> Result<bool> {
+        let now = now();
+        Ok(self
+            .state
+            .lock()
+            .api_keys
+            .get(&api_key.digest().into_inner())
+            .map(|key| key.expires_at.map(|expires| now <= expires).unwrap_or(true))
+            .unwrap_or(false))
+    }
+
+    async fn revoke_api_key(&self, api_key: &ApiKey) -> Result<RevocationStatus> {
+        let digest = api_key.digest().into_inner();
+        Ok(match self.state.lock().api_keys.remove(&digest) {
+            Some(_) => RevocationStatus::Revoked,
+            None => RevocationStatus::NotFound,
+        })
+    }
+
+    async fn revoke_api_key_by_id(&self, id: &str) -> Result<RevocationStatus> {
+        let id = id.to_ascii_lowercase();
+        let mut state = self.state.lock();
+        let before = state.api_keys.len();
+        state.api_keys.retain(|digest, _| key_id(digest) != id);
+        Ok(if state.api_keys.len() < before {
+            RevocationStatus::Revoked
+        } else {
+            RevocationStatus::NotFound
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::clip::field::shortcode;
+    use crate::service::ask;
+
+    fn new_clip(shortcode: &str, content: &str) -> model::NewClip {
+        model::NewClip {
+            clip_id: crate::data::DbId::new().into(),
+            shortcode: Some(shortcode.to_owned()),
+            content: content.to_owned(),
+            title: None,
+            posted: now(),
+            expires: None,
+            password: None,
+            edit_token: "token".to_owned(),
+            max_views: None,
+            language: None,
+            format: "text".to_owned(),
+            attachments: vec![],
+            api_key: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_clips() {
+        let store = MemoryStore::new();
+        let shortcode = ShortCode::from("memory");
+        store
+            .insert_clip(new_clip("memory", "first"), &Quota::default())
+            .await
+            .unwrap();
+        assert!(matches!(
+            store
+                .insert_clip(new_clip("memory", "again"), &Quota::default())
+                .await,
+            Err(DataError::ShortCodeTaken(_))
+        ));
+        let drawn = model::NewClip {
+            shortcode: None,
+            ..new_clip("", "drawn")
+        };
+        let drawn = store.insert_clip(drawn, &Quota::default()).await.unwrap();
+        assert_eq!(drawn.shortcode.len(), shortcode::DEFAULT_LENGTH);
+
+        let update = model::UpdateClip {
+            shortcode: "memory".to_owned(),
+            content: "second".to_owned(),
+            title: Some("Title".to_owned()),
+            expires: None,
+            password: None,
+            keep_password: true,
+        };
+        let clip: crate::Clip = store
+            .update_clip(update, &Quota::default())
+            .await
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(clip.content.as_str(), "second");
+        let revisions = store.list_revisions(&shortcode).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(
+            store.get_revision(&shortcode, 1).await.unwrap().content,
+            b"first"
+        );
+        assert!(store.get_revision(&shortcode, 3).await.is_err());
+
+        let view = View::new(shortcode.clone(), ViewSource::Raw, Agent::Cli, None);
+        let gone = View::new(ShortCode::from("gone"), ViewSource::Web, Agent::Bot, None);
+        store
+            .record_views(&[(view.clone(), 3), (gone, 1)])
+            .await
+            .unwrap();
+        assert_eq!(store.get_clip(&shortcode).await.unwrap().hits, 3);
+        let buckets = store
+            .clip_views(&shortcode, view.hour, view.hour)
+            .await
+            .unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!((buckets[0].source.as_str(), buckets[0].views), ("raw", 3));
+        assert_eq!(store.delete_old_views(view.hour).await.unwrap(), 0);
+        assert_eq!(store.delete_old_views(view.hour + 3600).await.unwrap(), 1);
+        assert!(store
+            .clip_views(&shortcode, view.hour, view.hour)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let mut expired = new_clip("expired", "gone");
+        expired.expires = Some(0);
+        store.insert_clip(expired, &Quota::default()).await.unwrap();
+        assert_eq!(store.stats().await.unwrap().expired(), 1);
+        assert_eq!(store.delete_expired().await.unwrap(), 1);
+
+        store.delete_clip(&shortcode).await.unwrap();
+        assert!(matches!(
+            store.get_clip(&shortcode).await,
+            Err(DataError::Database(sqlx::Error::RowNotFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_record_view() {
+        let store = MemoryStore::new();
+        let shortcode = ShortCode::from("limited");
+        let mut clip = new_clip("limited", "once");
+        clip.max_views = Some(2);
+        store.insert_clip(clip, &Quota::default()).await.unwrap();
+
+        store.record_view(&shortcode).await.unwrap();
+        assert_eq!(store.get_clip(&shortcode).await.unwrap().views, 1);
+        store.record_view(&shortcode).await.unwrap();
+        assert!(store.get_clip(&shortcode).await.is_err());
+        assert!(store.record_view(&shortcode).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_and_search() {
+        let store = MemoryStore::new();
+        let mut older = new_clip("older", "the quick brown fox");
+        older.posted -= 10;
+        store.insert_clip(older, &Quota::default()).await.unwrap();
+        store
+            .insert_clip(new_clip("newer", "a lazy dog, quickly"), &Quota::default())
+            .await
+            .unwrap();
+        let mut protected = new_clip("protected", "quick secret");
+        protected.password = Some("hash".to_owned());
+        store
+            .insert_clip(protected, &Quota::default())
+            .await
+            .unwrap();
+
+        let page = store.list_clips(ClipSort::Posted, None, 1).await.unwrap();
+        assert_eq!(page[0].shortcode, "newer");
+        let clip: crate::Clip = page.into_iter().next().unwrap().try_into().unwrap();
+        let cursor = ListCursor::after(&clip, ClipSort::Posted);
+        let page = store
+            .list_clips(ClipSort::Posted, Some(&cursor), 10)
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].shortcode, "older");
+
+        let hits = store.search_clips("QUICK", 10, 0).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        let hits = store.search_clips("quick fox", 10, 0).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].snippet,
+            format!("the {0}quick{1} brown {0}fox{1}", MATCH_START, MATCH_END)
+        );
+        assert!(store
+            .search_clips("secret", 10, 0)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(store.search_clips(" ", 10, 0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_api_keys() {
+        let store = MemoryStore::new();
+        let api_key = ApiKey::default();
+        let req = ask::NewApiKey {
+            name: Some("ci".to_owned()),
+            ..Default::default()
+        };
+        store.save_api_key(&api_key, req.into()).await.unwrap();
+        assert!(store.api_key_is_valid(&api_key).await.unwrap());
+        assert!(!store.api_key_is_valid(&ApiKey::default()).await.unwrap());
+
+        store.touch_api_key(&api_key).await.unwrap();
+        let info = store.get_api_key(&api_key).await.unwrap();
+        assert!(info.last_used_at.is_some());
+        assert_eq!(info.id, key_id(&api_key.digest().into_inner()));
+
+        let mut clip = new_clip("owned", "12345");
+        clip.api_key = Some(api_key.digest().into_inner());
+        store.insert_clip(clip, &Quota::default()).await.unwrap();
+        let usage = store.key_usage(&api_key.digest()).await.unwrap();
+        assert_eq!((usage.clips, usage.bytes), (1, 5));
+
+        let quota = Quota {
+            max_clips: Some(1),
+            max_bytes: None,
+        };
+        let mut clip = new_clip("over", "12345");
+        clip.api_key = Some(api_key.digest().into_inner());
+        assert!(matches!(
+            store.insert_clip(clip, &quota).await,
+            Err(DataError::Quota(_))
+        ));
+        assert!(store.get_clip(&ShortCode::from("over")).await.is_err());
+
+        assert!(matches!(
+            store.revoke_api_key_by_id(&info.id).await.unwrap(),
+            RevocationStatus::Revoked
+        ));
+        assert!(store.list_api_keys().await.unwrap().is_empty());
+    }
+}
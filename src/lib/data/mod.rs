@@ -1,17 +1,37 @@
+pub mod compression;
+pub mod memory;
 pub mod model;
 pub mod query;
+pub mod store;
+
+pub use memory::MemoryStore;
+pub use store::{ClipStore, Store};
 
 use std::str::FromStr;
+use std::time::Duration;
 
 use derive_more::{Display, From};
 use serde::{Deserialize, Serialize};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions};
 use sqlx::Sqlite;
 use uuid::Uuid;
 
+use crate::domain::clip::field::shortcode::Generator;
+use crate::domain::quota::QuotaError;
+
 #[derive(Debug, thiserror::Error)]
 pub enum DataError {
     #[error("database error: {0}")]
     Database(#[from] sqlx::Error),
+    #[error("shortcode already taken: {0}")]
+    ShortCodeTaken(String),
+    #[error("migration error: {0}")]
+    Migrate(#[from] sqlx::migrate::MigrateError),
+    #[error("database schema version {database} is newer than this binary supports ({binary}); upgrade clipstash")]
+    SchemaTooNew { database: i64, binary: i64 },
+    #[error("{0}")]
+    Quota(#[from] QuotaError),
 }
 
 pub type AppDatabase = Database<Sqlite>;
@@ -20,27 +40,122 @@ pub type Transaction<'t> = sqlx::Transaction<'t, Sqlite>;
 pub type AppDatabaseRow = sqlx::sqlite::SqliteRow;
 pub type AppQueryResult = sqlx::sqlite::SqliteQueryResult;
 
-pub struct Database<D: sqlx::Database>(sqlx::Pool<D>);
+/// How many generated shortcodes are tried before giving up on inserting a clip.
+pub const SHORTCODE_ATTEMPTS: u32 = 5;
+
+/// The migrations in `migrations/`, embedded in the binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// How long a connection waits on a locked database before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Options for connecting to the database.
+#[derive(Clone, Debug)]
+pub struct DatabaseOptions {
+    /// The maximum number of pooled connections.
+    pub pool_size: u32,
+    /// Whether to create the database file if it does not exist.
+    pub create_if_missing: bool,
+    /// Clip content larger than this many bytes is stored compressed.
+    pub compress_above: usize,
+    /// The generator the shortcodes of new clips are drawn from.
+    pub shortcodes: Generator,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        Self {
+            pool_size: 10,
+            create_if_missing: false,
+            compress_above: compression::DEFAULT_THRESHOLD,
+            shortcodes: Generator::default(),
+        }
+    }
+}
+
+pub struct Database<D: sqlx::Database> {
+    pool: sqlx::Pool<D>,
+    compress_above: usize,
+    shortcodes: Generator,
+}
 
 impl Database<Sqlite> {
     pub async fn new(connection_str: &str) -> Self {
-        let pool = sqlx::sqlite::SqlitePoolOptions::new()
-            .connect(connection_str)
-            .await;
-        match pool {
-            Ok(pool) => Self(pool),
+        match Self::connect(connection_str, DatabaseOptions::default()).await {
+            Ok(database) => database,
             Err(e) => {
                 eprintln!("{}\n", e);
-                eprintln!(
-                    "If the database has not yet been created, run: \n   $ sqlx database setup\n"
-                );
+                eprintln!("If the database has not yet been created, start httpd to create it.\n");
                 panic!("database connection error");
             }
         }
     }
 
+    /// Connects to the database in write-ahead logging mode, waiting on locks for up to
+    /// `BUSY_TIMEOUT`.
+    pub async fn connect(
+        connection_str: &str,
+        options: DatabaseOptions,
+    ) -> Result<Self, DataError> {
+        let connect_options = SqliteConnectOptions::from_str(connection_str)?
+            .create_if_missing(options.create_if_missing)
+            .journal_mode(SqliteJournalMode::Wal)
+            .busy_timeout(BUSY_TIMEOUT);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(options.pool_size)
+            .connect_with(connect_options)
+            .await?;
+        Ok(Self {
+            pool,
+            compress_above: options.compress_above,
+            shortcodes: options.shortcodes,
+        })
+    }
+
+    /// Applies the embedded migrations the database has not seen yet.
+    ///
+    /// Fails without touching the database if its schema is newer than the binary.
+    pub async fn migrate(&self) -> Result<(), DataError> {
+        self.check_schema().await?;
+        MIGRATOR.run(self.get_pool()).await?;
+        Ok(())
+    }
+
+    /// Checks that the database schema is not newer than the embedded migrations.
+    pub async fn check_schema(&self) -> Result<(), DataError> {
+        let has_migrations: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+        )
+        .fetch_one(self.get_pool())
+        .await?;
+        if !has_migrations {
+            return Ok(());
+        }
+        let database: Option<i64> =
+            sqlx::query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+                .fetch_one(self.get_pool())
+                .await?;
+        let binary = MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0);
+        match database {
+            Some(database) if database > binary => {
+                Err(DataError::SchemaTooNew { database, binary })
+            }
+            _ => Ok(()),
+        }
+    }
+
     pub fn get_pool(&self) -> &DatabasePool {
-        &self.0
+        &self.pool
+    }
+
+    /// The size, in bytes, above which clip content is stored compressed.
+    pub fn compress_above(&self) -> usize {
+        self.compress_above
+    }
+
+    /// The generator the shortcodes of new clips are drawn from.
+    pub fn shortcodes(&self) -> &Generator {
+        &self.shortcodes
     }
 }
 
@@ -116,15 +231,41 @@ mod tests {
     }
 
     pub async fn _new_db() -> AppDatabase {
-        use sqlx::migrate::Migrator;
-        use std::path::Path;
         let db = Database::new(":memory:").await;
-        let migrator = Migrator::new(Path::new("./migrations")).await.unwrap();
-        let pool = db.get_pool();
-        migrator.run(pool).await.unwrap();
+        db.migrate().await.unwrap();
         db
     }
 
+    #[tokio::test]
+    async fn test_migrate() {
+        let db = _new_db().await;
+        db.migrate().await.unwrap();
+        let applied: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations")
+            .fetch_one(db.get_pool())
+            .await
+            .unwrap();
+        assert_eq!(applied, MIGRATOR.iter().count() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_schema_too_new() {
+        let db = _new_db().await;
+        sqlx::query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+             VALUES (99990101000000, 'from the future', TRUE, x'00', 0)",
+        )
+        .execute(db.get_pool())
+        .await
+        .unwrap();
+        assert!(matches!(
+            db.migrate().await,
+            Err(DataError::SchemaTooNew {
+                database: 99990101000000,
+                ..
+            })
+        ));
+    }
+
     #[tokio::test]
     async fn test_database_query_simple() {
         // Test that a query can be executed on the database
@@ -2,19 +2,27 @@ use std::convert::TryFrom;
 
 use chrono::{NaiveDateTime, Utc};
 
-use crate::data::DbId;
+use crate::data::{compression, DbId};
+use crate::domain::clip::field::{EditToken, Language, PasswordHash};
+use crate::web::api::scope::{Scope, Scopes};
 use crate::{ClipError, ShortCode, Time};
 
 #[derive(Debug, sqlx::FromRow)]
 pub struct Clip {
     pub(in crate::data) clip_id: String,
     pub(in crate::data) shortcode: String,
-    pub(in crate::data) content: String,
+    pub(in crate::data) content: Vec<u8>,
     pub(in crate::data) title: Option<String>,
     pub(in crate::data) posted: NaiveDateTime,
     pub(in crate::data) expires: Option<NaiveDateTime>,
     pub(in crate::data) password: Option<String>,
     pub(in crate::data) hits: i64,
+    pub(in crate::data) edit_token: Option<String>,
+    pub(in crate::data) max_views: Option<i64>,
+    pub(in crate::data) views: i64,
+    pub(in crate::data) language: Option<String>,
+    pub(in crate::data) format: String,
+    pub(in crate::data) content_encoding: Option<String>,
 }
 
 impl TryFrom<Clip> for crate::domain::Clip {
@@ -27,16 +35,186 @@ impl TryFrom<Clip> for crate::domain::Clip {
         Ok(Self {
             clip_id: field::ClipId::new(DbId::from_str(clip.clip_id.as_str())?),
             shortcode: field::ShortCode::from(clip.shortcode),
-            content: field::Content::new(clip.content.as_str())?,
+            content: field::Content::new(&decode_content(clip.content, clip.content_encoding)?)?,
             title: field::Title::new(clip.title),
             posted: field::Posted::new(Time::from_naive_utc(clip.posted)),
             expires: field::Expires::new(clip.expires.map(Time::from_naive_utc)),
-            password: field::Password::new(clip.password.unwrap_or_default())?,
+            password: field::PasswordHash::new(clip.password),
             hits: field::Hits::new(u64::try_from(clip.hits)?),
+            edit_token: field::EditToken::new(clip.edit_token),
+            max_views: field::MaxViews::new(clip.max_views.map(u32::try_from).transpose()?)?,
+            views: field::Views::new(u64::try_from(clip.views)?),
+            language: field::Language::new(clip.language)?,
+            format: field::Format::new(&clip.format)?,
+        })
+    }
+}
+
+fn decode_content(data: Vec<u8>, encoding: Option<String>) -> Result<String, ClipError> {
+    compression::decode(data, encoding.as_deref()).map_err(ClipError::ContentEncoding)
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Revision {
+    pub(in crate::data) revision: i64,
+    pub(in crate::data) content: Vec<u8>,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) posted: NaiveDateTime,
+    pub(in crate::data) content_encoding: Option<String>,
+}
+
+impl TryFrom<Revision> for crate::domain::clip::Revision {
+    type Error = ClipError;
+
+    fn try_from(revision: Revision) -> Result<Self, Self::Error> {
+        use crate::domain::clip::field;
+
+        Ok(Self {
+            revision: u32::try_from(revision.revision)?,
+            content: field::Content::new(&decode_content(
+                revision.content,
+                revision.content_encoding,
+            )?)?,
+            title: field::Title::new(revision.title),
+            posted: field::Posted::new(Time::from_naive_utc(revision.posted)),
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Attachment {
+    pub(in crate::data) attachment_id: String,
+    pub(in crate::data) filename: String,
+    pub(in crate::data) mime_type: String,
+    pub(in crate::data) size: i64,
+}
+
+impl TryFrom<Attachment> for crate::domain::clip::Attachment {
+    type Error = ClipError;
+
+    fn try_from(attachment: Attachment) -> Result<Self, Self::Error> {
+        use crate::domain::clip::field;
+        use std::str::FromStr;
+
+        Ok(Self {
+            attachment_id: field::AttachmentId::from_str(&attachment.attachment_id)?,
+            filename: field::FileName::new(&attachment.filename)?,
+            mime_type: field::MimeType::new(&attachment.mime_type)?,
+            size: u64::try_from(attachment.size)?,
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ViewBucket {
+    pub(in crate::data) hour: i64,
+    pub(in crate::data) source: String,
+    pub(in crate::data) agent: String,
+    pub(in crate::data) referrer: String,
+    pub(in crate::data) views: i64,
+}
+
+impl TryFrom<ViewBucket> for crate::domain::clip::ViewBucket {
+    type Error = ClipError;
+
+    fn try_from(bucket: ViewBucket) -> Result<Self, Self::Error> {
+        use std::str::FromStr;
+
+        let invalid = |what: &str| ClipError::InvalidViews(format!("unknown {}", what));
+        Ok(Self {
+            hour: bucket.hour,
+            source: FromStr::from_str(&bucket.source).map_err(|_| invalid(&bucket.source))?,
+            agent: FromStr::from_str(&bucket.agent).map_err(|_| invalid(&bucket.agent))?,
+            referrer: Some(bucket.referrer).filter(|referrer| !referrer.is_empty()),
+            views: u64::try_from(bucket.views)?,
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct AttachmentFile {
+    pub(in crate::data) attachment_id: String,
+    pub(in crate::data) filename: String,
+    pub(in crate::data) mime_type: String,
+    pub(in crate::data) size: i64,
+    pub(in crate::data) data: Vec<u8>,
+}
+
+impl TryFrom<AttachmentFile> for crate::domain::clip::AttachmentFile {
+    type Error = ClipError;
+
+    fn try_from(file: AttachmentFile) -> Result<Self, Self::Error> {
+        let attachment = Attachment {
+            attachment_id: file.attachment_id,
+            filename: file.filename,
+            mime_type: file.mime_type,
+            size: file.size,
+        };
+        Ok(Self {
+            attachment: attachment.try_into()?,
+            data: file.data,
         })
     }
 }
 
+#[derive(Debug)]
+pub struct NewAttachment {
+    pub(in crate::data) attachment_id: String,
+    pub(in crate::data) filename: String,
+    pub(in crate::data) mime_type: String,
+    pub(in crate::data) size: i64,
+    pub(in crate::data) data: Vec<u8>,
+}
+
+impl TryFrom<crate::service::ask::NewAttachment> for NewAttachment {
+    type Error = ClipError;
+
+    fn try_from(req: crate::service::ask::NewAttachment) -> Result<Self, Self::Error> {
+        Ok(Self {
+            attachment_id: DbId::new().into(),
+            filename: req.filename.into_inner(),
+            mime_type: req.mime_type.into_inner(),
+            size: i64::try_from(req.data.len())?,
+            data: req.data,
+        })
+    }
+}
+
+pub struct GetAttachment {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) attachment_id: String,
+}
+
+impl From<crate::service::ask::GetAttachment> for GetAttachment {
+    fn from(req: crate::service::ask::GetAttachment) -> Self {
+        Self {
+            shortcode: req.shortcode.into_inner(),
+            attachment_id: req.attachment_id.into_inner().into(),
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct SearchHit {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) title: Option<String>,
+    pub(in crate::data) snippet: String,
+    pub(in crate::data) rank: f64,
+}
+
+impl From<SearchHit> for crate::domain::clip::SearchHit {
+    fn from(hit: SearchHit) -> Self {
+        use crate::domain::clip::field;
+
+        Self {
+            shortcode: field::ShortCode::from(hit.shortcode),
+            title: field::Title::new(hit.title),
+            snippet: hit.snippet,
+            rank: hit.rank,
+        }
+    }
+}
+
 pub struct GetClip {
     pub(in crate::data) shortcode: String,
 }
@@ -63,27 +241,74 @@ impl From<ShortCode> for GetClip {
     }
 }
 
+pub struct DeleteClip {
+    pub(in crate::data) shortcode: String,
+}
+
+impl From<crate::service::ask::DeleteClip> for DeleteClip {
+    fn from(req: crate::service::ask::DeleteClip) -> Self {
+        Self {
+            shortcode: req.shortcode.into_inner(),
+        }
+    }
+}
+
+impl From<ShortCode> for DeleteClip {
+    fn from(shortcode: ShortCode) -> Self {
+        DeleteClip {
+            shortcode: shortcode.into_inner(),
+        }
+    }
+}
+
 pub struct NewClip {
     pub(in crate::data) clip_id: String,
-    pub(in crate::data) shortcode: String,
+    /// The requested shortcode, or `None` to have the store draw one.
+    pub(in crate::data) shortcode: Option<String>,
     pub(in crate::data) content: String,
     pub(in crate::data) title: Option<String>,
     pub(in crate::data) posted: i64,
     pub(in crate::data) expires: Option<i64>,
     pub(in crate::data) password: Option<String>,
+    pub(in crate::data) edit_token: String,
+    pub(in crate::data) max_views: Option<u32>,
+    pub(in crate::data) language: Option<String>,
+    pub(in crate::data) format: String,
+    pub(in crate::data) attachments: Vec<NewAttachment>,
+    pub(in crate::data) api_key: Option<Vec<u8>>,
 }
 
-impl From<crate::service::ask::NewClip> for NewClip {
-    fn from(req: crate::service::ask::NewClip) -> Self {
-        Self {
+impl TryFrom<crate::service::ask::NewClip> for NewClip {
+    type Error = ClipError;
+
+    fn try_from(req: crate::service::ask::NewClip) -> Result<Self, Self::Error> {
+        req.expires.check_future()?;
+        let language = if req.language.is_set() {
+            req.language
+        } else {
+            Language::detect(req.content.as_str())
+        };
+        Ok(Self {
             clip_id: DbId::new().into(),
             content: req.content.into_inner(),
             title: req.title.into_inner(),
             expires: req.expires.into_inner().map(|time| time.timestamp()),
-            password: req.password.into_inner(),
-            shortcode: ShortCode::default().into(),
+            password: PasswordHash::from_password(&req.password)?.into_inner(),
+            shortcode: req.shortcode.into_inner().map(String::from),
             posted: Utc::now().timestamp(),
-        }
+            edit_token: EditToken::generate()
+                .into_inner()
+                .expect("generated edit token should have a value"),
+            max_views: req.max_views.into_inner(),
+            language: language.into_inner(),
+            format: req.format.to_string(),
+            attachments: req
+                .attachments
+                .into_iter()
+                .map(NewAttachment::try_from)
+                .collect::<Result<_, _>>()?,
+            api_key: req.api_key.map(|api_key| api_key.digest().into_inner()),
+        })
     }
 }
 
@@ -93,20 +318,198 @@ pub struct UpdateClip {
     pub(in crate::data) title: Option<String>,
     pub(in crate::data) expires: Option<i64>,
     pub(in crate::data) password: Option<String>,
+    pub(in crate::data) keep_password: bool,
 }
 
-impl From<crate::service::ask::UpdateClip> for UpdateClip {
-    fn from(req: crate::service::ask::UpdateClip) -> Self {
-        Self {
+impl TryFrom<crate::service::ask::UpdateClip> for UpdateClip {
+    type Error = ClipError;
+
+    fn try_from(req: crate::service::ask::UpdateClip) -> Result<Self, Self::Error> {
+        req.expires.check_future()?;
+        let password = if req.keep_password {
+            PasswordHash::default()
+        } else {
+            PasswordHash::from_password(&req.password)?
+        };
+        Ok(Self {
             content: req.content.into_inner(),
             title: req.title.into_inner(),
             expires: req.expires.into_inner().map(|time| time.timestamp()),
-            password: req.password.into_inner(),
-            shortcode: ShortCode::default().into(),
+            password: password.into_inner(),
+            keep_password: req.keep_password,
+            shortcode: req.shortcode.into_inner(),
+        })
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct StoredPassword {
+    pub(in crate::data) shortcode: String,
+    pub(in crate::data) password: String,
+}
+
+impl StoredPassword {
+    pub fn shortcode(&self) -> ShortCode {
+        ShortCode::from(self.shortcode.as_str())
+    }
+
+    pub fn is_plaintext(&self) -> bool {
+        !PasswordHash::is_hash(&self.password)
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct KeyInfo {
+    pub(in crate::data) id: String,
+    pub(in crate::data) name: Option<String>,
+    pub(in crate::data) created_at: NaiveDateTime,
+    pub(in crate::data) last_used_at: Option<NaiveDateTime>,
+    pub(in crate::data) expires_at: Option<NaiveDateTime>,
+    pub(in crate::data) scopes: String,
+}
+
+impl From<KeyInfo> for crate::web::api::KeyInfo {
+    fn from(api_key: KeyInfo) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            created_at: Time::from_naive_utc(api_key.created_at),
+            last_used_at: api_key.last_used_at.map(Time::from_naive_utc),
+            expires_at: api_key.expires_at.map(Time::from_naive_utc),
+            // Scopes this version does not know about grant nothing.
+            scopes: Scopes::new(
+                api_key
+                    .scopes
+                    .split_whitespace()
+                    .filter_map(|scope| scope.parse::<Scope>().ok()),
+            ),
+        }
+    }
+}
+
+pub struct NewApiKey {
+    pub(in crate::data) name: Option<String>,
+    pub(in crate::data) created_at: i64,
+    pub(in crate::data) expires_at: Option<i64>,
+    pub(in crate::data) scopes: String,
+}
+
+impl From<crate::service::ask::NewApiKey> for NewApiKey {
+    fn from(req: crate::service::ask::NewApiKey) -> Self {
+        Self {
+            name: req.name,
+            created_at: Utc::now().timestamp(),
+            expires_at: req.expires.into_inner().map(|time| time.timestamp()),
+            scopes: req.scopes.to_string(),
         }
     }
 }
 
+/// What the live clips of an API key store.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Usage {
+    pub(in crate::data) clips: i64,
+    pub(in crate::data) bytes: i64,
+}
+
+impl From<Usage> for crate::domain::quota::Usage {
+    fn from(usage: Usage) -> Self {
+        Self {
+            clips: u64::try_from(usage.clips).unwrap_or_default(),
+            bytes: u64::try_from(usage.bytes).unwrap_or_default(),
+        }
+    }
+}
+
+/// The outcome of recompressing the stored content of every clip and revision.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Recompression {
+    pub(in crate::data) rewritten: u64,
+    pub(in crate::data) bytes_before: u64,
+    pub(in crate::data) bytes_after: u64,
+}
+
+impl Recompression {
+    /// The number of rows whose stored content changed.
+    pub fn rewritten(&self) -> u64 {
+        self.rewritten
+    }
+
+    /// The size of all stored content before recompressing, in bytes.
+    pub fn bytes_before(&self) -> u64 {
+        self.bytes_before
+    }
+
+    /// The size of all stored content after recompressing, in bytes.
+    pub fn bytes_after(&self) -> u64 {
+        self.bytes_after
+    }
+
+    /// The number of bytes saved, negative if content grew by being stored uncompressed.
+    pub fn bytes_saved(&self) -> i64 {
+        self.bytes_before as i64 - self.bytes_after as i64
+    }
+}
+
+/// Totals over the whole database.
+#[derive(Debug, sqlx::FromRow)]
+pub struct Stats {
+    pub(in crate::data) clips: i64,
+    pub(in crate::data) expired: i64,
+    pub(in crate::data) protected: i64,
+    pub(in crate::data) content_bytes: i64,
+    pub(in crate::data) attachments: i64,
+    pub(in crate::data) attachment_bytes: i64,
+    pub(in crate::data) hits: i64,
+    pub(in crate::data) api_keys: i64,
+}
+
+impl Stats {
+    /// The number of stored clips, including expired ones not yet purged.
+    pub fn clips(&self) -> u64 {
+        u64::try_from(self.clips).unwrap_or_default()
+    }
+
+    /// The number of expired clips waiting to be purged.
+    pub fn expired(&self) -> u64 {
+        u64::try_from(self.expired).unwrap_or_default()
+    }
+
+    /// The number of password-protected clips.
+    pub fn protected(&self) -> u64 {
+        u64::try_from(self.protected).unwrap_or_default()
+    }
+
+    /// The stored size of clip content, after compression, in bytes.
+    pub fn content_bytes(&self) -> u64 {
+        u64::try_from(self.content_bytes).unwrap_or_default()
+    }
+
+    /// The number of attached files.
+    pub fn attachments(&self) -> u64 {
+        u64::try_from(self.attachments).unwrap_or_default()
+    }
+
+    /// The size of all attached files, in bytes.
+    pub fn attachment_bytes(&self) -> u64 {
+        u64::try_from(self.attachment_bytes).unwrap_or_default()
+    }
+
+    /// The hits of all stored clips.
+    pub fn hits(&self) -> u64 {
+        u64::try_from(self.hits).unwrap_or_default()
+    }
+
+    /// The number of API keys.
+    pub fn api_keys(&self) -> u64 {
+        u64::try_from(self.api_keys).unwrap_or_default()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,12 +522,18 @@ mod tests {
         let clip = Clip {
             clip_id: id_str.to_string(),
             shortcode: "abc123".to_string(),
-            content: "Hello, world!".to_string(),
+            content: b"Hello, world!".to_vec(),
             title: Some("Test Clip".to_string()),
             posted: NaiveDateTime::from_timestamp_opt(862070800, 0).unwrap(),
             expires: NaiveDateTime::from_timestamp_opt(862060800, 0),
             password: Some("password".to_string()),
             hits: 10,
+            edit_token: Some("token".to_string()),
+            max_views: Some(1),
+            views: 0,
+            language: Some("rust".to_string()),
+            format: "markdown".to_string(),
+            content_encoding: None,
         };
 
         let result = crate::domain::Clip::try_from(clip).unwrap();
@@ -145,10 +554,27 @@ mod tests {
                 NaiveDateTime::from_timestamp_opt(862060800, 0).unwrap()
             ))
         );
-        assert_eq!(
-            result.password,
-            Password::new("password".to_string()).unwrap()
-        );
+        assert_eq!(result.password, PasswordHash::new("password".to_string()));
         assert_eq!(result.hits, Hits::new(10));
+        assert_eq!(result.edit_token, EditToken::new("token".to_string()));
+        assert_eq!(result.max_views, MaxViews::new(1).unwrap());
+        assert_eq!(result.views, Views::new(0));
+        assert_eq!(result.language, Language::new("rust".to_string()).unwrap());
+        assert_eq!(result.format, Format::Markdown);
+    }
+
+    #[test]
+    fn test_try_from_new_clip_rejects_past_expiry() {
+        let req: crate::service::ask::NewClip = serde_json::from_value(serde_json::json!({
+            "content": "expired",
+            "title": null,
+            "expires": "1997-05-01T00:00:00Z",
+            "password": null,
+        }))
+        .unwrap();
+        assert!(matches!(
+            NewClip::try_from(req),
+            Err(ClipError::InvalidDate(_))
+        ));
     }
 }
@@ -1,43 +1,208 @@
+use std::collections::{HashMap, HashSet};
+
 use sqlx::Row;
 
 use super::model;
 
 use crate::{
-    data::{DataError, DatabasePool},
-    web::api::ApiKey,
+    data::{compression, DataError, DatabasePool, Transaction, SHORTCODE_ATTEMPTS},
+    domain::clip::{
+        field::shortcode::Generator,
+        field::PasswordHash,
+        views::{self, OTHER_REFERRER},
+        ClipSort, ListCursor, View,
+    },
+    domain::quota::{Quota, Usage},
+    web::api::{ApiKey, KeyDigest},
     ShortCode,
 };
 
 type Result<T> = std::result::Result<T, DataError>;
 
-/// Increases the hit count for a given shortcode in the database.
+/// Records views of clips, adding them to the hit counts and the hourly view statistics
+/// of the clips in one transaction.
 ///
-/// This function updates the `hits` field of a clip with the specified `shortcode`
-/// by adding the provided `hits` value to the existing hit count.
+/// The views are passed to SQLite as JSON, so each statement stays the same however many
+/// clips were viewed. Views of clips that are gone are skipped, and views
+/// from hosts beyond the referrers a clip has room for are folded together.
 ///
 /// # Arguments
 ///
-/// * `shortcode` - A reference to a `ShortCode` representing the shortcode of the clip.
-/// * `hits` - The number of hits to increase the hit count by.
+/// * `views` - The views along with the number of times each was counted.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns `Result<()>`, indicating success or an error if the views could not be recorded.
+///
+pub async fn record_views(views: &[(View, u32)], pool: &DatabasePool) -> Result<()> {
+    if views.is_empty() {
+        return Ok(());
+    }
+    let mut hits = serde_json::Map::new();
+    for (view, count) in views {
+        let total = hits
+            .get(view.shortcode.as_str())
+            .and_then(serde_json::Value::as_u64)
+            .unwrap_or(0);
+        hits.insert(
+            view.shortcode.as_str().to_owned(),
+            (total + u64::from(*count)).into(),
+        );
+    }
+    let hits = serde_json::Value::Object(hits).to_string();
+    let shortcodes = serde_json::Value::Array(
+        views
+            .iter()
+            .map(|(view, _)| view.shortcode.as_str().into())
+            .collect(),
+    )
+    .to_string();
+
+    let mut transaction = pool.begin().await?;
+    let mut known: HashMap<ShortCode, HashSet<String>> = HashMap::new();
+    let rows = sqlx::query!(
+        r#"SELECT DISTINCT clips.shortcode, clip_views.referrer
+            FROM clip_views
+            INNER JOIN clips ON clips.clip_id = clip_views.clip_id
+            WHERE clips.shortcode IN (SELECT value FROM json_each(?1))
+                AND clip_views.referrer NOT IN ('', ?2)"#,
+        shortcodes,
+        OTHER_REFERRER
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+    for row in rows {
+        known
+            .entry(ShortCode::from(row.shortcode))
+            .or_default()
+            .insert(row.referrer);
+    }
+    let mut views = views.to_vec();
+    views::fold_referrers(&mut views, known);
+    let buckets = serde_json::Value::Array(
+        views
+            .iter()
+            .map(|(view, count)| {
+                serde_json::json!({
+                    "shortcode": view.shortcode.as_str(),
+                    "hour": view.hour,
+                    "source": view.source.as_ref(),
+                    "agent": view.agent.as_ref(),
+                    "referrer": view.referrer.as_deref().unwrap_or_default(),
+                    "views": count,
+                })
+            })
+            .collect(),
+    )
+    .to_string();
+
+    sqlx::query!(
+        r#"UPDATE clips
+            SET hits = hits + (SELECT value FROM json_each(?1) WHERE key = clips.shortcode)
+            WHERE shortcode IN (SELECT key FROM json_each(?1))"#,
+        hits
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO clip_views (clip_id, hour, source, agent, referrer, views)
+            SELECT
+                clips.clip_id,
+                json_extract(bucket.value, '$.hour'),
+                json_extract(bucket.value, '$.source'),
+                json_extract(bucket.value, '$.agent'),
+                json_extract(bucket.value, '$.referrer'),
+                json_extract(bucket.value, '$.views')
+            FROM json_each(?1) AS bucket
+            INNER JOIN clips ON clips.shortcode = json_extract(bucket.value, '$.shortcode')
+            WHERE true
+            ON CONFLICT (clip_id, hour, source, agent, referrer)
+                DO UPDATE SET views = views + excluded.views"#,
+        buckets
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Retrieves the view statistics of a clip, bucket by bucket.
+///
+/// # Arguments
+///
+/// * `shortcode` - The shortcode of the clip.
+/// * `from` - The start of the first hour to include, in seconds since the epoch.
+/// * `to` - The start of the last hour to include, in seconds since the epoch.
 /// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
 ///
 /// # Returns
 ///
-/// Returns `Result<()>`, indicating success or an error if the hit count update fails.
+/// Returns a `Result` containing the buckets of the clip within the hours, oldest first.
 ///
-pub async fn increase_hit_count(
+pub async fn clip_views(
     shortcode: &ShortCode,
-    hits: u32,
+    from: i64,
+    to: i64,
     pool: &DatabasePool,
-) -> Result<()> {
+) -> Result<Vec<model::ViewBucket>> {
+    let shortcode = shortcode.as_str();
+    Ok(sqlx::query_as!(
+        model::ViewBucket,
+        r#"SELECT
+                clip_views.hour,
+                clip_views.source,
+                clip_views.agent,
+                clip_views.referrer,
+                clip_views.views
+            FROM clip_views
+            INNER JOIN clips ON clips.clip_id = clip_views.clip_id
+            WHERE clips.shortcode = ? AND clip_views.hour >= ? AND clip_views.hour <= ?
+            ORDER BY clip_views.hour"#,
+        shortcode,
+        from,
+        to
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Records a single view of a view-limited clip.
+///
+/// The view counter is only increased while the clip has views left, and the clip is
+/// deleted as part of the same transaction once its last view has been consumed.
+///
+/// # Arguments
+///
+/// * `shortcode` - A reference to a `ShortCode` representing the shortcode of the clip.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns `Result<()>`, indicating success or an error if the view could not be recorded.
+/// If the clip does not exist or has no views left, a `sqlx::Error::RowNotFound` error is returned.
+///
+pub async fn record_view(shortcode: &ShortCode, pool: &DatabasePool) -> Result<()> {
     let shortcode = shortcode.as_str();
+    let mut transaction = pool.begin().await?;
+    let result = sqlx::query!(
+        r#"UPDATE clips
+            SET views = views + 1
+            WHERE shortcode = ? AND (max_views IS NULL OR views < max_views)"#,
+        shortcode
+    )
+    .execute(&mut transaction)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(sqlx::Error::RowNotFound.into());
+    }
     sqlx::query!(
-        "UPDATE clips SET hits = hits + ? WHERE shortcode = ?",
-        hits,
+        "DELETE FROM clips WHERE shortcode = ? AND views >= max_views",
         shortcode
     )
-    .execute(pool)
+    .execute(&mut transaction)
     .await?;
+    transaction.commit().await?;
     Ok(())
 }
 
@@ -60,18 +225,202 @@ pub async fn get_clip<M: Into<model::GetClip>>(
     let shortcode = model.shortcode.as_str();
     Ok(sqlx::query_as!(
         model::Clip,
-        "SELECT * FROM clips WHERE shortcode = ?",
+        r#"SELECT
+                clip_id,
+                shortcode,
+                content AS "content: Vec<u8>",
+                title,
+                posted,
+                expires,
+                password,
+                hits,
+                edit_token,
+                max_views,
+                views,
+                language,
+                format,
+                content_encoding
+            FROM clips
+            WHERE shortcode = ?"#,
         shortcode
     )
     .fetch_one(pool)
     .await?)
 }
 
+/// Lists the publicly readable clips in the given order, starting after `cursor`.
+///
+/// Password-protected, view-limited and expired clips are never listed. Clips are
+/// ordered by the key described by `ClipSort::key`, then by shortcode.
+///
+/// # Arguments
+///
+/// * `sort` - The order to list the clips in.
+/// * `cursor` - The sort key and shortcode of the last clip of the previous page, if any.
+/// * `limit` - The maximum number of clips to return.
+/// * `pool` - The database connection pool.
+///
+/// # Returns
+///
+/// A `Result` containing the listed clips on success, or an error on failure.
+///
+pub async fn list_clips(
+    sort: ClipSort,
+    cursor: Option<&ListCursor>,
+    limit: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<model::Clip>> {
+    let sort = sort.as_ref();
+    let key = cursor.map(|cursor| cursor.key);
+    let shortcode = cursor.map(|cursor| cursor.shortcode.as_str());
+    Ok(sqlx::query_as!(
+        model::Clip,
+        r#"SELECT
+                clip_id AS "clip_id!",
+                shortcode AS "shortcode!",
+                content AS "content!: Vec<u8>",
+                title,
+                posted AS "posted!",
+                expires,
+                password,
+                hits AS "hits!",
+                edit_token,
+                max_views,
+                views AS "views!",
+                language,
+                format AS "format!",
+                content_encoding
+            FROM clips
+            WHERE password IS NULL
+                AND max_views IS NULL
+                AND (expires IS NULL OR strftime('%s', 'now') <= expires)
+                AND (
+                    ?2 IS NULL
+                    OR (
+                        CASE ?1
+                            WHEN 'posted' THEN -posted
+                            WHEN 'hits' THEN -hits
+                            ELSE COALESCE(expires, 9223372036854775807)
+                        END,
+                        shortcode
+                    ) > (?2, ?3)
+                )
+            ORDER BY
+                CASE ?1
+                    WHEN 'posted' THEN -posted
+                    WHEN 'hits' THEN -hits
+                    ELSE COALESCE(expires, 9223372036854775807)
+                END,
+                shortcode
+            LIMIT ?4"#,
+        sort,
+        key,
+        shortcode,
+        limit
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Lists every stored clip, newest first, including protected and expired ones.
+///
+/// # Arguments
+///
+/// * `limit` - The maximum number of clips to return.
+/// * `pool` - The database connection pool.
+///
+/// # Returns
+///
+/// A `Result` containing the listed clips on success, or an error on failure.
+///
+pub async fn list_all_clips(limit: u32, pool: &DatabasePool) -> Result<Vec<model::Clip>> {
+    Ok(sqlx::query_as!(
+        model::Clip,
+        r#"SELECT
+                clip_id AS "clip_id!",
+                shortcode AS "shortcode!",
+                content AS "content!: Vec<u8>",
+                title,
+                posted AS "posted!",
+                expires,
+                password,
+                hits AS "hits!",
+                edit_token,
+                max_views,
+                views AS "views!",
+                language,
+                format AS "format!",
+                content_encoding
+            FROM clips
+            ORDER BY posted DESC, shortcode
+            LIMIT ?"#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Replaces the search index entry of a clip with its current title and `content`.
+///
+/// The index holds the plain text of clips, as their stored content may be compressed.
+/// Only clips without a password or view limit are indexed.
+///
+/// # Arguments
+///
+/// * `shortcode` - The shortcode of the clip.
+/// * `content` - The uncompressed content of the clip.
+/// * `transaction` - The transaction the clip was written in.
+///
+/// # Returns
+///
+/// A `Result` indicating success, or an error on failure.
+///
+async fn index_clip(
+    shortcode: &str,
+    content: &str,
+    transaction: &mut Transaction<'_>,
+) -> Result<()> {
+    sqlx::query!("DELETE FROM clips_fts WHERE shortcode = ?", shortcode)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query!(
+        r#"INSERT INTO clips_fts (shortcode, title, content)
+            SELECT shortcode, title, ?
+            FROM clips
+            WHERE shortcode = ? AND password IS NULL AND max_views IS NULL"#,
+        content,
+        shortcode
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(())
+}
+
+/// Checks whether an error is the UNIQUE constraint on `clips.shortcode` tripping.
+fn is_shortcode_conflict(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Database(e) => {
+            e.code().as_deref() == Some("2067") && e.message().contains("clips.shortcode")
+        }
+        _ => false,
+    }
+}
+
 /// Inserts a new clip into the database based on the provided model and database connection pool.
 ///
+/// Content larger than `compress_above` bytes is stored compressed. Any attachments
+/// of the clip are stored in the same transaction. Unless the clip requests a shortcode,
+/// one is drawn from `shortcodes`, and drawn again if it is already taken, up to
+/// `SHORTCODE_ATTEMPTS` times; a requested shortcode that is taken fails with
+/// `DataError::ShortCodeTaken`. A clip created with an API key
+/// that would take the key over `quota` is not stored and fails with `DataError::Quota`.
+///
 /// # Arguments
 ///
 /// * `model` - The model representing the new clip to insert.
+/// * `quota` - The limits on what a single API key may store.
+/// * `shortcodes` - The generator shortcodes are drawn from when none was requested.
+/// * `compress_above` - The size, in bytes, above which the content is stored compressed.
 /// * `pool` - The database connection pool.
 ///
 /// # Returns
@@ -80,41 +429,132 @@ pub async fn get_clip<M: Into<model::GetClip>>(
 ///
 pub async fn insert_clip<M: Into<model::NewClip>>(
     model: M,
+    quota: &Quota,
+    shortcodes: &Generator,
+    compress_above: usize,
     pool: &DatabasePool,
 ) -> Result<model::Clip> {
     let model = model.into();
+    let mut shortcode = match &model.shortcode {
+        Some(requested) => requested.clone(),
+        None => shortcodes.generate(),
+    };
+    let stored = compression::encode(&model.content, compress_above);
+    let content_size = model.content.len() as i64;
+    let mut transaction = pool.begin().await?;
+    let mut attempts = 1;
+    loop {
+        let inserted = sqlx::query!(
+            r#"INSERT INTO
+                        clips (
+                            clip_id,
+                            shortcode,
+                            content,
+                            content_encoding,
+                            title,
+                            posted,
+                            expires,
+                            password,
+                            hits,
+                            edit_token,
+                            max_views,
+                            language,
+                            format,
+                            api_key,
+                            content_size
+                        )
+                    VALUES (
+                        ?1,
+                        ?2,
+                        CASE WHEN ?4 IS NULL THEN CAST(?3 AS TEXT) ELSE ?3 END,
+                        ?4,
+                        ?5,
+                        ?6,
+                        ?7,
+                        ?8,
+                        0,
+                        ?9,
+                        ?10,
+                        ?11,
+                        ?12,
+                        ?13,
+                        ?14
+                    )"#,
+            model.clip_id,
+            shortcode,
+            stored.data,
+            stored.encoding,
+            model.title,
+            model.posted,
+            model.expires,
+            model.password,
+            model.edit_token,
+            model.max_views,
+            model.language,
+            model.format,
+            model.api_key,
+            content_size
+        )
+        .execute(&mut transaction)
+        .await;
+        match inserted {
+            Ok(_) => break,
+            Err(e) if is_shortcode_conflict(&e) => {
+                if attempts == SHORTCODE_ATTEMPTS || model.shortcode.is_some() {
+                    return Err(DataError::ShortCodeTaken(shortcode));
+                }
+                shortcode = shortcodes.generate();
+                attempts += 1;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
     sqlx::query!(
         r#"INSERT INTO
-                clips (
-                    clip_id,
-                    shortcode,
-                    content,
-                    title,
-                    posted,
-                    expires,
-                    password,
-                    hits
-                )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
-        model.clip_id,
-        model.shortcode,
-        model.content,
-        model.title,
-        model.posted,
-        model.expires,
-        model.password,
-        0
+                clip_revisions (clip_id, revision, content, content_encoding, title, posted)
+            SELECT clip_id, 1, content, content_encoding, title, posted
+            FROM clips
+            WHERE clip_id = ?"#,
+        model.clip_id
     )
-    .execute(pool)
+    .execute(&mut transaction)
     .await?;
-    get_clip(model.shortcode, pool).await
+    index_clip(&shortcode, &model.content, &mut transaction).await?;
+    for attachment in &model.attachments {
+        sqlx::query!(
+            r#"INSERT INTO
+                    clip_attachments (attachment_id, clip_id, filename, mime_type, size, data)
+                VALUES (?, ?, ?, ?, ?, ?)"#,
+            attachment.attachment_id,
+            model.clip_id,
+            attachment.filename,
+            attachment.mime_type,
+            attachment.size,
+            attachment.data
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    if let Some(api_key) = &model.api_key {
+        let bytes = content_size + model.attachments.iter().map(|a| a.size).sum::<i64>();
+        check_quota(api_key, 1, bytes as u64, quota, &mut transaction).await?;
+    }
+    transaction.commit().await?;
+    get_clip(shortcode, pool).await
 }
 
 /// Updates an existing clip in the database based on the provided model and database connection pool.
 ///
+/// The new content and title are also recorded as the next revision of the clip. Content
+/// larger than `compress_above` bytes is stored compressed. If the clip was created with
+/// an API key, the new content counts towards `quota` in place of the old, and an update
+/// that would take the key over it fails with `DataError::Quota`.
+///
 /// # Arguments
 ///
 /// * `model` - The model representing the clip to update.
+/// * `quota` - The limits on what a single API key may store.
+/// * `compress_above` - The size, in bytes, above which the content is stored compressed.
 /// * `pool` - The database connection pool.
 ///
 /// # Returns
@@ -123,152 +563,940 @@ pub async fn insert_clip<M: Into<model::NewClip>>(
 ///
 pub async fn update_clip<M: Into<model::UpdateClip>>(
     model: M,
+    quota: &Quota,
+    compress_above: usize,
     pool: &DatabasePool,
 ) -> Result<model::Clip> {
     let model = model.into();
+    let stored = compression::encode(&model.content, compress_above);
+    let content_size = model.content.len() as i64;
+    let mut transaction = pool.begin().await?;
     let _ = sqlx::query!(
         r#"UPDATE clips
             SET
-                content = ?,
-                expires = ?,
-                password = ?,
-                title = ?
-            WHERE shortcode = ?"#,
-        model.content,
+                content = CASE WHEN ?2 IS NULL THEN CAST(?1 AS TEXT) ELSE ?1 END,
+                content_encoding = ?2,
+                content_size = ?3,
+                expires = ?4,
+                password = CASE WHEN ?5 THEN password ELSE ?6 END,
+                title = ?7
+            WHERE shortcode = ?8"#,
+        stored.data,
+        stored.encoding,
+        content_size,
         model.expires,
+        model.keep_password,
         model.password,
         model.title,
         model.shortcode
     )
-    .execute(pool)
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        r#"INSERT INTO
+                clip_revisions (clip_id, revision, content, content_encoding, title, posted)
+            SELECT
+                clip_id,
+                (
+                    SELECT COALESCE(MAX(revision), 0) + 1
+                    FROM clip_revisions
+                    WHERE clip_revisions.clip_id = clips.clip_id
+                ),
+                content,
+                content_encoding,
+                title,
+                CAST(strftime('%s', 'now') AS INTEGER)
+            FROM clips
+            WHERE shortcode = ?"#,
+        model.shortcode
+    )
+    .execute(&mut transaction)
+    .await?;
+    index_clip(&model.shortcode, &model.content, &mut transaction).await?;
+    let owner = sqlx::query!(
+        "SELECT api_key FROM clips WHERE shortcode = ?",
+        model.shortcode
+    )
+    .fetch_one(&mut transaction)
     .await?;
+    if let Some(api_key) = &owner.api_key {
+        check_quota(api_key, 0, content_size as u64, quota, &mut transaction).await?;
+    }
+    transaction.commit().await?;
     get_clip(model.shortcode, pool).await
 }
 
-/// Saves an API key to the database.
+/// Checks, once a clip has been written within `transaction`, that the clips of an API
+/// key stay within `quota`.
+///
+/// `clips` and `bytes` are what the write added, and are already part of the usage read
+/// back. As the write holds the database lock until the transaction ends, concurrent
+/// writes by the same key are checked one after the other.
+async fn check_quota(
+    api_key: &[u8],
+    clips: u64,
+    bytes: u64,
+    quota: &Quota,
+    transaction: &mut Transaction<'_>,
+) -> Result<()> {
+    let mut usage: Usage = usage_of(api_key, transaction).await?.into();
+    usage.clips = usage.clips.saturating_sub(clips);
+    usage.bytes = usage.bytes.saturating_sub(bytes);
+    Ok(quota.check(usage, clips, bytes)?)
+}
+
+/// Reports what the live clips created with an API key store.
 ///
-/// This function inserts the provided `api_key` into the `api_keys` table in the database.
+/// Content is counted at its size before compression, along with the size of any
+/// attachments. Expired clips awaiting deletion are not counted.
 ///
 /// # Arguments
 ///
-/// * `api_key` - An `ApiKey` representing the API key to save.
+/// * `digest` - A reference to the `KeyDigest` of the API key the clips were created with.
 /// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
 ///
 /// # Returns
 ///
-/// Returns a `Result<ApiKey, DataError>` indicating success or an error if the save operation fails.
-/// If successful, the original `api_key` is returned.
+/// A `Result` containing the number of clips and their stored bytes, or an error on failure.
 ///
-pub async fn save_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<ApiKey> {
-    let bytes = api_key.clone().into_inner();
-    let _ = sqlx::query!("INSERT INTO api_keys (api_key) VALUES (?)", bytes)
-        .execute(pool)
-        .await
-        .map(|_| ())?;
-    Ok(api_key)
+pub async fn key_usage(digest: &KeyDigest, pool: &DatabasePool) -> Result<model::Usage> {
+    let mut connection = pool.acquire().await?;
+    usage_of(&digest.clone().into_inner(), &mut connection).await
 }
 
-/// Represents the status of an API key revocation operation.
+/// Reports what the live clips created with an API key store, as `key_usage` does.
+async fn usage_of(api_key: &[u8], connection: &mut sqlx::SqliteConnection) -> Result<model::Usage> {
+    Ok(sqlx::query_as!(
+        model::Usage,
+        r#"SELECT
+                COUNT(*) AS "clips!: i64",
+                COALESCE(SUM(
+                    COALESCE(content_size, length(CAST(content AS BLOB))) + (
+                        SELECT COALESCE(SUM(size), 0)
+                        FROM clip_attachments
+                        WHERE clip_attachments.clip_id = clips.clip_id
+                    )
+                ), 0) AS "bytes!: i64"
+            FROM clips
+            WHERE
+                api_key = ?
+                AND (expires IS NULL OR strftime('%s', 'now') <= expires)"#,
+        api_key
+    )
+    .fetch_one(connection)
+    .await?)
+}
+
+/// Lists the stored passwords of every password-protected clip.
 ///
-/// # Variants
+/// # Arguments
 ///
-/// * `Revoked` - Indicates that the API key was successfully revoked.
-/// * `NotFound` - Indicates that the API key was not found and could not be revoked.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
 ///
-pub enum RevocationStatus {
-    Revoked,
-    NotFound,
+/// # Returns
+///
+/// A `Result` containing the shortcodes and stored passwords of the clips, or an error on failure.
+///
+pub async fn list_stored_passwords(pool: &DatabasePool) -> Result<Vec<model::StoredPassword>> {
+    Ok(sqlx::query_as!(
+        model::StoredPassword,
+        r#"SELECT shortcode, password as "password!"
+            FROM clips
+            WHERE password IS NOT NULL"#
+    )
+    .fetch_all(pool)
+    .await?)
 }
 
-/// Revokes an API key in the database.
-///
-/// This function deletes the provided `api_key` from the `api_keys` table in the database.
-/// It returns a `RevocationStatus` indicating whether the key was successfully revoked or not found.
+/// Replaces the stored password of a clip with its hash.
 ///
 /// # Arguments
 ///
-/// * `api_key` - An `ApiKey` representing the API key to revoke.
+/// * `shortcode` - A reference to a `ShortCode` representing the shortcode of the clip.
+/// * `hash` - The password hash to store.
 /// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
 ///
 /// # Returns
 ///
-/// Returns a `Result<RevocationStatus, DataError>` indicating success or an error if the revocation operation fails.
-/// If successful, it returns a `RevocationStatus` indicating whether the key was successfully revoked or not found.
+/// Returns `Result<()>`, indicating success or an error if the update failed.
 ///
-pub async fn revoke_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<RevocationStatus> {
-    let bytes = api_key.clone().into_inner();
-    Ok(
-        sqlx::query!("DELETE FROM api_keys WHERE api_key == ?", bytes)
-            .execute(pool)
-            .await
-            .map(|result| match result.rows_affected() {
-                0 => RevocationStatus::NotFound,
-                _ => RevocationStatus::Revoked,
-            })?,
+pub async fn set_password_hash(
+    shortcode: &ShortCode,
+    hash: PasswordHash,
+    pool: &DatabasePool,
+) -> Result<()> {
+    let shortcode = shortcode.as_str();
+    let hash = hash.into_inner();
+    sqlx::query!(
+        "UPDATE clips SET password = ? WHERE shortcode = ?",
+        hash,
+        shortcode
     )
+    .execute(pool)
+    .await?;
+    Ok(())
 }
 
-/// Checks if an API key is valid in the database.
-///
-/// This function performs a database query to check if the provided `api_key`
-/// exists in the `api_keys` table. It returns a boolean indicating whether the
-/// API key is valid or not.
+/// Lists every revision of a clip, oldest first.
 ///
 /// # Arguments
 ///
-/// * `api_key` - An `ApiKey` representing the API key to check.
+/// * `shortcode` - A reference to a `ShortCode` representing the shortcode of the clip.
 /// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
 ///
 /// # Returns
 ///
-/// Returns a `Result<bool, DataError>` indicating success or an error if the check operation fails.
-/// If successful, it returns a boolean value indicating whether the API key is valid or not.
+/// A `Result` containing the revisions of the clip on success, or an error on failure.
 ///
-pub async fn api_key_is_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bool> {
-    let bytes = api_key.clone().into_inner();
-    Ok(
-        sqlx::query("SELECT COUNT(api_key) FROM api_keys WHERE api_key = ?")
-            .bind(bytes)
-            .fetch_one(pool)
-            .await
-            .map(|row| {
-                let count: u32 = row.get(0);
-                count > 0
-            })?,
+pub async fn list_revisions(
+    shortcode: &ShortCode,
+    pool: &DatabasePool,
+) -> Result<Vec<model::Revision>> {
+    let shortcode = shortcode.as_str();
+    Ok(sqlx::query_as!(
+        model::Revision,
+        r#"SELECT
+                clip_revisions.revision,
+                clip_revisions.content AS "content: Vec<u8>",
+                clip_revisions.title,
+                clip_revisions.posted,
+                clip_revisions.content_encoding
+            FROM clip_revisions
+            INNER JOIN clips ON clips.clip_id = clip_revisions.clip_id
+            WHERE clips.shortcode = ?
+            ORDER BY clip_revisions.revision"#,
+        shortcode
     )
+    .fetch_all(pool)
+    .await?)
 }
 
-/// Deletes expired clips from the database.
+/// Builds an FTS5 match expression from free-form user input.
 ///
-/// This function executes a database query to delete clips from the `clips` table
-/// where the expiration time is earlier than the current time. It returns the
-/// number of rows affected by the deletion operation.
+/// Every whitespace-separated term is quoted, so that FTS5 operators in the input are
+/// searched for literally, and matched as a prefix. All terms must match.
+fn match_expression(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Searches the content and titles of clips, best matches first.
+///
+/// Only clips without a password or view limit are indexed, and expired clips are
+/// never returned.
 ///
 /// # Arguments
 ///
+/// * `query` - The free-form search terms; all of them must match.
+/// * `limit` - The maximum number of results to return.
+/// * `offset` - The number of results to skip.
 /// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
 ///
 /// # Returns
 ///
-/// Returns a `Result<u64, DataError>` indicating success or an error if the deletion operation fails.
-/// If successful, it returns the number of rows affected by the deletion operation.
+/// A `Result` containing the matching clips on success, or an error on failure. The
+/// matched terms in each snippet are enclosed in `MATCH_START` and `MATCH_END`.
 ///
-pub async fn delete_expired(pool: &DatabasePool) -> Result<u64> {
-    Ok(
-        sqlx::query!(r#"DELETE FROM clips WHERE strftime('%s', 'now') > expires"#)
-            .execute(pool)
-            .await?
-            .rows_affected(),
+pub async fn search_clips(
+    query: &str,
+    limit: u32,
+    offset: u32,
+    pool: &DatabasePool,
+) -> Result<Vec<model::SearchHit>> {
+    let expression = match_expression(query);
+    if expression.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(sqlx::query_as!(
+        model::SearchHit,
+        r#"SELECT
+                clips.shortcode AS "shortcode!",
+                clips.title,
+                snippet(clips_fts, -1, char(2), char(3), '…', 16) AS "snippet!: String",
+                bm25(clips_fts) AS "rank!: f64"
+            FROM clips_fts
+            INNER JOIN clips ON clips.shortcode = clips_fts.shortcode
+            WHERE clips_fts MATCH ?
+                AND clips.password IS NULL
+                AND clips.max_views IS NULL
+                AND (clips.expires IS NULL OR strftime('%s', 'now') <= clips.expires)
+            ORDER BY bm25(clips_fts)
+            LIMIT ? OFFSET ?"#,
+        expression,
+        limit,
+        offset
     )
+    .fetch_all(pool)
+    .await?)
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::data::Database;
+/// Retrieves a single revision of a clip.
+///
+/// # Arguments
+///
+/// * `shortcode` - A reference to a `ShortCode` representing the shortcode of the clip.
+/// * `revision` - The revision number to retrieve.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the revision on success, or an error on failure.
+///
+pub async fn get_revision(
+    shortcode: &ShortCode,
+    revision: u32,
+    pool: &DatabasePool,
+) -> Result<model::Revision> {
+    let shortcode = shortcode.as_str();
+    Ok(sqlx::query_as!(
+        model::Revision,
+        r#"SELECT
+                clip_revisions.revision,
+                clip_revisions.content AS "content: Vec<u8>",
+                clip_revisions.title,
+                clip_revisions.posted,
+                clip_revisions.content_encoding
+            FROM clip_revisions
+            INNER JOIN clips ON clips.clip_id = clip_revisions.clip_id
+            WHERE clips.shortcode = ? AND clip_revisions.revision = ?"#,
+        shortcode,
+        revision
+    )
+    .fetch_one(pool)
+    .await?)
+}
 
-    use super::*;
-    use chrono::{Duration, Utc};
-    use uuid::Uuid;
+/// Retrieves the attachments of a clip, without their contents, in upload order.
+///
+/// # Arguments
+///
+/// * `shortcode` - A reference to a `ShortCode` representing the shortcode of the clip.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the attachments on success, or an error on failure.
+///
+pub async fn list_attachments(
+    shortcode: &ShortCode,
+    pool: &DatabasePool,
+) -> Result<Vec<model::Attachment>> {
+    let shortcode = shortcode.as_str();
+    Ok(sqlx::query_as!(
+        model::Attachment,
+        r#"SELECT
+                clip_attachments.attachment_id,
+                clip_attachments.filename,
+                clip_attachments.mime_type,
+                clip_attachments.size
+            FROM clip_attachments
+            INNER JOIN clips ON clips.clip_id = clip_attachments.clip_id
+            WHERE clips.shortcode = ?
+            ORDER BY clip_attachments.rowid"#,
+        shortcode
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Retrieves an attachment of a clip together with its contents.
+///
+/// # Arguments
+///
+/// * `model` - The model identifying the clip and the attachment.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the attachment on success, or an error on failure. If the
+/// attachment does not belong to the clip, a `sqlx::Error::RowNotFound` error is returned.
+///
+pub async fn get_attachment<M: Into<model::GetAttachment>>(
+    model: M,
+    pool: &DatabasePool,
+) -> Result<model::AttachmentFile> {
+    let model = model.into();
+    Ok(sqlx::query_as!(
+        model::AttachmentFile,
+        r#"SELECT
+                clip_attachments.attachment_id,
+                clip_attachments.filename,
+                clip_attachments.mime_type,
+                clip_attachments.size,
+                clip_attachments.data
+            FROM clip_attachments
+            INNER JOIN clips ON clips.clip_id = clip_attachments.clip_id
+            WHERE clips.shortcode = ? AND clip_attachments.attachment_id = ?"#,
+        model.shortcode,
+        model.attachment_id
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Deletes a clip from the database based on the provided model and database connection pool.
+///
+/// # Arguments
+///
+/// * `model` - The model identifying the clip to delete.
+/// * `pool` - The database connection pool.
+///
+/// # Returns
+///
+/// A `Result` indicating success, or an error on failure. If no clip matches the
+/// shortcode, a `sqlx::Error::RowNotFound` error is returned.
+///
+pub async fn delete_clip<M: Into<model::DeleteClip>>(model: M, pool: &DatabasePool) -> Result<()> {
+    let model = model.into();
+    let shortcode = model.shortcode.as_str();
+    let result = sqlx::query!("DELETE FROM clips WHERE shortcode = ?", shortcode)
+        .execute(pool)
+        .await?;
+    match result.rows_affected() {
+        0 => Err(sqlx::Error::RowNotFound.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Saves an API key to the database.
+///
+/// This function inserts the digest of the provided `api_key` into the `api_keys` table in
+/// the database, along with its name, expiration and scopes. The key itself is not stored.
+///
+/// # Arguments
+///
+/// * `api_key` - A reference to the `ApiKey` to save.
+/// * `model` - The details of the key.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns `Result<()>`, indicating success or an error if the save operation fails.
+///
+pub async fn save_api_key<M: Into<model::NewApiKey>>(
+    api_key: &ApiKey,
+    model: M,
+    pool: &DatabasePool,
+) -> Result<()> {
+    let model = model.into();
+    let digest = api_key.digest().into_inner();
+    let _ = sqlx::query!(
+        r#"INSERT INTO
+                api_keys (api_key, name, created_at, expires_at, scopes)
+            VALUES (?, ?, ?, ?, ?)"#,
+        digest,
+        model.name,
+        model.created_at,
+        model.expires_at,
+        model.scopes
+    )
+    .execute(pool)
+    .await
+    .map(|_| ())?;
+    Ok(())
+}
+
+/// Retrieves the details of an API key.
+///
+/// # Arguments
+///
+/// * `api_key` - A reference to the `ApiKey` to look up.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the name, timestamps and scopes of the key, or an error if the key
+/// does not exist.
+///
+pub async fn get_api_key(api_key: &ApiKey, pool: &DatabasePool) -> Result<model::KeyInfo> {
+    let bytes = api_key.digest().into_inner();
+    Ok(sqlx::query_as!(
+        model::KeyInfo,
+        r#"SELECT
+                lower(hex(substr(api_key, 1, 6))) AS "id!: String",
+                name,
+                created_at,
+                last_used_at,
+                expires_at,
+                scopes
+            FROM api_keys
+            WHERE api_key = ?"#,
+        bytes
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Lists the details of every API key, oldest first.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the name, timestamps and scopes of each key, or an error on failure.
+///
+pub async fn list_api_keys(pool: &DatabasePool) -> Result<Vec<model::KeyInfo>> {
+    Ok(sqlx::query_as!(
+        model::KeyInfo,
+        r#"SELECT
+                lower(hex(substr(api_key, 1, 6))) AS "id!: String",
+                name,
+                created_at,
+                last_used_at,
+                expires_at,
+                scopes
+            FROM api_keys
+            ORDER BY created_at, api_key"#
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Records that an API key was just used.
+///
+/// The time is only written when it moved on by more than a minute, so a busy key does
+/// not cause a write on every request.
+///
+/// # Arguments
+///
+/// * `api_key` - A reference to the `ApiKey` that was used.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns `Result<()>`, indicating success or an error if the update fails.
+///
+pub async fn touch_api_key(api_key: &ApiKey, pool: &DatabasePool) -> Result<()> {
+    let bytes = api_key.digest().into_inner();
+    sqlx::query!(
+        r#"UPDATE api_keys
+            SET last_used_at = CAST(strftime('%s', 'now') AS INTEGER)
+            WHERE
+                api_key = ?
+                AND (last_used_at IS NULL OR last_used_at < strftime('%s', 'now') - 60)"#,
+        bytes
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Lists the API keys stored in plaintext, before keys were stored as digests.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the plaintext keys, or an error on failure.
+///
+pub async fn list_plaintext_api_keys(pool: &DatabasePool) -> Result<Vec<ApiKey>> {
+    let digest_len = KeyDigest::LEN as i64;
+    Ok(sqlx::query!(
+        r#"SELECT api_key AS "api_key!: Vec<u8>" FROM api_keys WHERE length(api_key) != ?"#,
+        digest_len
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| ApiKey::from(row.api_key))
+    .collect())
+}
+
+/// Replaces a plaintext API key with its digest, keeping its details and the clips
+/// created with it.
+///
+/// # Arguments
+///
+/// * `api_key` - A reference to the plaintext `ApiKey` to replace.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns `Result<()>`, indicating success or an error if the replacement fails.
+///
+pub async fn replace_with_digest(api_key: &ApiKey, pool: &DatabasePool) -> Result<()> {
+    let plaintext = api_key.clone().into_inner();
+    let digest = api_key.digest().into_inner();
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"INSERT INTO
+                api_keys (api_key, name, created_at, last_used_at, expires_at, scopes)
+            SELECT ?, name, created_at, last_used_at, expires_at, scopes
+            FROM api_keys
+            WHERE api_key = ?"#,
+        digest,
+        plaintext
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        "UPDATE clips SET api_key = ? WHERE api_key = ?",
+        digest,
+        plaintext
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!("DELETE FROM api_keys WHERE api_key = ?", plaintext)
+        .execute(&mut transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Represents the status of an API key revocation operation.
+///
+/// # Variants
+///
+/// * `Revoked` - Indicates that the API key was successfully revoked.
+/// * `NotFound` - Indicates that the API key was not found and could not be revoked.
+///
+pub enum RevocationStatus {
+    Revoked,
+    NotFound,
+}
+
+/// Revokes an API key in the database.
+///
+/// This function deletes the provided `api_key` from the `api_keys` table in the database,
+/// looking it up by its digest.
+/// It returns a `RevocationStatus` indicating whether the key was successfully revoked or not found.
+///
+/// # Arguments
+///
+/// * `api_key` - An `ApiKey` representing the API key to revoke.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns a `Result<RevocationStatus, DataError>` indicating success or an error if the revocation operation fails.
+/// If successful, it returns a `RevocationStatus` indicating whether the key was successfully revoked or not found.
+///
+pub async fn revoke_api_key(api_key: ApiKey, pool: &DatabasePool) -> Result<RevocationStatus> {
+    let bytes = api_key.digest().into_inner();
+    Ok(
+        sqlx::query!("DELETE FROM api_keys WHERE api_key == ?", bytes)
+            .execute(pool)
+            .await
+            .map(|result| match result.rows_affected() {
+                0 => RevocationStatus::NotFound,
+                _ => RevocationStatus::Revoked,
+            })?,
+    )
+}
+
+/// Revokes the API key with the given id, as listed by `list_api_keys`.
+///
+/// # Arguments
+///
+/// * `id` - The hex id of the key to revoke.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns a `Result<RevocationStatus, DataError>` indicating whether a key was revoked,
+/// or an error if the revocation operation fails.
+///
+pub async fn revoke_api_key_by_id(id: &str, pool: &DatabasePool) -> Result<RevocationStatus> {
+    let id = id.to_ascii_lowercase();
+    Ok(sqlx::query!(
+        "DELETE FROM api_keys WHERE lower(hex(substr(api_key, 1, 6))) = ?",
+        id
+    )
+    .execute(pool)
+    .await
+    .map(|result| match result.rows_affected() {
+        0 => RevocationStatus::NotFound,
+        _ => RevocationStatus::Revoked,
+    })?)
+}
+
+/// Checks if an API key is valid in the database.
+///
+/// This function performs a database query to check if the digest of the provided
+/// `api_key` exists in the `api_keys` table and has not expired. It returns a boolean indicating
+/// whether the API key is valid or not.
+///
+/// # Arguments
+///
+/// * `api_key` - An `ApiKey` representing the API key to check.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns a `Result<bool, DataError>` indicating success or an error if the check operation fails.
+/// If successful, it returns a boolean value indicating whether the API key is valid or not.
+///
+pub async fn api_key_is_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bool> {
+    let bytes = api_key.digest().into_inner();
+    Ok(sqlx::query(
+        r#"SELECT COUNT(api_key)
+                FROM api_keys
+                WHERE
+                    api_key = ?
+                    AND (expires_at IS NULL OR strftime('%s', 'now') <= expires_at)"#,
+    )
+    .bind(bytes)
+    .fetch_one(pool)
+    .await
+    .map(|row| {
+        let count: u32 = row.get(0);
+        count > 0
+    })?)
+}
+
+/// Deletes expired clips from the database.
+///
+/// This function executes a database query to delete clips from the `clips` table
+/// where the expiration time is earlier than the current time. It returns the
+/// number of rows affected by the deletion operation.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns a `Result<u64, DataError>` indicating success or an error if the deletion operation fails.
+/// If successful, it returns the number of rows affected by the deletion operation.
+///
+pub async fn delete_expired(pool: &DatabasePool) -> Result<u64> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"DELETE FROM clip_attachments
+            WHERE clip_id IN (
+                SELECT clip_id FROM clips WHERE strftime('%s', 'now') > expires
+            )"#
+    )
+    .execute(&mut transaction)
+    .await?;
+    let deleted = sqlx::query!(r#"DELETE FROM clips WHERE strftime('%s', 'now') > expires"#)
+        .execute(&mut transaction)
+        .await?
+        .rows_affected();
+    transaction.commit().await?;
+    Ok(deleted)
+}
+
+/// Deletes the view statistics of hours starting before `before`, in seconds since the
+/// epoch, returning how many buckets were deleted.
+///
+/// # Arguments
+///
+/// * `before` - The start of the oldest hour to keep, in seconds since the epoch.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the number of deleted buckets.
+///
+pub async fn delete_old_views(before: i64, pool: &DatabasePool) -> Result<u64> {
+    Ok(
+        sqlx::query!("DELETE FROM clip_views WHERE hour < ?", before)
+            .execute(pool)
+            .await?
+            .rows_affected(),
+    )
+}
+
+/// Rewrites the stored content of every clip and revision according to a compression
+/// threshold.
+///
+/// Content above the threshold is compressed, and content at or below it is stored
+/// uncompressed again. Rows whose stored form does not change are left untouched, apart
+/// from recording the uncompressed size of clips that predate `content_size`.
+///
+/// # Arguments
+///
+/// * `compress_above` - The size, in bytes, above which content is stored compressed.
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the number of rewritten rows and the stored content size before
+/// and after, or an error on failure.
+///
+pub async fn recompress_contents(
+    compress_above: usize,
+    pool: &DatabasePool,
+) -> Result<model::Recompression> {
+    let threshold = compress_above;
+    let mut outcome = model::Recompression::default();
+    let mut transaction = pool.begin().await?;
+
+    let clips = sqlx::query!(
+        r#"SELECT
+                rowid AS "row_id!: i64",
+                content AS "content!: Vec<u8>",
+                content_encoding,
+                content_size
+            FROM clips"#
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+    for clip in clips {
+        let (content_size, stored) =
+            recompress(clip.content, clip.content_encoding, threshold, &mut outcome)?;
+        if let Some(stored) = stored {
+            sqlx::query!(
+                r#"UPDATE clips
+                    SET
+                        content = CASE WHEN ?2 IS NULL THEN CAST(?1 AS TEXT) ELSE ?1 END,
+                        content_encoding = ?2,
+                        content_size = ?3
+                    WHERE rowid = ?4"#,
+                stored.data,
+                stored.encoding,
+                content_size,
+                clip.row_id
+            )
+            .execute(&mut transaction)
+            .await?;
+        } else if clip.content_size != Some(content_size) {
+            sqlx::query!(
+                "UPDATE clips SET content_size = ? WHERE rowid = ?",
+                content_size,
+                clip.row_id
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+    }
+
+    let revisions = sqlx::query!(
+        r#"SELECT
+                rowid AS "row_id!: i64",
+                content AS "content!: Vec<u8>",
+                content_encoding
+            FROM clip_revisions"#
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+    for revision in revisions {
+        let (_, stored) = recompress(
+            revision.content,
+            revision.content_encoding,
+            threshold,
+            &mut outcome,
+        )?;
+        if let Some(stored) = stored {
+            sqlx::query!(
+                r#"UPDATE clip_revisions
+                    SET
+                        content = CASE WHEN ?2 IS NULL THEN CAST(?1 AS TEXT) ELSE ?1 END,
+                        content_encoding = ?2
+                    WHERE rowid = ?3"#,
+                stored.data,
+                stored.encoding,
+                revision.row_id
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+    }
+
+    transaction.commit().await?;
+    Ok(outcome)
+}
+
+/// Re-encodes stored content, tallying its size in `outcome`. Returns the size of the
+/// content before compression, and the new stored form if it differs from the current one.
+fn recompress(
+    data: Vec<u8>,
+    encoding: Option<String>,
+    threshold: usize,
+    outcome: &mut model::Recompression,
+) -> Result<(i64, Option<compression::StoredContent>)> {
+    let bytes_before = data.len() as u64;
+    let current = compression::StoredContent { data, encoding };
+    let content = compression::decode(current.data.clone(), current.encoding.as_deref())
+        .map_err(|e| sqlx::Error::Decode(e.into()))?;
+    let stored = compression::encode(&content, threshold);
+    let content_size = content.len() as i64;
+
+    outcome.bytes_before += bytes_before;
+    outcome.bytes_after += stored.data.len() as u64;
+    if stored == current {
+        return Ok((content_size, None));
+    }
+    outcome.rewritten += 1;
+    Ok((content_size, Some(stored)))
+}
+
+/// Counts the clips, stored bytes, hits and API keys in the database.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the totals, or an error on failure.
+///
+pub async fn stats(pool: &DatabasePool) -> Result<model::Stats> {
+    Ok(sqlx::query_as::<_, model::Stats>(
+        r#"SELECT
+                (SELECT COUNT(*) FROM clips) AS clips,
+                (SELECT COUNT(*) FROM clips WHERE strftime('%s', 'now') > expires) AS expired,
+                (SELECT COUNT(*) FROM clips WHERE password IS NOT NULL) AS protected,
+                (SELECT COALESCE(SUM(length(CAST(content AS BLOB))), 0) FROM clips)
+                    AS content_bytes,
+                (SELECT COUNT(*) FROM clip_attachments) AS attachments,
+                (SELECT COALESCE(SUM(size), 0) FROM clip_attachments) AS attachment_bytes,
+                (SELECT COALESCE(SUM(hits), 0) FROM clips) AS hits,
+                (SELECT COUNT(*) FROM api_keys) AS api_keys"#,
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Returns the size of the database file, in bytes.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// A `Result` containing the number of pages times the page size, or an error on failure.
+///
+pub async fn database_size(pool: &DatabasePool) -> Result<u64> {
+    let row =
+        sqlx::query("SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()")
+            .fetch_one(pool)
+            .await?;
+    Ok(u64::try_from(row.get::<i64, _>(0)).unwrap_or_default())
+}
+
+/// Rebuilds the database file, reclaiming the space left by deleted rows.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+///
+/// # Returns
+///
+/// Returns `Result<()>`, indicating success or an error if the database could not be rebuilt.
+///
+pub async fn vacuum(pool: &DatabasePool) -> Result<()> {
+    sqlx::query("VACUUM").execute(pool).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::Database;
+
+    use super::*;
+    use crate::{domain::quota::QuotaError, service::ask, web::api::scope::Scope};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
 
     // Helper function to create a test database pool
     async fn create_test_pool() -> DatabasePool {
@@ -278,17 +1506,35 @@ mod tests {
         pool.clone()
     }
 
+    /// Adds `hits` views of a clip through the batched write path of the hit counter.
+    async fn record_hits(shortcode: &ShortCode, hits: u32, pool: &DatabasePool) {
+        use crate::domain::clip::views::{Agent, ViewSource};
+
+        let view = View::new(shortcode.clone(), ViewSource::Web, Agent::Browser, None);
+        record_views(&[(view, hits)], pool).await.unwrap();
+    }
+
+    fn random_shortcode() -> ShortCode {
+        ShortCode::from(Generator::default().generate())
+    }
+
     fn model_new_clip(shortcode: &str) -> model::NewClip {
         let posted = Utc::now();
         let expires = posted + Duration::days(7);
         model::NewClip {
             clip_id: Uuid::new_v4().to_string(),
-            shortcode: shortcode.to_string(),
+            shortcode: Some(shortcode.to_string()),
             content: "Hello, world!".to_string(),
             title: Some("Test Clip".to_string()),
             posted: posted.timestamp(),
             expires: Some(expires.timestamp()),
             password: Some("password".to_string()),
+            edit_token: "token".to_string(),
+            max_views: None,
+            language: None,
+            format: "text".to_owned(),
+            attachments: vec![],
+            api_key: None,
         }
     }
 
@@ -299,32 +1545,927 @@ mod tests {
             title: Some("Updated title".to_string()),
             expires: Some((Utc::now() + Duration::days(2)).timestamp()),
             password: None,
+            keep_password: false,
         }
     }
 
     #[tokio::test]
-    async fn test_increase_hit_insert_update_and_get_clip() {
+    async fn test_insert_clip_draws_shortcodes() {
         let pool = create_test_pool().await;
+        let shortcodes = Generator::random(1, "ab").unwrap();
+        let drawn = || model::NewClip {
+            shortcode: None,
+            ..model_new_clip("")
+        };
 
-        let shortcode = ShortCode::new();
+        let clip = insert_clip(
+            drawn(),
+            &Quota::default(),
+            &shortcodes,
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert!(["a", "b"].contains(&clip.shortcode.as_str()));
+        let shortcode = ShortCode::from(clip.shortcode.as_str());
+        let found = get_clip(shortcode.clone(), &pool).await.unwrap();
+        assert_eq!(found.clip_id, clip.clip_id);
+        assert_eq!(list_revisions(&shortcode, &pool).await.unwrap().len(), 1);
+
+        assert!(matches!(
+            insert_clip(
+                model_new_clip(&clip.shortcode),
+                &Quota::default(),
+                &shortcodes,
+                compression::DEFAULT_THRESHOLD,
+                &pool,
+            )
+            .await,
+            Err(DataError::ShortCodeTaken(taken)) if taken == clip.shortcode
+        ));
+
+        let other = if clip.shortcode == "a" { "b" } else { "a" };
+        insert_clip(
+            model_new_clip(other),
+            &Quota::default(),
+            &shortcodes,
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            insert_clip(
+                drawn(),
+                &Quota::default(),
+                &shortcodes,
+                compression::DEFAULT_THRESHOLD,
+                &pool,
+            )
+            .await,
+            Err(DataError::ShortCodeTaken(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_record_hit_insert_update_and_get_clip() {
+        let pool = create_test_pool().await;
+
+        let shortcode = random_shortcode();
 
         let new_clip = model_new_clip(shortcode.as_str());
 
-        let inserted_clip = insert_clip(new_clip, &pool).await.unwrap();
+        let inserted_clip = insert_clip(
+            new_clip,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
         let retrieved_clip = get_clip(inserted_clip.shortcode, &pool).await.unwrap();
 
         assert_eq!(retrieved_clip.clip_id, inserted_clip.clip_id);
         assert_eq!(retrieved_clip.hits, 0);
 
-        increase_hit_count(&shortcode, 1, &pool).await.unwrap();
+        record_hits(&shortcode, 1, &pool).await;
 
         let updated_clip = model_update_clip(shortcode.as_str());
 
-        let updated_clip = update_clip(updated_clip, &pool).await.unwrap();
-        assert_eq!(updated_clip.content, "Updated content");
+        let updated_clip = update_clip(
+            updated_clip,
+            &Quota::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(updated_clip.content, b"Updated content");
         assert_eq!(updated_clip.title, Some("Updated title".to_string()));
         assert_eq!(updated_clip.password, None);
 
         assert_eq!(updated_clip.hits, 1);
     }
+
+    #[tokio::test]
+    async fn test_record_views() {
+        use crate::domain::clip::views::{Agent, ViewSource};
+
+        let pool = create_test_pool().await;
+
+        let first = random_shortcode();
+        let second = random_shortcode();
+        insert_clip(
+            model_new_clip(first.as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        insert_clip(
+            model_new_clip(second.as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let view = |shortcode: &ShortCode, source, referrer: Option<&str>| View {
+            shortcode: shortcode.clone(),
+            hour: 7200,
+            source,
+            agent: Agent::Browser,
+            referrer: referrer.map(str::to_owned),
+        };
+        let views = [
+            (view(&first, ViewSource::Web, Some("chat.example")), 2),
+            (view(&second, ViewSource::Raw, None), 5),
+            (view(&first, ViewSource::Web, Some("chat.example")), 1),
+            (view(&ShortCode::from("missing"), ViewSource::Web, None), 4),
+        ];
+        record_views(&views, &pool).await.unwrap();
+        record_views(&views[1..2], &pool).await.unwrap();
+        record_views(&[], &pool).await.unwrap();
+
+        assert_eq!(get_clip(first.clone(), &pool).await.unwrap().hits, 3);
+        assert_eq!(get_clip(second.clone(), &pool).await.unwrap().hits, 10);
+
+        let buckets = clip_views(&first, 0, 7200, &pool).await.unwrap();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].source, "web");
+        assert_eq!(buckets[0].referrer, "chat.example");
+        assert_eq!(buckets[0].views, 3);
+        let buckets = clip_views(&second, 0, 7200, &pool).await.unwrap();
+        assert_eq!((buckets[0].referrer.as_str(), buckets[0].views), ("", 10));
+        assert!(clip_views(&second, 0, 3600, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(delete_old_views(7200, &pool).await.unwrap(), 0);
+        assert_eq!(delete_old_views(7201, &pool).await.unwrap(), 2);
+        record_views(&views, &pool).await.unwrap();
+
+        delete_clip(first.clone(), &pool).await.unwrap();
+        let orphans: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM clip_views")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(orphans, 1);
+    }
+
+    #[tokio::test]
+    async fn test_record_views_folds_referrers() {
+        use crate::domain::clip::views::{Agent, ViewSource, MAX_REFERRERS};
+
+        let pool = create_test_pool().await;
+        let shortcode = random_shortcode();
+        insert_clip(
+            model_new_clip(shortcode.as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let view = |host: String| View {
+            shortcode: shortcode.clone(),
+            hour: 3600,
+            source: ViewSource::Web,
+            agent: Agent::Browser,
+            referrer: Some(host),
+        };
+        let views: Vec<_> = (0..MAX_REFERRERS)
+            .map(|n| (view(format!("{}.example", n)), 1))
+            .collect();
+        record_views(&views, &pool).await.unwrap();
+        record_views(
+            &[
+                (view("late.example".to_owned()), 2),
+                (view("0.example".to_owned()), 1),
+                (view("later.example".to_owned()), 3),
+            ],
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let buckets = clip_views(&shortcode, 0, 3600, &pool).await.unwrap();
+        assert_eq!(buckets.len(), MAX_REFERRERS + 1);
+        let views_from = |host: &str| {
+            buckets
+                .iter()
+                .find(|bucket| bucket.referrer == host)
+                .map(|bucket| bucket.views)
+        };
+        assert_eq!(views_from(OTHER_REFERRER), Some(5));
+        assert_eq!(views_from("0.example"), Some(2));
+        assert_eq!(views_from("late.example"), None);
+    }
+
+    #[tokio::test]
+    async fn test_delete_clip() {
+        let pool = create_test_pool().await;
+
+        let shortcode = random_shortcode();
+        insert_clip(
+            model_new_clip(shortcode.as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        delete_clip(shortcode.clone(), &pool).await.unwrap();
+
+        assert!(matches!(
+            get_clip(shortcode.clone(), &pool).await,
+            Err(DataError::Database(sqlx::Error::RowNotFound))
+        ));
+        assert!(matches!(
+            delete_clip(shortcode, &pool).await,
+            Err(DataError::Database(sqlx::Error::RowNotFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_record_view() {
+        let pool = create_test_pool().await;
+
+        let shortcode = random_shortcode();
+        let mut new_clip = model_new_clip(shortcode.as_str());
+        new_clip.max_views = Some(2);
+        insert_clip(
+            new_clip,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        record_view(&shortcode, &pool).await.unwrap();
+        assert_eq!(get_clip(shortcode.clone(), &pool).await.unwrap().views, 1);
+
+        record_view(&shortcode, &pool).await.unwrap();
+        assert!(get_clip(shortcode.clone(), &pool).await.is_err());
+        assert!(matches!(
+            record_view(&shortcode, &pool).await,
+            Err(DataError::Database(sqlx::Error::RowNotFound))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stored_passwords() {
+        let pool = create_test_pool().await;
+
+        let shortcode = random_shortcode();
+        insert_clip(
+            model_new_clip(shortcode.as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let stored = list_stored_passwords(&pool).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].shortcode(), shortcode);
+        assert_eq!(stored[0].password(), "password");
+
+        let hash = PasswordHash::new("hash".to_string());
+        set_password_hash(&shortcode, hash, &pool).await.unwrap();
+
+        let mut updated_clip = model_update_clip(shortcode.as_str());
+        updated_clip.keep_password = true;
+        let clip = update_clip(
+            updated_clip,
+            &Quota::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(clip.password, Some("hash".to_string()));
+
+        let clip = update_clip(
+            model_update_clip(shortcode.as_str()),
+            &Quota::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(clip.password, None);
+        assert!(list_stored_passwords(&pool).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_list_clips() {
+        let pool = create_test_pool().await;
+        let now = Utc::now();
+
+        let mut shortcodes = vec![];
+        for (age, hits, expires) in [(3, 5, Some(1)), (2, 1, None), (1, 9, Some(3))] {
+            let shortcode = random_shortcode();
+            let mut new_clip = model_new_clip(shortcode.as_str());
+            new_clip.password = None;
+            new_clip.posted = (now - Duration::days(age)).timestamp();
+            new_clip.expires = expires.map(|days| (now + Duration::days(days)).timestamp());
+            insert_clip(
+                new_clip,
+                &Quota::default(),
+                &Generator::default(),
+                compression::DEFAULT_THRESHOLD,
+                &pool,
+            )
+            .await
+            .unwrap();
+            record_hits(&shortcode, hits, &pool).await;
+            shortcodes.push(shortcode);
+        }
+        insert_clip(
+            model_new_clip(random_shortcode().as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        let mut expired = model_new_clip(random_shortcode().as_str());
+        expired.password = None;
+        expired.expires = Some((now - Duration::days(1)).timestamp());
+        insert_clip(
+            expired,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let list = |sort, cursor: Option<ListCursor>| {
+            let pool = pool.clone();
+            async move {
+                let clips: Vec<crate::Clip> = list_clips(sort, cursor.as_ref(), 2, &pool)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|clip| clip.try_into().unwrap())
+                    .collect();
+                clips
+            }
+        };
+        for (sort, expected) in [
+            (ClipSort::Posted, [2, 1, 0]),
+            (ClipSort::Hits, [2, 0, 1]),
+            (ClipSort::Expires, [0, 2, 1]),
+        ] {
+            let first = list(sort, None).await;
+            assert_eq!(first.len(), 2);
+            let cursor = ListCursor::after(first.last().unwrap(), sort);
+            let second = list(sort, Some(cursor)).await;
+            assert_eq!(second.len(), 1);
+            let listed: Vec<_> = first.iter().chain(&second).map(|c| &c.shortcode).collect();
+            let expected: Vec<_> = expected.iter().map(|i| &shortcodes[*i]).collect();
+            assert_eq!(listed, expected);
+        }
+    }
+
+    #[test]
+    fn test_match_expression() {
+        assert_eq!(match_expression("  "), "");
+        assert_eq!(match_expression("nginx error"), r#""nginx"* "error"*"#);
+        assert_eq!(match_expression(r#"a"b OR"#), r#""a""b"* "OR"*"#);
+    }
+
+    #[tokio::test]
+    async fn test_search_clips() {
+        let pool = create_test_pool().await;
+
+        let mut public = model_new_clip(random_shortcode().as_str());
+        public.password = None;
+        public.content = "nginx error log from the edge proxy".to_string();
+        insert_clip(
+            public,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let mut protected = model_new_clip(random_shortcode().as_str());
+        protected.content = "nginx error log behind a password".to_string();
+        insert_clip(
+            protected,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let mut limited = model_new_clip(random_shortcode().as_str());
+        limited.password = None;
+        limited.max_views = Some(1);
+        limited.content = "nginx error log read once".to_string();
+        insert_clip(
+            limited,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let hits = search_clips("nginx err", 10, 0, &pool).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(hits[0].snippet.contains("\u{2}nginx\u{3}"));
+        assert!(search_clips("nginx", 10, 1, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(search_clips("NEAR(", 10, 0, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+
+        let shortcode = ShortCode::from(hits[0].shortcode.as_str());
+        let mut updated_clip = model_update_clip(shortcode.as_str());
+        updated_clip.password = Some("hash".to_string());
+        update_clip(
+            updated_clip,
+            &Quota::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert!(search_clips("updated", 10, 0, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+
+        update_clip(
+            model_update_clip(shortcode.as_str()),
+            &Quota::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            search_clips("updated", 10, 0, &pool).await.unwrap().len(),
+            1
+        );
+        assert!(search_clips("nginx", 10, 0, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+
+        delete_clip(shortcode, &pool).await.unwrap();
+        assert!(search_clips("updated", 10, 0, &pool)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_revisions() {
+        let pool = create_test_pool().await;
+
+        let shortcode = random_shortcode();
+        insert_clip(
+            model_new_clip(shortcode.as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        update_clip(
+            model_update_clip(shortcode.as_str()),
+            &Quota::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let revisions = list_revisions(&shortcode, &pool).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].revision, 1);
+        assert_eq!(revisions[0].content, b"Hello, world!");
+        assert_eq!(revisions[1].revision, 2);
+        assert_eq!(revisions[1].content, b"Updated content");
+
+        let revision = get_revision(&shortcode, 1, &pool).await.unwrap();
+        assert_eq!(revision.title, Some("Test Clip".to_string()));
+        assert!(get_revision(&shortcode, 3, &pool).await.is_err());
+
+        delete_clip(shortcode, &pool).await.unwrap();
+        let remaining: i64 = sqlx::query("SELECT COUNT(*) FROM clip_revisions")
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(remaining, 0);
+    }
+
+    #[tokio::test]
+    async fn test_attachments() {
+        let pool = create_test_pool().await;
+
+        let shortcode = random_shortcode();
+        let mut new_clip = model_new_clip(shortcode.as_str());
+        new_clip.attachments = vec![
+            model::NewAttachment {
+                attachment_id: Uuid::new_v4().to_string(),
+                filename: "shot.png".to_string(),
+                mime_type: "image/png".to_string(),
+                size: 4,
+                data: vec![0x89, b'P', b'N', b'G'],
+            },
+            model::NewAttachment {
+                attachment_id: Uuid::new_v4().to_string(),
+                filename: "core".to_string(),
+                mime_type: "application/octet-stream".to_string(),
+                size: 0,
+                data: vec![],
+            },
+        ];
+        let attachment_id = new_clip.attachments[0].attachment_id.clone();
+        insert_clip(
+            new_clip,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let attachments = list_attachments(&shortcode, &pool).await.unwrap();
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].filename, "shot.png");
+        assert_eq!(attachments[1].filename, "core");
+
+        let get = |shortcode: &ShortCode| model::GetAttachment {
+            shortcode: shortcode.as_str().to_owned(),
+            attachment_id: attachment_id.clone(),
+        };
+        let file = get_attachment(get(&shortcode), &pool).await.unwrap();
+        assert_eq!(file.mime_type, "image/png");
+        assert_eq!(file.data, vec![0x89, b'P', b'N', b'G']);
+        assert!(get_attachment(get(&random_shortcode()), &pool)
+            .await
+            .is_err());
+
+        sqlx::query("UPDATE clips SET expires = 0 WHERE shortcode = ?")
+            .bind(shortcode.as_str())
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(delete_expired(&pool).await.unwrap(), 1);
+        let remaining: i64 = sqlx::query("SELECT COUNT(*) FROM clip_attachments")
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .get(0);
+        assert_eq!(remaining, 0);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_content() {
+        let pool = create_test_pool().await;
+        let content = "GET /index.html HTTP/1.1 200 nginx\n".repeat(500);
+
+        let shortcode = random_shortcode();
+        let mut new_clip = model_new_clip(shortcode.as_str());
+        new_clip.password = None;
+        new_clip.content = content.clone();
+        insert_clip(
+            new_clip,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let row = sqlx::query("SELECT length(content), content_encoding FROM clips")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(row.get::<i64, _>(0) < content.len() as i64);
+        assert_eq!(
+            row.get::<Option<String>, _>(1).as_deref(),
+            Some(compression::ZSTD)
+        );
+
+        let clip = get_clip(shortcode.clone(), &pool).await.unwrap();
+        let clip = crate::domain::Clip::try_from(clip).unwrap();
+        assert_eq!(clip.content.as_str(), content);
+        let revision = get_revision(&shortcode, 1, &pool).await.unwrap();
+        let revision = crate::domain::clip::Revision::try_from(revision).unwrap();
+        assert_eq!(revision.content.as_str(), content);
+        assert_eq!(search_clips("nginx", 10, 0, &pool).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recompress_contents() {
+        let pool = create_test_pool().await;
+        let content = "GET /index.html HTTP/1.1 200 nginx\n".repeat(500);
+
+        let shortcode = random_shortcode();
+        insert_clip(
+            model_new_clip(shortcode.as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        sqlx::query("UPDATE clips SET content = ?, content_encoding = NULL")
+            .bind(&content)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let outcome = recompress_contents(compression::DEFAULT_THRESHOLD, &pool)
+            .await
+            .unwrap();
+        assert_eq!(outcome.rewritten(), 1);
+        assert!(outcome.bytes_saved() > 0);
+        let clip = get_clip(shortcode, &pool).await.unwrap();
+        assert_eq!(clip.content_encoding.as_deref(), Some(compression::ZSTD));
+
+        let outcome = recompress_contents(compression::DEFAULT_THRESHOLD, &pool)
+            .await
+            .unwrap();
+        assert_eq!(outcome.rewritten(), 0);
+        assert_eq!(outcome.bytes_saved(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_key_usage() {
+        let pool = create_test_pool().await;
+        let api_key = ApiKey::default();
+        save_api_key(&api_key, ask::NewApiKey::default(), &pool)
+            .await
+            .unwrap();
+
+        let mut owned = model_new_clip(random_shortcode().as_str());
+        owned.api_key = Some(api_key.digest().into_inner());
+        owned.attachments = vec![model::NewAttachment {
+            attachment_id: Uuid::new_v4().to_string(),
+            filename: "notes.txt".to_string(),
+            mime_type: "text/plain".to_string(),
+            size: 3,
+            data: b"abc".to_vec(),
+        }];
+        insert_clip(
+            owned,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        let mut expired = model_new_clip(random_shortcode().as_str());
+        expired.api_key = Some(api_key.digest().into_inner());
+        expired.expires = Some(0);
+        insert_clip(
+            expired,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        insert_clip(
+            model_new_clip(random_shortcode().as_str()),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+
+        let usage = key_usage(&api_key.digest(), &pool).await.unwrap();
+        assert_eq!(usage.clips, 1);
+        assert_eq!(usage.bytes, "Hello, world!".len() as i64 + 3);
+
+        revoke_api_key(api_key.clone(), &pool).await.unwrap();
+        let usage = key_usage(&api_key.digest(), &pool).await.unwrap();
+        assert_eq!((usage.clips, usage.bytes), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_quota_counts_uncompressed_content() {
+        let pool = create_test_pool().await;
+        let api_key = ApiKey::default();
+        save_api_key(&api_key, ask::NewApiKey::default(), &pool)
+            .await
+            .unwrap();
+        let content = "GET /index.html 200\n".repeat(500);
+        let quota = Quota {
+            max_clips: None,
+            max_bytes: Some(content.len() as u64 + 10),
+        };
+
+        let mut first = model_new_clip(random_shortcode().as_str());
+        first.api_key = Some(api_key.digest().into_inner());
+        first.content = content.clone();
+        let first = insert_clip(first, &quota, &Generator::default(), 16, &pool)
+            .await
+            .unwrap();
+        let usage = key_usage(&api_key.digest(), &pool).await.unwrap();
+        assert_eq!(usage.bytes, content.len() as i64);
+
+        let shortcode = random_shortcode();
+        let mut second = model_new_clip(shortcode.as_str());
+        second.api_key = Some(api_key.digest().into_inner());
+        second.content = content.clone();
+        assert!(matches!(
+            insert_clip(second, &quota, &Generator::default(), 16, &pool).await,
+            Err(DataError::Quota(QuotaError::Bytes { .. }))
+        ));
+        assert!(get_clip(shortcode, &pool).await.is_err());
+
+        let mut update = model_update_clip(first.shortcode.as_str());
+        update.content = format!("{}{}", content, "x".repeat(11));
+        assert!(matches!(
+            update_clip(update, &quota, 16, &pool).await,
+            Err(DataError::Quota(QuotaError::Bytes { .. }))
+        ));
+        let mut update = model_update_clip(first.shortcode.as_str());
+        update.content = format!("{}{}", content, "x".repeat(10));
+        update_clip(update, &quota, 16, &pool).await.unwrap();
+        let usage = key_usage(&api_key.digest(), &pool).await.unwrap();
+        assert_eq!((usage.clips, usage.bytes), (1, content.len() as i64 + 10));
+    }
+
+    #[tokio::test]
+    async fn test_api_keys() {
+        let pool = create_test_pool().await;
+
+        let req = ask::NewApiKey {
+            name: Some("ci".to_owned()),
+            scopes: "clip:read key:admin".parse().unwrap(),
+            ..Default::default()
+        };
+        let api_key = ApiKey::default();
+        save_api_key(&api_key, req, &pool).await.unwrap();
+        assert!(api_key_is_valid(api_key.clone(), &pool).await.unwrap());
+
+        let info = crate::web::api::KeyInfo::from(get_api_key(&api_key, &pool).await.unwrap());
+        assert_eq!(info.name.as_deref(), Some("ci"));
+        assert!(info.scopes.contains(Scope::KeyAdmin));
+        assert!(!info.scopes.contains(Scope::ClipWrite));
+        assert!(info.last_used_at.is_none());
+
+        touch_api_key(&api_key, &pool).await.unwrap();
+        let info = get_api_key(&api_key, &pool).await.unwrap();
+        assert!(info.last_used_at.is_some());
+
+        sqlx::query("UPDATE api_keys SET expires_at = 0")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(!api_key_is_valid(api_key.clone(), &pool).await.unwrap());
+        assert!(!api_key_is_valid(ApiKey::default(), &pool).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_replace_with_digest() {
+        let pool = create_test_pool().await;
+
+        let api_key = ApiKey::default();
+        sqlx::query("INSERT INTO api_keys (api_key, name) VALUES (?, 'legacy')")
+            .bind(api_key.clone().into_inner())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let mut owned = model_new_clip(random_shortcode().as_str());
+        owned.api_key = Some(api_key.clone().into_inner());
+        insert_clip(
+            owned,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        assert!(!api_key_is_valid(api_key.clone(), &pool).await.unwrap());
+
+        assert_eq!(
+            list_plaintext_api_keys(&pool).await.unwrap(),
+            vec![api_key.clone()]
+        );
+        replace_with_digest(&api_key, &pool).await.unwrap();
+        assert!(list_plaintext_api_keys(&pool).await.unwrap().is_empty());
+        assert!(api_key_is_valid(api_key.clone(), &pool).await.unwrap());
+        let info = get_api_key(&api_key, &pool).await.unwrap();
+        assert_eq!(info.name.as_deref(), Some("legacy"));
+        let usage = key_usage(&api_key.digest(), &pool).await.unwrap();
+        assert_eq!(usage.clips, 1);
+    }
+
+    #[tokio::test]
+    async fn test_list_and_revoke_api_keys() {
+        let pool = create_test_pool().await;
+
+        let api_key = ApiKey::default();
+        save_api_key(&api_key, ask::NewApiKey::default(), &pool)
+            .await
+            .unwrap();
+        save_api_key(&ApiKey::default(), ask::NewApiKey::default(), &pool)
+            .await
+            .unwrap();
+        let keys = list_api_keys(&pool).await.unwrap();
+        assert_eq!(keys.len(), 2);
+
+        let id = get_api_key(&api_key, &pool).await.unwrap().id;
+        assert_eq!(id.len(), 12);
+        assert!(keys.iter().any(|key| key.id == id));
+        assert!(matches!(
+            revoke_api_key_by_id(&id.to_uppercase(), &pool)
+                .await
+                .unwrap(),
+            RevocationStatus::Revoked
+        ));
+        assert!(matches!(
+            revoke_api_key_by_id(&id, &pool).await.unwrap(),
+            RevocationStatus::NotFound
+        ));
+        assert_eq!(list_api_keys(&pool).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let pool = create_test_pool().await;
+
+        let empty = stats(&pool).await.unwrap();
+        assert_eq!(empty.clips(), 0);
+        assert_eq!(empty.hits(), 0);
+
+        let clip = insert_clip(
+            model_new_clip("stats"),
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        let mut expired = model_new_clip("expired");
+        expired.password = None;
+        expired.expires = Some(0);
+        insert_clip(
+            expired,
+            &Quota::default(),
+            &Generator::default(),
+            compression::DEFAULT_THRESHOLD,
+            &pool,
+        )
+        .await
+        .unwrap();
+        record_hits(&ShortCode::from(clip.shortcode.as_str()), 3, &pool).await;
+
+        let totals = stats(&pool).await.unwrap();
+        assert_eq!(totals.clips(), 2);
+        assert_eq!(totals.expired(), 1);
+        assert_eq!(totals.protected(), 1);
+        assert_eq!(totals.content_bytes(), 2 * "Hello, world!".len() as u64);
+        assert_eq!(totals.hits(), 3);
+        assert_eq!(list_all_clips(10, &pool).await.unwrap().len(), 2);
+        assert_eq!(list_all_clips(1, &pool).await.unwrap().len(), 1);
+    }
 }
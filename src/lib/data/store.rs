@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use sqlx::Sqlite;
+
+use super::{model, query, query::RevocationStatus, DataError, Database};
+use crate::{
+    domain::clip::{ClipSort, ListCursor, View},
+    domain::quota::Quota,
+    web::api::{ApiKey, KeyDigest},
+    ShortCode,
+};
+
+type Result<T> = std::result::Result<T, DataError>;
+
+/// Storage for clips and API keys.
+///
+/// `service::action` only reaches the data through this trait, so clipstash runs the same
+/// on the SQLite database and on a `MemoryStore`. A missing clip, revision, attachment or
+/// key is reported as `sqlx::Error::RowNotFound` by every implementation.
+#[rocket::async_trait]
+pub trait ClipStore: Send + Sync {
+    /// Retrieves a clip.
+    async fn get_clip(&self, shortcode: &ShortCode) -> Result<model::Clip>;
+
+    /// Stores a new clip with its attachments, recording its content as revision 1.
+    ///
+    /// A clip created with an API key that would take the key over `quota` is not stored
+    /// and fails with `DataError::Quota`. Content is counted at its uncompressed size, and
+    /// the check and the insert cannot be interleaved with another write.
+    async fn insert_clip(&self, clip: model::NewClip, quota: &Quota) -> Result<model::Clip>;
+
+    /// Replaces the content, title, expiration and password of a clip, recording the new
+    /// content as its next revision.
+    ///
+    /// If the clip was created with an API key, the new content counts towards `quota` in
+    /// place of the old, checked the same way as by `insert_clip`.
+    async fn update_clip(&self, clip: model::UpdateClip, quota: &Quota) -> Result<model::Clip>;
+
+    /// Deletes a clip with its revisions and attachments.
+    async fn delete_clip(&self, shortcode: &ShortCode) -> Result<()>;
+
+    /// Deletes every expired clip, returning how many were deleted.
+    async fn delete_expired(&self) -> Result<u64>;
+
+    /// Records views of several clips at once, adding them to the hit counts and the hourly
+    /// view statistics of the clips, and skipping clips that are gone.
+    ///
+    /// A clip keeps separate counts for at most `views::MAX_REFERRERS` referrer hosts, and
+    /// views from any other host are recorded under `views::OTHER_REFERRER`.
+    async fn record_views(&self, views: &[(View, u32)]) -> Result<()>;
+
+    /// Deletes the view statistics of hours starting before `before`, in seconds since the
+    /// epoch, returning how many buckets were deleted.
+    async fn delete_old_views(&self, before: i64) -> Result<u64>;
+
+    /// Retrieves the view statistics of a clip for the hours starting between `from` and
+    /// `to`, in seconds since the epoch, oldest first.
+    async fn clip_views(
+        &self,
+        shortcode: &ShortCode,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<model::ViewBucket>>;
+
+    /// Records a view of a view-limited clip, deleting it once its last view is consumed.
+    async fn record_view(&self, shortcode: &ShortCode) -> Result<()>;
+
+    /// Lists the publicly readable clips in the given order, starting after `cursor`.
+    async fn list_clips(
+        &self,
+        sort: ClipSort,
+        cursor: Option<&ListCursor>,
+        limit: u32,
+    ) -> Result<Vec<model::Clip>>;
+
+    /// Lists every stored clip, newest first, including protected and expired ones.
+    async fn list_all_clips(&self, limit: u32) -> Result<Vec<model::Clip>>;
+
+    /// Searches the publicly readable clips, best matches first.
+    async fn search_clips(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<model::SearchHit>>;
+
+    /// Lists the revisions of a clip, oldest first.
+    async fn list_revisions(&self, shortcode: &ShortCode) -> Result<Vec<model::Revision>>;
+
+    /// Retrieves a single revision of a clip.
+    async fn get_revision(&self, shortcode: &ShortCode, revision: u32) -> Result<model::Revision>;
+
+    /// Lists the attachments of a clip, without their contents, in upload order.
+    async fn list_attachments(&self, shortcode: &ShortCode) -> Result<Vec<model::Attachment>>;
+
+    /// Retrieves an attachment of a clip together with its contents.
+    async fn get_attachment(
+        &self,
+        attachment: model::GetAttachment,
+    ) -> Result<model::AttachmentFile>;
+
+    /// Reports what the live clips created with an API key store.
+    async fn key_usage(&self, digest: &KeyDigest) -> Result<model::Usage>;
+
+    /// Counts the clips, stored bytes, hits and API keys.
+    async fn stats(&self) -> Result<model::Stats>;
+
+    /// Stores the digest of a new API key along with its details.
+    async fn save_api_key(&self, api_key: &ApiKey, details: model::NewApiKey) -> Result<()>;
+
+    /// Retrieves the details of an API key.
+    async fn get_api_key(&self, api_key: &ApiKey) -> Result<model::KeyInfo>;
+
+    /// Lists the details of every API key, oldest first.
+    async fn list_api_keys(&self) -> Result<Vec<model::KeyInfo>>;
+
+    /// Records that an API key was just used, at most once a minute.
+    async fn touch_api_key(&self, api_key: &ApiKey) -> Result<()>;
+
+    /// Checks that an API key exists and has not expired.
+    async fn api_key_is_valid(&self, api_key: &ApiKey) -> Result<bool>;
+
+    /// Revokes an API key.
+    async fn revoke_api_key(&self, api_key: &ApiKey) -> Result<RevocationStatus>;
+
+    /// Revokes the API key with the given id, as listed by `list_api_keys`.
+    async fn revoke_api_key_by_id(&self, id: &str) -> Result<RevocationStatus>;
+}
+
+/// A shared handle to the `ClipStore` the application runs on.
+#[derive(Clone)]
+pub struct Store(Arc<dyn ClipStore>);
+
+impl Store {
+    pub fn new<S: ClipStore + 'static>(store: S) -> Self {
+        Self(Arc::new(store))
+    }
+
+    pub fn get(&self) -> &dyn ClipStore {
+        self.0.as_ref()
+    }
+}
+
+impl<S: ClipStore + 'static> From<S> for Store {
+    fn from(store: S) -> Self {
+        Self::new(store)
+    }
+}
+
+#[rocket::async_trait]
+impl ClipStore for Database<Sqlite> {
+    async fn get_clip(&self, shortcode: &ShortCode) -> Result<model::Clip> {
+        query::get_clip(shortcode.clone(), self.get_pool()).await
+    }
+
+    async fn insert_clip(&self, clip: model::NewClip, quota: &Quota) -> Result<model::Clip> {
+        query::insert_clip(
+            clip,
+            quota,
+            self.shortcodes(),
+            self.compress_above(),
+            self.get_pool(),
+        )
+        .await
+    }
+
+    async fn update_clip(&self, clip: model::UpdateClip, quota: &Quota) -> Result<model::Clip> {
+        query::update_clip(clip, quota, self.compress_above(), self.get_pool()).await
+    }
+
+    async fn delete_clip(&self, shortcode: &ShortCode) -> Result<()> {
+        query::delete_clip(shortcode.clone(), self.get_pool()).await
+    }
+
+    async fn delete_expired(&self) -> Result<u64> {
+        query::delete_expired(self.get_pool()).await
+    }
+
+    async fn record_views(&self, views: &[(View, u32)]) -> Result<()> {
+        query::record_views(views, self.get_pool()).await
+    }
+
+    async fn delete_old_views(&self, before: i64) -> Result<u64> {
+        query::delete_old_views(before, self.get_pool()).await
+    }
+
+    async fn clip_views(
+        &self,
+        shortcode: &ShortCode,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<model::ViewBucket>> {
+        query::clip_views(shortcode, from, to, self.get_pool()).await
+    }
+
+    async fn record_view(&self, shortcode: &ShortCode) -> Result<()> {
+        query::record_view(shortcode, self.get_pool()).await
+    }
+
+    async fn list_clips(
+        &self,
+        sort: ClipSort,
+        cursor: Option<&ListCursor>,
+        limit: u32,
+    ) -> Result<Vec<model::Clip>> {
+        query::list_clips(sort, cursor, limit, self.get_pool()).await
+    }
+
+    async fn list_all_clips(&self, limit: u32) -> Result<Vec<model::Clip>> {
+        query::list_all_clips(limit, self.get_pool()).await
+    }
+
+    async fn search_clips(
+        &self,
+        query: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<model::SearchHit>> {
+        query::search_clips(query, limit, offset, self.get_pool()).await
+    }
+
+    async fn list_revisions(&self, shortcode: &ShortCode) -> Result<Vec<model::Revision>> {
+        query::list_revisions(shortcode, self.get_pool()).await
+    }
+
+    async fn get_revision(&self, shortcode: &ShortCode, revision: u32) -> Result<model::Revision> {
+        query::get_revision(shortcode, revision, self.get_pool()).await
+    }
+
+    async fn list_attachments(&self, shortcode: &ShortCode) -> Result<Vec<model::Attachment>> {
+        query::list_attachments(shortcode, self.get_pool()).await
+    }
+
+    async fn get_attachment(
+        &self,
+        attachment: model::GetAttachment,
+    ) -> Result<model::AttachmentFile> {
+        query::get_attachment(attachment, self.get_pool()).await
+    }
+
+    async fn key_usage(&self, digest: &KeyDigest) -> Result<model::Usage> {
+        query::key_usage(digest, self.get_pool()).await
+    }
+
+    async fn stats(&self) -> Result<model::Stats> {
+        query::stats(self.get_pool()).await
+    }
+
+    async fn save_api_key(&self, api_key: &ApiKey, details: model::NewApiKey) -> Result<()> {
+        query::save_api_key(api_key, details, self.get_pool()).await
+    }
+
+    async fn get_api_key(&self, api_key: &ApiKey) -> Result<model::KeyInfo> {
+        query::get_api_key(api_key, self.get_pool()).await
+    }
+
+    async fn list_api_keys(&self) -> Result<Vec<model::KeyInfo>> {
+        query::list_api_keys(self.get_pool()).await
+    }
+
+    async fn touch_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        query::touch_api_key(api_key, self.get_pool()).await
+    }
+
+    async fn api_key_is_valid(&self, api_key: &ApiKey) -> Result<bool> {
+        query::api_key_is_valid(api_key.clone(), self.get_pool()).await
+    }
+
+    async fn revoke_api_key(&self, api_key: &ApiKey) -> Result<RevocationStatus> {
+        query::revoke_api_key(api_key.clone(), self.get_pool()).await
+    }
+
+    async fn revoke_api_key_by_id(&self, id: &str) -> Result<RevocationStatus> {
+        query::revoke_api_key_by_id(id, self.get_pool()).await
+    }
+}
@@ -0,0 +1,19 @@
+use super::field;
+use serde::{Deserialize, Serialize};
+
+/// A file stored alongside a clip.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Attachment {
+    pub attachment_id: field::AttachmentId,
+    pub filename: field::FileName,
+    pub mime_type: field::MimeType,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+/// An attachment together with its contents, as served for download.
+#[derive(Debug, Clone)]
+pub struct AttachmentFile {
+    pub attachment: Attachment,
+    pub data: Vec<u8>,
+}
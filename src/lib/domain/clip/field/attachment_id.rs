@@ -0,0 +1,41 @@
+use derive_more::Constructor;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::data::DbId;
+use crate::domain::clip::ClipError;
+
+#[derive(Debug, Clone, Constructor, Serialize, Deserialize, PartialEq)]
+pub struct AttachmentId(DbId);
+
+impl AttachmentId {
+    pub fn into_inner(self) -> DbId {
+        self.0
+    }
+}
+
+impl From<DbId> for AttachmentId {
+    fn from(id: DbId) -> Self {
+        Self(id)
+    }
+}
+
+impl FromStr for AttachmentId {
+    type Err = ClipError;
+    fn from_str(id: &str) -> Result<Self, Self::Err> {
+        Ok(Self(DbId::from_str(id)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attachment_id_from_str() {
+        let db_id = DbId::new();
+        let id = AttachmentId::from_str(&db_id.to_string()).unwrap();
+        assert_eq!(id.into_inner(), db_id);
+        assert!(AttachmentId::from_str("not-an-id").is_err());
+    }
+}
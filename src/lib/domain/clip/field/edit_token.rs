@@ -0,0 +1,93 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+
+const TOKEN_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct EditToken(Option<String>);
+
+impl EditToken {
+    pub fn new<T: Into<Option<String>>>(token: T) -> Self {
+        match token.into() {
+            Some(token) if !token.trim().is_empty() => Self(Some(token)),
+            _ => Self(None),
+        }
+    }
+
+    pub fn generate() -> Self {
+        let token = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect::<String>();
+        Self(Some(token))
+    }
+
+    pub fn into_inner(self) -> Option<String> {
+        self.0
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    pub fn has_token(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Returns `true` if both tokens are present and equal, comparing them in constant time.
+    pub fn grants(&self, other: &EditToken) -> bool {
+        match (&self.0, &other.0) {
+            (Some(expected), Some(provided)) => {
+                expected.as_bytes().ct_eq(provided.as_bytes()).into()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for EditToken {
+    type Err = std::convert::Infallible;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::new(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_edit_token() {
+        let token = EditToken::new("secret".to_string());
+        assert!(token.has_token());
+        assert_eq!(token.as_str(), Some("secret"));
+
+        let token = EditToken::new("  ".to_string());
+        assert!(!token.has_token());
+
+        let token = EditToken::new(None);
+        assert_eq!(token.into_inner(), None);
+    }
+
+    #[test]
+    fn test_generate() {
+        let token = EditToken::generate();
+        let raw = token.as_str().unwrap();
+        assert_eq!(raw.len(), TOKEN_LENGTH);
+        assert!(raw.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(token, EditToken::generate());
+    }
+
+    #[test]
+    fn test_grants() {
+        let token = EditToken::new("secret".to_string());
+        assert!(token.grants(&EditToken::new("secret".to_string())));
+        assert!(!token.grants(&EditToken::new("other".to_string())));
+        assert!(!token.grants(&EditToken::new("secreT".to_string())));
+        assert!(!token.grants(&EditToken::new("secrets".to_string())));
+        assert!(!token.grants(&EditToken::default()));
+        assert!(!EditToken::default().grants(&EditToken::default()));
+    }
+}
@@ -1,9 +1,18 @@
 use crate::domain::{clip::ClipError, time::Time};
+use chrono::{Duration, Utc};
 use rocket::form;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// When a clip expires, if ever.
+///
+/// Expiries can be given as a relative duration such as `10m`, `2h`, `7d` or `1w`,
+/// a bare `YYYY-MM-DD` date (midnight UTC) or an RFC 3339 timestamp with an offset.
+///
+/// Parsing and deserializing accept any time, so clips read back from the server or the
+/// database never fail to parse once they expire. Expiries given for new clips, updates
+/// and API keys are checked with [`Expires::check_future`] where that input is accepted.
+#[derive(Debug, Clone, Serialize, PartialEq)]
 pub struct Expires(Option<Time>);
 
 impl Expires {
@@ -13,6 +22,16 @@ impl Expires {
     pub fn into_inner(self) -> Option<Time> {
         self.0
     }
+
+    /// Rejects expiries that are not in the future.
+    pub fn check_future(&self) -> Result<(), ClipError> {
+        match &self.0 {
+            Some(time) if time.timestamp() <= Utc::now().timestamp() => {
+                Err(ClipError::InvalidDate("expiry is in the past".to_owned()))
+            }
+            _ => Ok(()),
+        }
+    }
 }
 
 impl Default for Expires {
@@ -21,16 +40,42 @@ impl Default for Expires {
     }
 }
 
+/// Parses a relative duration made of a number and one of the units `m`, `h`, `d` or `w`.
+fn parse_duration(raw: &str) -> Option<Duration> {
+    let unit = raw.chars().last()?;
+    let amount = i64::from(raw[..raw.len() - unit.len_utf8()].parse::<u32>().ok()?);
+    match unit {
+        'm' => Some(Duration::minutes(amount)),
+        'h' => Some(Duration::hours(amount)),
+        'd' => Some(Duration::days(amount)),
+        'w' => Some(Duration::weeks(amount)),
+        _ => None,
+    }
+}
+
 impl FromStr for Expires {
     type Err = ClipError;
     fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let raw = raw.trim();
         if raw.is_empty() {
-            Ok(Self(None))
-        } else {
-            match Time::from_str(raw) {
-                Ok(time) => Ok(Self::new(time)),
-                Err(e) => Err(e.into()),
-            }
+            return Ok(Self(None));
+        }
+        let time = match parse_duration(raw) {
+            Some(duration) => Utc::now()
+                .checked_add_signed(duration)
+                .ok_or_else(|| ClipError::InvalidDate("expiry is too far away".to_owned()))?
+                .into(),
+            None => Time::from_str(raw)?,
+        };
+        Ok(Self::new(time))
+    }
+}
+
+impl<'de> Deserialize<'de> for Expires {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(raw) => Self::from_str(&raw).map_err(serde::de::Error::custom),
+            None => Ok(Self(None)),
         }
     }
 }
@@ -38,12 +83,14 @@ impl FromStr for Expires {
 #[rocket::async_trait]
 impl<'r> form::FromFormField<'r> for Expires {
     fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
-        if field.value.trim().is_empty() {
-            Ok(Self(None))
-        } else {
-            Ok(Self::from_str(field.value)
-                .map_err(|e| form::Error::validation(format!("{}", e)))?)
-        }
+        let expires = Self::from_str(field.value)
+            .and_then(|expires| expires.check_future().map(|_| expires))
+            .map_err(|e| form::Error::validation(format!("{}", e)))?;
+        Ok(expires)
+    }
+
+    fn default() -> Option<Self> {
+        Some(Self(None))
     }
 }
 
@@ -73,12 +120,69 @@ mod tests {
         assert_eq!(expires.unwrap().into_inner(), None);
     }
 
+    #[test]
+    fn test_from_str_relative() {
+        for (raw, duration) in [
+            ("10m", Duration::minutes(10)),
+            ("2h", Duration::hours(2)),
+            ("7d", Duration::days(7)),
+            ("1w", Duration::weeks(1)),
+        ] {
+            let expected = (Utc::now() + duration).timestamp();
+            let expires = Expires::from_str(raw).unwrap().into_inner().unwrap();
+            assert!((expires.timestamp() - expected).abs() <= 1);
+        }
+        assert!(Expires::from_str("10s").is_err());
+        assert!(Expires::from_str("m").is_err());
+        assert!(Expires::from_str("-1h").is_err());
+    }
+
+    #[test]
+    fn test_from_str_rfc3339() {
+        let expires = Expires::from_str("2999-05-01T17:00:00+01:00").unwrap();
+        assert_eq!(
+            expires.into_inner(),
+            Some(Time::from_str("2999-05-01T16:00:00Z").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_check_future() {
+        let past = Expires::from_str("1997-05-01").unwrap();
+        assert!(matches!(
+            past.check_future(),
+            Err(ClipError::InvalidDate(_))
+        ));
+        let now = Expires::from_str("0m").unwrap();
+        assert!(now.check_future().is_err());
+        assert!(Expires::from_str("1h").unwrap().check_future().is_ok());
+        assert!(<Expires as Default>::default().check_future().is_ok());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let expires: Expires = serde_json::from_str("\"2h\"").unwrap();
+        assert!(expires.into_inner().is_some());
+        let expires: Expires = serde_json::from_str("null").unwrap();
+        assert_eq!(expires.into_inner(), None);
+        let expired: Expires = serde_json::from_str("\"1997-05-01T00:00:00Z\"").unwrap();
+        assert_eq!(
+            expired.into_inner(),
+            Some(Time::from_str("1997-05-01").unwrap())
+        );
+    }
+
     #[test]
     fn test_from_value() {
-        let field = form::ValueField::parse("expires=1997-05-01");
+        let field = form::ValueField::parse("expires=2999-05-01");
         let result = Expires::from_value(field);
-        let expected = Expires::from_str("1997-05-01").unwrap();
+        let expected = Expires::from_str("2999-05-01").unwrap();
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), expected);
+        assert!(Expires::from_value(form::ValueField::parse("expires=1997-05-01")).is_err());
+        assert_eq!(
+            Expires::from_value(form::ValueField::parse("expires=")).unwrap(),
+            <Expires as Default>::default()
+        );
     }
 }
@@ -0,0 +1,71 @@
+use crate::domain::clip::ClipError;
+use serde::{Deserialize, Serialize};
+
+/// The name an attachment was uploaded with, and is offered for download as.
+///
+/// Any directory components are dropped, along with control characters and quotes,
+/// so the name can be placed in a `Content-Disposition` header as is.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FileName(String);
+
+impl FileName {
+    /// The longest file name accepted, in bytes.
+    pub const MAX_LEN: usize = 255;
+
+    pub fn new(name: &str) -> Result<Self, ClipError> {
+        let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+        let name: String = base
+            .chars()
+            .filter(|c| !c.is_control() && *c != '"')
+            .collect::<String>()
+            .trim()
+            .to_owned();
+        if name.is_empty() || name == "." || name == ".." {
+            Err(ClipError::InvalidAttachment(
+                "attachments must have a file name".to_owned(),
+            ))
+        } else if name.len() > Self::MAX_LEN {
+            Err(ClipError::InvalidAttachment(format!(
+                "file names must be at most {} bytes long",
+                Self::MAX_LEN
+            )))
+        } else {
+            Ok(Self(name))
+        }
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(FileName::new("shot.png").unwrap().as_str(), "shot.png");
+        assert_eq!(
+            FileName::new("../../etc/passwd").unwrap().as_str(),
+            "passwd"
+        );
+        assert_eq!(
+            FileName::new("C:\\Users\\me\\core.dump").unwrap().as_str(),
+            "core.dump"
+        );
+        assert_eq!(FileName::new("a\"b\r\n.txt").unwrap().as_str(), "ab.txt");
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(FileName::new("").is_err());
+        assert!(FileName::new("dir/").is_err());
+        assert!(FileName::new("..").is_err());
+        assert!(FileName::new(&"a".repeat(256)).is_err());
+    }
+}
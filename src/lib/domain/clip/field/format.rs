@@ -0,0 +1,79 @@
+use crate::domain::clip::ClipError;
+use rocket::form;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// How a clip's content is displayed.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    /// Shown as source, highlighted according to the clip's language.
+    #[default]
+    Text,
+    /// Rendered as sanitized HTML, with the source available alongside.
+    Markdown,
+}
+
+impl Format {
+    pub fn new(format: &str) -> Result<Self, ClipError> {
+        Self::from_str(format.trim())
+            .map_err(|_| ClipError::InvalidFormat("format must be text or markdown".to_owned()))
+    }
+
+    pub fn is_markdown(&self) -> bool {
+        matches!(self, Self::Markdown)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> form::FromFormField<'r> for Format {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        if field.value.trim().is_empty() {
+            return Ok(Self::Text);
+        }
+        Ok(Self::new(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    fn default() -> Option<Self> {
+        Some(Self::Text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(Format::new("text").unwrap(), Format::Text);
+        assert_eq!(Format::new(" markdown ").unwrap(), Format::Markdown);
+        assert!(Format::new("html").is_err());
+        assert!(Format::new("").is_err());
+    }
+
+    #[test]
+    fn test_serde() {
+        assert_eq!(
+            serde_json::to_string(&Format::Markdown).unwrap(),
+            "\"markdown\""
+        );
+        assert_eq!(
+            serde_json::from_str::<Format>("\"text\"").unwrap(),
+            Format::Text
+        );
+        assert_eq!(Format::Markdown.as_ref(), "markdown");
+    }
+}
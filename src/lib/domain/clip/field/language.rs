@@ -0,0 +1,207 @@
+use crate::domain::clip::ClipError;
+use rocket::form;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// The language a clip is written in, such as `rust`, `json` or `yaml`.
+///
+/// Languages are lowercase tokens matching a syntax name or file extension; clips
+/// without a language are shown as plain text.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "Option<String>")]
+pub struct Language(Option<String>);
+
+impl Language {
+    pub fn new<T: Into<Option<String>>>(language: T) -> Result<Self, ClipError> {
+        let language = match language.into() {
+            Some(language) => language.trim().to_lowercase(),
+            None => return Ok(Self(None)),
+        };
+        if language.is_empty() {
+            Ok(Self(None))
+        } else if language.len() > 32
+            || !language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+#._-".contains(c))
+        {
+            Err(ClipError::InvalidLanguage(language))
+        } else {
+            Ok(Self(Some(language)))
+        }
+    }
+
+    /// Guesses the language of some content, for clips posted without one.
+    pub fn detect(content: &str) -> Self {
+        Self(detect(content).map(str::to_owned))
+    }
+
+    pub fn into_inner(self) -> Option<String> {
+        self.0
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+fn detect(content: &str) -> Option<&'static str> {
+    let trimmed = content.trim_start();
+    let first_line = trimmed.lines().next().unwrap_or_default();
+    if let Some(interpreter) = first_line.strip_prefix("#!") {
+        return [
+            ("python", "python"),
+            ("node", "javascript"),
+            ("ruby", "ruby"),
+            ("perl", "perl"),
+            ("bash", "bash"),
+            ("sh", "bash"),
+        ]
+        .into_iter()
+        .find(|(name, _)| interpreter.contains(name))
+        .map(|(_, language)| language);
+    }
+    let lowercase = first_line.to_lowercase();
+    if lowercase.starts_with("<?xml") {
+        return Some("xml");
+    }
+    if lowercase.starts_with("<!doctype html") || lowercase.starts_with("<html") {
+        return Some("html");
+    }
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return Some("json");
+    }
+    if ["select ", "insert ", "update ", "delete ", "create "]
+        .iter()
+        .any(|keyword| lowercase.starts_with(keyword))
+    {
+        return Some("sql");
+    }
+    if ["fn ", "use std::", "#[derive(", "impl ", "pub fn "]
+        .iter()
+        .any(|marker| content.contains(marker))
+        && (content.contains("->") || content.contains("let ") || content.contains("::"))
+    {
+        return Some("rust");
+    }
+
+    let lines: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let is_key = |key: &str| {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    };
+    let count = |predicate: &dyn Fn(&str) -> bool| lines.iter().filter(|l| predicate(l)).count();
+    let sections = count(&|line| line.starts_with('[') && line.ends_with(']'));
+    let assignments = count(&|line| {
+        line.split_once(" = ")
+            .is_some_and(|(key, _)| is_key(key.trim()))
+    });
+    if sections > 0 && assignments > 0 && sections + assignments == lines.len() {
+        return Some("toml");
+    }
+    let mappings = count(&|line| {
+        let line = line.strip_prefix("- ").unwrap_or(line);
+        line.split_once(':')
+            .is_some_and(|(key, value)| is_key(key) && (value.is_empty() || value.starts_with(' ')))
+    });
+    let items = count(&|line| line.starts_with("- "));
+    if first_line.trim_end() == "---"
+        || (lines.len() >= 2 && mappings > 0 && mappings + items >= lines.len())
+    {
+        return Some("yaml");
+    }
+    None
+}
+
+impl TryFrom<Option<String>> for Language {
+    type Error = ClipError;
+    fn try_from(language: Option<String>) -> Result<Self, Self::Error> {
+        Self::new(language)
+    }
+}
+
+impl FromStr for Language {
+    type Err = ClipError;
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Self::new(raw.to_owned())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> form::FromFormField<'r> for Language {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    fn default() -> Option<Self> {
+        Some(Self(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            Language::new("Rust".to_owned()).unwrap().as_str(),
+            Some("rust")
+        );
+        assert_eq!(
+            Language::new("c++".to_owned()).unwrap().as_str(),
+            Some("c++")
+        );
+        assert!(!Language::new("  ".to_owned()).unwrap().is_set());
+        assert!(!Language::new(None).unwrap().is_set());
+        assert!(Language::new("<script>".to_owned()).is_err());
+        assert!(Language::new("x".repeat(33)).is_err());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let language: Language = serde_json::from_str("\"YAML\"").unwrap();
+        assert_eq!(language.as_str(), Some("yaml"));
+        let language: Language = serde_json::from_str("null").unwrap();
+        assert!(!language.is_set());
+        assert!(serde_json::from_str::<Language>("\"a b\"").is_err());
+    }
+
+    #[test]
+    fn test_detect() {
+        let detect = |content: &str| Language::detect(content).into_inner();
+        assert_eq!(
+            detect("#!/usr/bin/env python3\nprint(1)"),
+            Some("python".into())
+        );
+        assert_eq!(detect("#!/bin/sh\necho hi"), Some("bash".into()));
+        assert_eq!(detect("{\"a\": [1, 2]}"), Some("json".into()));
+        assert_eq!(detect("<?xml version=\"1.0\"?><a/>"), Some("xml".into()));
+        assert_eq!(detect("SELECT * FROM clips;"), Some("sql".into()));
+        assert_eq!(
+            detect("fn main() {\n    let x = 1;\n}"),
+            Some("rust".into())
+        );
+        assert_eq!(
+            detect("[package]\nname = \"clipstash\"\nversion = \"0.1.0\""),
+            Some("toml".into())
+        );
+        assert_eq!(
+            detect("server:\n  port: 8000\n  hosts:\n    - a\n    - b"),
+            Some("yaml".into())
+        );
+        assert_eq!(detect("just some notes\nabout things"), None);
+    }
+}
@@ -0,0 +1,91 @@
+use crate::domain::clip::ClipError;
+use rocket::form;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct MaxViews(Option<u32>);
+
+impl MaxViews {
+    pub fn new<T: Into<Option<u32>>>(max_views: T) -> Result<Self, ClipError> {
+        match max_views.into() {
+            Some(0) => Err(ClipError::InvalidMaxViews(
+                "a clip must allow at least one view".to_owned(),
+            )),
+            max_views => Ok(Self(max_views)),
+        }
+    }
+
+    pub fn into_inner(self) -> Option<u32> {
+        self.0
+    }
+
+    pub fn is_limited(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl FromStr for MaxViews {
+    type Err = ClipError;
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            Ok(Self(None))
+        } else {
+            match raw.parse::<u32>() {
+                Ok(max_views) => Self::new(max_views),
+                Err(e) => Err(ClipError::InvalidMaxViews(e.to_string())),
+            }
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> form::FromFormField<'r> for MaxViews {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    fn default() -> Option<Self> {
+        Some(Self(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::form::FromFormField;
+
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(MaxViews::new(3).unwrap().into_inner(), Some(3));
+        assert_eq!(MaxViews::new(None).unwrap().into_inner(), None);
+        assert!(MaxViews::new(0).is_err());
+    }
+
+    #[test]
+    fn test_is_limited() {
+        assert!(MaxViews::new(1).unwrap().is_limited());
+        assert!(!MaxViews::new(None).unwrap().is_limited());
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(
+            MaxViews::from_str("").unwrap(),
+            MaxViews::new(None).unwrap()
+        );
+        assert_eq!(MaxViews::from_str("5").unwrap(), MaxViews::new(5).unwrap());
+        assert!(MaxViews::from_str("0").is_err());
+        assert!(MaxViews::from_str("-1").is_err());
+        assert!(MaxViews::from_str("many").is_err());
+    }
+
+    #[test]
+    fn test_from_value() {
+        let field = form::ValueField::parse("max_views=1");
+        let result = MaxViews::from_value(field);
+        assert_eq!(result.unwrap(), MaxViews::new(1).unwrap());
+    }
+}
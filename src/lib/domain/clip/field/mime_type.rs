@@ -0,0 +1,51 @@
+use crate::domain::clip::ClipError;
+use rocket::http::ContentType;
+use serde::{Deserialize, Serialize};
+
+/// The media type of an attachment, such as `image/png`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MimeType(String);
+
+impl MimeType {
+    pub fn new(mime_type: &str) -> Result<Self, ClipError> {
+        ContentType::parse_flexible(mime_type.trim())
+            .map(|content_type| Self(content_type.to_string()))
+            .ok_or_else(|| {
+                ClipError::InvalidAttachment(format!("invalid media type: {}", mime_type))
+            })
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Default for MimeType {
+    fn default() -> Self {
+        Self(ContentType::Binary.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(MimeType::new("image/png").unwrap().as_str(), "image/png");
+        assert_eq!(
+            MimeType::new("text/plain; charset=utf-8").unwrap().as_str(),
+            "text/plain; charset=utf-8"
+        );
+        assert!(MimeType::new("not a type").is_err());
+    }
+
+    #[test]
+    fn test_default() {
+        assert_eq!(MimeType::default().as_str(), "application/octet-stream");
+    }
+}
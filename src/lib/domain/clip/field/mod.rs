@@ -1,9 +1,21 @@
 mod clip_id;
 pub use clip_id::ClipId;
 
-mod shortcode;
+mod attachment_id;
+pub use attachment_id::AttachmentId;
+
+mod file_name;
+pub use file_name::FileName;
+
+mod mime_type;
+pub use mime_type::MimeType;
+
+pub mod shortcode;
 pub use shortcode::ShortCode;
 
+mod vanity;
+pub use vanity::VanityShortCode;
+
 mod content;
 pub use content::Content;
 
@@ -19,5 +31,23 @@ pub use expires::Expires;
 mod password;
 pub use password::Password;
 
+mod password_hash;
+pub use password_hash::PasswordHash;
+
+mod format;
+pub use format::Format;
+
 mod hits;
 pub use hits::Hits;
+
+mod edit_token;
+pub use edit_token::EditToken;
+
+mod language;
+pub use language::Language;
+
+mod max_views;
+pub use max_views::MaxViews;
+
+mod views;
+pub use views::Views;
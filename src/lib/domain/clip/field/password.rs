@@ -23,6 +23,10 @@ impl Password {
         self.0
     }
 
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_deref()
+    }
+
     pub fn has_password(&self) -> bool {
         self.0.is_some()
     }
@@ -47,6 +51,10 @@ impl<'r> form::FromFormField<'r> for Password {
         Ok(Self::new(field.value.to_owned())
             .map_err(|e| form::Error::validation(format!("{}", e)))?)
     }
+
+    fn default() -> Option<Self> {
+        Some(Self(None))
+    }
 }
 
 #[cfg(test)]
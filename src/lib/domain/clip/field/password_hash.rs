@@ -0,0 +1,95 @@
+use super::Password;
+use crate::domain::clip::ClipError;
+use argon2::password_hash::{rand_core::OsRng, SaltString};
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+
+/// The Argon2id hash of a clip password, in PHC string format.
+///
+/// Clips only ever carry the hash of their password; plaintext passwords are
+/// limited to the requests that set or unlock a clip.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PasswordHash(Option<String>);
+
+impl PasswordHash {
+    /// Wraps a hash as stored in the database.
+    pub fn new<T: Into<Option<String>>>(hash: T) -> Self {
+        Self(hash.into())
+    }
+
+    /// Hashes a password with Argon2id and a random salt.
+    pub fn from_password(password: &Password) -> Result<Self, ClipError> {
+        match password.as_str() {
+            Some(password) => {
+                let salt = SaltString::generate(&mut OsRng);
+                let hash = Argon2::default()
+                    .hash_password(password.as_bytes(), &salt)
+                    .map_err(|e| ClipError::PasswordHash(e.to_string()))?;
+                Ok(Self(Some(hash.to_string())))
+            }
+            None => Ok(Self(None)),
+        }
+    }
+
+    /// Returns whether a stored password is already an Argon2 hash, as opposed to a
+    /// plaintext password left over from before passwords were hashed.
+    pub fn is_hash(raw: &str) -> bool {
+        argon2::PasswordHash::new(raw).is_ok()
+    }
+
+    pub fn into_inner(self) -> Option<String> {
+        self.0
+    }
+
+    pub fn has_password(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Checks a password against this hash in constant time.
+    ///
+    /// A clip without a password accepts any password.
+    pub fn verify(&self, password: &Password) -> bool {
+        match (&self.0, password.as_str()) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(hash), Some(password)) => match argon2::PasswordHash::new(hash) {
+                Ok(hash) => Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .is_ok(),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_password() {
+        let password = Password::new("password123".to_string()).unwrap();
+        let hash = PasswordHash::from_password(&password).unwrap();
+        assert!(hash.has_password());
+        let raw = hash.clone().into_inner().unwrap();
+        assert!(raw.starts_with("$argon2id$"));
+        assert!(!raw.contains("password123"));
+        assert!(PasswordHash::is_hash(&raw));
+        assert!(!PasswordHash::is_hash("password123"));
+
+        let hash = PasswordHash::from_password(&Password::default()).unwrap();
+        assert!(!hash.has_password());
+    }
+
+    #[test]
+    fn test_verify() {
+        let password = Password::new("password123".to_string()).unwrap();
+        let hash = PasswordHash::from_password(&password).unwrap();
+        assert!(hash.verify(&password));
+        assert!(!hash.verify(&Password::new("password124".to_string()).unwrap()));
+        assert!(!hash.verify(&Password::default()));
+
+        let hash = PasswordHash::default();
+        assert!(hash.verify(&password));
+        assert!(hash.verify(&Password::default()));
+    }
+}
@@ -7,25 +7,142 @@ use serde::{Deserialize, Serialize};
 
 use super::super::ClipError;
 
+/// The length of generated shortcodes unless configured otherwise.
+pub const DEFAULT_LENGTH: usize = 10;
+
+/// The characters generated shortcodes are drawn from unless configured otherwise.
+///
+/// Lowercase letters and digits, leaving out `0`, `1`, `i`, `l` and `o`, which are easily
+/// mistaken for one another when a link is read out or copied by hand.
+pub const DEFAULT_ALPHABET: &str = "abcdefghjkmnpqrstuvwxyz23456789";
+
+/// The number of words in word-based shortcodes unless configured otherwise.
+pub const DEFAULT_WORDS: usize = 4;
+
+/// The longest shortcode accepted.
+pub const MAX_LENGTH: usize = 64;
+
+/// Joins the words of word-based shortcodes.
+pub const WORD_SEPARATOR: char = '-';
+
+const WORDS: &str = include_str!("words.txt");
+
+/// How shortcodes are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Characters drawn at random from an alphabet, such as `k3vq8zr2mw`.
+    #[default]
+    Random,
+    /// Dictionary words joined by dashes, such as `otter-maple-comet-linen`.
+    Words,
+}
+
+/// Generates random shortcodes.
+///
+/// The generator in use is configured once and handed to the store, which draws the
+/// shortcodes of new clips from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Generator {
+    Random { length: usize, alphabet: Vec<char> },
+    Words { count: usize },
+}
+
+impl Generator {
+    /// A generator drawing `length` characters from `alphabet`.
+    pub fn random(length: usize, alphabet: &str) -> Result<Self, ClipError> {
+        let mut chars: Vec<char> = alphabet.chars().collect();
+        chars.sort_unstable();
+        chars.dedup();
+        if length == 0 || length > MAX_LENGTH {
+            return Err(ClipError::InvalidShortCode(format!(
+                "length must be between 1 and {}",
+                MAX_LENGTH
+            )));
+        }
+        if chars.len() < 2 {
+            return Err(ClipError::InvalidShortCode(
+                "alphabet needs at least two distinct characters".to_owned(),
+            ));
+        }
+        if let Some(c) = chars.iter().find(|c| !c.is_ascii_alphanumeric()) {
+            return Err(ClipError::InvalidShortCode(format!(
+                "alphabet may only hold letters and digits, not {:?}",
+                c
+            )));
+        }
+        Ok(Self::Random {
+            length,
+            alphabet: chars,
+        })
+    }
+
+    /// A generator joining `count` words from the built-in word list.
+    pub fn words(count: usize) -> Result<Self, ClipError> {
+        let longest = WORDS.lines().map(str::len).max().unwrap_or(0);
+        if count == 0 || count * (longest + 1) - 1 > MAX_LENGTH {
+            return Err(ClipError::InvalidShortCode(format!(
+                "word count must be between 1 and {}",
+                (MAX_LENGTH + 1) / (longest + 1)
+            )));
+        }
+        Ok(Self::Words { count })
+    }
+
+    pub fn generate(&self) -> String {
+        let mut rng = thread_rng();
+        match self {
+            Self::Random { length, alphabet } => (0..*length)
+                .map(|_| {
+                    *alphabet
+                        .choose(&mut rng)
+                        .expect("sampling array should have values")
+                })
+                .collect(),
+            Self::Words { count } => {
+                let words: Vec<&str> = WORDS.lines().collect();
+                (0..*count)
+                    .map(|_| *words.choose(&mut rng).expect("word list should have words"))
+                    .collect::<Vec<_>>()
+                    .join(&WORD_SEPARATOR.to_string())
+            }
+        }
+    }
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Self::random(DEFAULT_LENGTH, DEFAULT_ALPHABET).expect("default alphabet should be valid")
+    }
+}
+
 #[derive(
     Debug, Clone, Serialize, Deserialize, PartialEq, From, UriDisplayPath, UriDisplayQuery, Eq, Hash,
 )]
 pub struct ShortCode(String);
 
 impl ShortCode {
-    pub fn new() -> Self {
-        let allowed_chars = ['a', 'b', 'c', 'd', '1', '2', '3', '4'];
-
-        let mut rng = thread_rng();
-        let mut shortcode = String::with_capacity(10);
-        for _ in 0..10 {
-            shortcode.push(
-                *allowed_chars
-                    .choose(&mut rng)
-                    .expect("sampling array should have values"),
-            );
+    /// Checks the syntax of a shortcode: letters, digits and single dashes between them,
+    /// at most [`MAX_LENGTH`] long.
+    ///
+    /// This is only a syntax check, not a check against the configured [`Generator`].
+    /// Clips keep the shortcodes they were created with, so a shortcode drawn from an
+    /// earlier alphabet or requested for the clip, such as `l0g-1nfo`, must still parse.
+    pub fn validate(shortcode: &str) -> Result<(), ClipError> {
+        let invalid = |reason: &str| Err(ClipError::InvalidShortCode(reason.to_owned()));
+        if shortcode.is_empty() {
+            return invalid("empty shortcode");
+        }
+        if shortcode.len() > MAX_LENGTH {
+            return invalid(&format!("longer than {} characters", MAX_LENGTH));
+        }
+        let parts_ok = shortcode
+            .split(WORD_SEPARATOR)
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()));
+        if !parts_ok {
+            return invalid("only letters, digits and single dashes between them are allowed");
         }
-        Self(shortcode)
+        Ok(())
     }
 
     pub fn as_str(&self) -> &str {
@@ -37,12 +154,6 @@ impl ShortCode {
     }
 }
 
-impl Default for ShortCode {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl From<ShortCode> for String {
     fn from(shortcode: ShortCode) -> Self {
         shortcode.0
@@ -58,15 +169,16 @@ impl From<&str> for ShortCode {
 impl FromStr for ShortCode {
     type Err = ClipError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::validate(s)?;
         Ok(Self(s.into()))
     }
 }
 
 impl<'r> FromParam<'r> for ShortCode {
-    type Error = &'r str;
+    type Error = ClipError;
 
     fn from_param(param: &'r str) -> Result<Self, Self::Error> {
-        Ok(ShortCode::from(param))
+        ShortCode::from_str(param)
     }
 }
 
@@ -74,14 +186,73 @@ impl<'r> FromParam<'r> for ShortCode {
 mod tests {
     use super::*;
 
+    fn shortcode() -> ShortCode {
+        ShortCode::from(Generator::default().generate())
+    }
+
     #[test]
-    fn test_new() {
-        let shortcode = ShortCode::new();
-        assert_eq!(shortcode.0.len(), 10);
-        assert!(shortcode
-            .0
-            .chars()
-            .all(|c| ['a', 'b', 'c', 'd', '1', '2', '3', '4'].contains(&c)));
+    fn test_default_generator() {
+        let shortcode = shortcode();
+        assert_eq!(shortcode.0.len(), DEFAULT_LENGTH);
+        assert!(shortcode.0.chars().all(|c| DEFAULT_ALPHABET.contains(c)));
+    }
+
+    #[test]
+    fn test_random_generator() {
+        let generator = Generator::random(6, "xyz789").unwrap();
+        let shortcode = generator.generate();
+        assert_eq!(shortcode.len(), 6);
+        assert!(shortcode.chars().all(|c| "xyz789".contains(c)));
+
+        assert!(Generator::random(0, "xyz789").is_err());
+        assert!(Generator::random(MAX_LENGTH + 1, "xyz789").is_err());
+        assert!(Generator::random(6, "xxx").is_err());
+        assert!(Generator::random(6, "ab/").is_err());
+    }
+
+    #[test]
+    fn test_words_generator() {
+        let shortcode = Generator::words(3).unwrap().generate();
+        let words: Vec<&str> = shortcode.split(WORD_SEPARATOR).collect();
+        assert_eq!(words.len(), 3);
+        assert!(words.iter().all(|word| WORDS.lines().any(|w| w == *word)));
+        assert!(ShortCode::validate(&shortcode).is_ok());
+
+        assert!(Generator::words(0).is_err());
+        assert!(Generator::words(MAX_LENGTH).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        for valid in [
+            "abcd1234",
+            "otter-maple-comet",
+            "X",
+            "l0g-1nfo",
+            &"a".repeat(MAX_LENGTH),
+        ] {
+            assert!(ShortCode::validate(valid).is_ok(), "{}", valid);
+        }
+        for invalid in [
+            "",
+            "-abc",
+            "abc-",
+            "ab--cd",
+            "ab cd",
+            "ab/cd",
+            "abc.txt",
+            "héllo",
+            &"a".repeat(MAX_LENGTH + 1),
+        ] {
+            assert!(
+                matches!(
+                    ShortCode::validate(invalid),
+                    Err(ClipError::InvalidShortCode(_))
+                ),
+                "{}",
+                invalid
+            );
+        }
     }
 
     #[test]
@@ -97,20 +268,11 @@ mod tests {
         assert_eq!(inner, "abcd1234ef");
     }
 
-    #[test]
-    fn test_default() {
-        let shortcode = ShortCode::default();
-        assert_eq!(shortcode.0.len(), 10);
-    }
-
     #[test]
     fn test_from() {
-        let shortcode = ShortCode::new();
+        let shortcode = shortcode();
         let string: String = shortcode.clone().into();
-        assert_eq!(string.len(), 10);
-        assert!(string
-            .chars()
-            .all(|c| ['a', 'b', 'c', 'd', '1', '2', '3', '4'].contains(&c)));
+        assert_eq!(string, shortcode.as_str());
 
         let shortcode_from_str = ShortCode::from(shortcode.as_str());
         assert_eq!(shortcode_from_str, shortcode);
@@ -118,15 +280,16 @@ mod tests {
 
     #[test]
     fn test_from_str() {
-        let shortcode = ShortCode::new();
+        let shortcode = shortcode();
         let shortcode_from_str = ShortCode::from_str(shortcode.as_str()).unwrap();
         assert_eq!(shortcode_from_str, shortcode);
     }
 
     #[test]
     fn test_from_param() {
-        let shortcode = ShortCode::new();
+        let shortcode = shortcode();
         let shortcode_from_param = ShortCode::from_param(shortcode.as_str()).unwrap();
         assert_eq!(shortcode_from_param, shortcode);
+        assert!(ShortCode::from_param("favicon.ico").is_err());
     }
 }
@@ -43,6 +43,10 @@ impl<'r> form::FromFormField<'r> for Title {
     fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
         Ok(Self::new(field.value.to_owned()))
     }
+
+    fn default() -> Option<Self> {
+        Some(Self(None))
+    }
 }
 
 #[cfg(test)]
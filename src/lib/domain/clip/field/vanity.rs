@@ -0,0 +1,140 @@
+use super::shortcode::ShortCode;
+use crate::domain::clip::ClipError;
+use rocket::form;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// The shortest shortcode that can be requested.
+pub const MIN_LENGTH: usize = 3;
+
+/// Words that cannot be requested as shortcodes, as they name pages or would be
+/// mistaken for them.
+pub const RESERVED: &[&str] = &[
+    "admin",
+    "api",
+    "attachments",
+    "clip",
+    "delete",
+    "edit",
+    "history",
+    "key",
+    "new",
+    "raw",
+    "recent",
+    "revisions",
+    "search",
+    "static",
+];
+
+/// A shortcode requested for a new clip, such as `deploy-notes`, instead of a generated one.
+///
+/// Requested shortcodes are lowercase letters, digits and single dashes between them,
+/// between [`MIN_LENGTH`] and [`MAX_LENGTH`](super::shortcode::MAX_LENGTH) characters
+/// long, and not one of the [`RESERVED`] words.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "Option<String>")]
+pub struct VanityShortCode(Option<ShortCode>);
+
+impl VanityShortCode {
+    pub fn new<T: Into<Option<String>>>(shortcode: T) -> Result<Self, ClipError> {
+        let shortcode = match shortcode.into() {
+            Some(shortcode) => shortcode.trim().to_lowercase(),
+            None => return Ok(Self(None)),
+        };
+        if shortcode.is_empty() {
+            return Ok(Self(None));
+        }
+        if shortcode.len() < MIN_LENGTH {
+            return Err(ClipError::InvalidShortCode(format!(
+                "requested shortcodes need at least {} characters",
+                MIN_LENGTH
+            )));
+        }
+        if RESERVED.contains(&shortcode.as_str()) {
+            return Err(ClipError::InvalidShortCode(format!(
+                "{} is reserved",
+                shortcode
+            )));
+        }
+        Ok(Self(Some(ShortCode::from_str(&shortcode)?)))
+    }
+
+    pub fn into_inner(self) -> Option<ShortCode> {
+        self.0
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+impl TryFrom<Option<String>> for VanityShortCode {
+    type Error = ClipError;
+
+    fn try_from(shortcode: Option<String>) -> Result<Self, Self::Error> {
+        Self::new(shortcode)
+    }
+}
+
+impl FromStr for VanityShortCode {
+    type Err = ClipError;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Self::new(raw.to_owned())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> form::FromFormField<'r> for VanityShortCode {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+
+    fn default() -> Option<Self> {
+        Some(Self(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::clip::field::shortcode::MAX_LENGTH;
+
+    #[test]
+    fn test_new() {
+        let shortcode = VanityShortCode::new(" Deploy-Notes ".to_owned()).unwrap();
+        assert_eq!(
+            shortcode.into_inner(),
+            Some(ShortCode::from("deploy-notes"))
+        );
+        assert!(!VanityShortCode::new("  ".to_owned()).unwrap().is_set());
+        assert!(!VanityShortCode::new(None).unwrap().is_set());
+
+        for invalid in [
+            "ab",
+            "raw",
+            "key",
+            "static",
+            "deploy notes",
+            "-notes",
+            "notes/x",
+        ] {
+            assert!(
+                VanityShortCode::new(invalid.to_owned()).is_err(),
+                "{}",
+                invalid
+            );
+        }
+        assert!(VanityShortCode::new("a".repeat(MAX_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let shortcode: VanityShortCode = serde_json::from_str("\"standup\"").unwrap();
+        assert_eq!(shortcode.into_inner(), Some(ShortCode::from("standup")));
+        let shortcode: VanityShortCode = serde_json::from_str("null").unwrap();
+        assert!(!shortcode.is_set());
+        assert!(serde_json::from_str::<VanityShortCode>("\"api\"").is_err());
+    }
+}
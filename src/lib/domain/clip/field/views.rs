@@ -0,0 +1,22 @@
+use derive_more::Constructor;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Constructor, PartialEq)]
+pub struct Views(u64);
+
+impl Views {
+    pub fn into_inner(self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_views_into_inner() {
+        let views = Views::new(3);
+        assert_eq!(views.into_inner(), 3);
+    }
+}
@@ -0,0 +1,137 @@
+use super::{field, Clip, ClipError};
+use base64::engine::{general_purpose, Engine};
+use rocket::form;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The order in which clips are listed.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ClipSort {
+    /// Most recently posted first.
+    #[default]
+    Posted,
+    /// Most viewed first.
+    Hits,
+    /// Soonest to expire first; clips that never expire come last.
+    Expires,
+}
+
+impl ClipSort {
+    /// Returns the key clips are listed by in ascending order for this sort.
+    ///
+    /// Must agree with the ordering used by `data::query::list_clips`.
+    pub fn key(&self, clip: &Clip) -> i64 {
+        match self {
+            Self::Posted => -clip.posted.clone().into_inner().timestamp(),
+            Self::Hits => -i64::try_from(clip.hits.clone().into_inner()).unwrap_or(i64::MAX),
+            Self::Expires => clip
+                .expires
+                .clone()
+                .into_inner()
+                .map(|time| time.timestamp())
+                .unwrap_or(i64::MAX),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> form::FromFormField<'r> for ClipSort {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value)
+            .map_err(|_| form::Error::validation("sort must be one of posted, hits or expires"))?)
+    }
+}
+
+/// An opaque position in a clip listing, pointing just past the last clip of a page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListCursor {
+    pub key: i64,
+    pub shortcode: field::ShortCode,
+}
+
+impl ListCursor {
+    /// Builds the cursor pointing past `clip` in a listing sorted by `sort`.
+    pub fn after(clip: &Clip, sort: ClipSort) -> Self {
+        Self {
+            key: sort.key(clip),
+            shortcode: clip.shortcode.clone(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        general_purpose::URL_SAFE_NO_PAD.encode(format!("{}:{}", self.key, self.shortcode.as_str()))
+    }
+}
+
+impl FromStr for ListCursor {
+    type Err = ClipError;
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let invalid = || ClipError::InvalidCursor(raw.to_owned());
+        let decoded = general_purpose::URL_SAFE_NO_PAD
+            .decode(raw)
+            .map_err(|_| invalid())?;
+        let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+        let (key, shortcode) = decoded.split_once(':').ok_or_else(invalid)?;
+        Ok(Self {
+            key: key.parse().map_err(|_| invalid())?,
+            shortcode: field::ShortCode::from(shortcode),
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> form::FromFormField<'r> for ListCursor {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        Ok(Self::from_str(field.value).map_err(|e| form::Error::validation(format!("{}", e)))?)
+    }
+}
+
+/// A page of listed clips.
+#[derive(Debug, Clone)]
+pub struct ClipPage {
+    pub clips: Vec<Clip>,
+    /// The cursor to fetch the next page with, if there is one.
+    pub next: Option<ListCursor>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_from_str() {
+        assert_eq!(ClipSort::from_str("posted").unwrap(), ClipSort::Posted);
+        assert_eq!(ClipSort::from_str("hits").unwrap(), ClipSort::Hits);
+        assert_eq!(ClipSort::from_str("expires").unwrap(), ClipSort::Expires);
+        assert!(ClipSort::from_str("title").is_err());
+        assert_eq!(ClipSort::Expires.as_ref(), "expires");
+    }
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = ListCursor {
+            key: -862070800,
+            shortcode: field::ShortCode::from("abc123"),
+        };
+        let encoded = cursor.encode();
+        assert_eq!(ListCursor::from_str(&encoded).unwrap(), cursor);
+        assert!(matches!(
+            ListCursor::from_str("not a cursor"),
+            Err(ClipError::InvalidCursor(_))
+        ));
+    }
+}
@@ -1,6 +1,12 @@
+mod attachment;
 pub mod field;
+mod listing;
+pub mod views;
+pub use attachment::{Attachment, AttachmentFile};
+pub use listing::{ClipPage, ClipSort, ListCursor};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+pub use views::{View, ViewBucket, ViewStats};
 
 #[derive(Debug, Error, PartialEq)]
 pub enum ClipError {
@@ -13,9 +19,36 @@ pub enum ClipError {
     #[error("empty content")]
     EmptyContent,
 
+    #[error("content encoding error: {0}")]
+    ContentEncoding(String),
+
+    #[error("password hash error: {0}")]
+    PasswordHash(String),
+
+    #[error("invalid language: {0}")]
+    InvalidLanguage(String),
+
+    #[error("invalid format: {0}")]
+    InvalidFormat(String),
+
+    #[error("invalid attachment: {0}")]
+    InvalidAttachment(String),
+
+    #[error("invalid max views: {0}")]
+    InvalidMaxViews(String),
+
+    #[error("invalid shortcode: {0}")]
+    InvalidShortCode(String),
+
+    #[error("invalid cursor: {0}")]
+    InvalidCursor(String),
+
     #[error("invalid date: {0}")]
     InvalidDate(String),
 
+    #[error("invalid view statistics: {0}")]
+    InvalidViews(String),
+
     #[error("date parse error: {0}")]
     DateParse(#[from] chrono::ParseError),
 
@@ -26,17 +59,97 @@ pub enum ClipError {
     Hits(#[from] std::num::TryFromIntError),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A clip as stored, including its secrets.
+///
+/// Clips are never serialized directly; see `web::PublicClip` for the view handed to
+/// API clients and templates.
+#[derive(Debug, Clone)]
 pub struct Clip {
-    #[serde(skip)]
     pub clip_id: field::ClipId,
     pub shortcode: field::ShortCode,
     pub content: field::Content,
     pub title: field::Title,
     pub posted: field::Posted,
     pub expires: field::Expires,
-    pub password: field::Password,
+    pub password: field::PasswordHash,
     pub hits: field::Hits,
+    pub edit_token: field::EditToken,
+    pub max_views: field::MaxViews,
+    pub views: field::Views,
+    pub language: field::Language,
+    pub format: field::Format,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    pub revision: u32,
+    pub content: field::Content,
+    pub title: field::Title,
+    pub posted: field::Posted,
+}
+
+impl Revision {
+    /// Produces a unified diff turning this revision's content into `other`'s.
+    pub fn diff(&self, other: &Revision) -> String {
+        similar::TextDiff::from_lines(self.content.as_str(), other.content.as_str())
+            .unified_diff()
+            .header(
+                &format!("revision {}", self.revision),
+                &format!("revision {}", other.revision),
+            )
+            .to_string()
+    }
+}
+
+/// Marks the start of a matched term in a search snippet.
+pub const MATCH_START: char = '\u{2}';
+/// Marks the end of a matched term in a search snippet.
+pub const MATCH_END: char = '\u{3}';
+
+/// A clip matching a full-text search.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub shortcode: field::ShortCode,
+    pub title: field::Title,
+    /// An excerpt of the clip around the matched terms, which are enclosed in
+    /// [`MATCH_START`] and [`MATCH_END`].
+    pub snippet: String,
+    /// The BM25 rank of the match; lower is better.
+    pub rank: f64,
+}
+
+impl SearchHit {
+    /// Splits the snippet into consecutive parts, flagging the parts that matched the search.
+    pub fn snippet_parts(&self) -> Vec<(bool, &str)> {
+        let mut parts = vec![];
+        let mut rest = self.snippet.as_str();
+        while let Some(start) = rest.find(MATCH_START) {
+            if start > 0 {
+                parts.push((false, &rest[..start]));
+            }
+            rest = &rest[start + MATCH_START.len_utf8()..];
+            let end = rest.find(MATCH_END).unwrap_or(rest.len());
+            parts.push((true, &rest[..end]));
+            rest = rest.get(end + MATCH_END.len_utf8()..).unwrap_or_default();
+        }
+        if !rest.is_empty() {
+            parts.push((false, rest));
+        }
+        parts
+    }
+
+    /// Returns the snippet without match markers.
+    pub fn plain_snippet(&self) -> String {
+        self.snippet.replace([MATCH_START, MATCH_END], "")
+    }
+}
+
+/// A page of search results.
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    pub page: u32,
+    pub has_more: bool,
 }
 
 #[cfg(test)]
@@ -57,8 +170,9 @@ mod tests {
         let time = Time::from_str(time_str).unwrap();
         let posted = field::Posted::new(time.clone());
         let expires = field::Expires::new(Some(Time::from_seconds(3600)));
-        let password = field::Password::new("password123".to_string()).unwrap();
+        let password = field::PasswordHash::new("hash".to_string());
         let hits = field::Hits::new(0);
+        let edit_token = field::EditToken::generate();
 
         let clip = Clip {
             clip_id: clip_id.clone(),
@@ -69,6 +183,11 @@ mod tests {
             expires: expires.clone(),
             password: password.clone(),
             hits: hits.clone(),
+            edit_token: edit_token.clone(),
+            max_views: field::MaxViews::default(),
+            views: field::Views::default(),
+            language: field::Language::default(),
+            format: field::Format::default(),
         };
 
         assert_eq!(clip.clip_id, clip_id);
@@ -79,5 +198,48 @@ mod tests {
         assert_eq!(clip.expires, expires);
         assert_eq!(clip.password, password);
         assert_eq!(clip.hits, hits);
+        assert_eq!(clip.edit_token, edit_token);
+    }
+
+    #[test]
+    fn test_revision_diff() {
+        let revision = |revision, content| Revision {
+            revision,
+            content: field::Content::new(content).unwrap(),
+            title: field::Title::default(),
+            posted: field::Posted::new(Time::from_seconds(0)),
+        };
+        let old = revision(1, "one\ntwo\nthree\n");
+        let new = revision(2, "one\n2\nthree\n");
+
+        let diff = old.diff(&new);
+        assert!(diff.starts_with("--- revision 1\n+++ revision 2\n"));
+        assert!(diff.contains("-two\n"));
+        assert!(diff.contains("+2\n"));
+        assert!(diff.contains(" one\n"));
+        assert_eq!(old.diff(&old), "");
+    }
+
+    #[test]
+    fn test_search_hit_snippet() {
+        let hit = SearchHit {
+            shortcode: field::ShortCode::from("abc123"),
+            title: field::Title::default(),
+            snippet: format!(
+                "a {}log{} line {}log{}",
+                MATCH_START, MATCH_END, MATCH_START, MATCH_END
+            ),
+            rank: -1.0,
+        };
+        assert_eq!(
+            hit.snippet_parts(),
+            vec![
+                (false, "a "),
+                (true, "log"),
+                (false, " line "),
+                (true, "log")
+            ]
+        );
+        assert_eq!(hit.plain_snippet(), "a log line log");
     }
 }
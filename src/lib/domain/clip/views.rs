@@ -0,0 +1,555 @@
+use std::collections::{HashMap, HashSet};
+
+use super::field;
+use crate::Time;
+use rocket::http::uri::Absolute;
+use serde::Serialize;
+
+/// The length of the buckets views are counted in, in seconds.
+pub const BUCKET_SECONDS: i64 = 3600;
+
+/// The longest span view statistics can be requested for at once, in seconds.
+pub const MAX_SPAN_SECONDS: i64 = 90 * 24 * BUCKET_SECONDS;
+
+/// The longest referrer host recorded; views from longer hosts are recorded without one.
+const MAX_HOST_LENGTH: usize = 253;
+
+/// The most referrer hosts counted separately for a clip, both when views are recorded and
+/// in its view statistics. Views from any other host are counted under `OTHER_REFERRER`.
+pub const MAX_REFERRERS: usize = 20;
+
+/// The referrer views from hosts beyond `MAX_REFERRERS` are recorded under. It is never
+/// returned by `referrer_host`, so it cannot be mistaken for a host.
+pub const OTHER_REFERRER: &str = "*";
+
+/// Parts of user agents sent by crawlers, link unfurlers and uptime checks.
+const BOT_MARKERS: &[&str] = &[
+    "bot",
+    "crawl",
+    "spider",
+    "slurp",
+    "preview",
+    "facebookexternalhit",
+    "whatsapp",
+    "headless",
+    "monitor",
+];
+
+/// Parts of user agents sent by command-line tools and HTTP libraries.
+const CLI_MARKERS: &[&str] = &[
+    "curl/",
+    "wget/",
+    "httpie/",
+    "python-requests",
+    "python-urllib",
+    "aiohttp",
+    "go-http-client",
+    "libwww-perl",
+    "powershell",
+    "okhttp",
+    "node-fetch",
+    "axios",
+    "reqwest",
+];
+
+/// Where a clip was viewed.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    strum::Display,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ViewSource {
+    /// The clip page.
+    Web,
+    /// The raw content of the clip.
+    Raw,
+    /// The clip API.
+    Api,
+}
+
+/// The coarse kind of client a clip was viewed with.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    strum::Display,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Agent {
+    Browser,
+    Cli,
+    Bot,
+}
+
+impl Agent {
+    /// Classifies a `User-Agent` header. Requests without one are almost always scripts,
+    /// and so are counted as command-line views.
+    pub fn classify(user_agent: Option<&str>) -> Self {
+        let user_agent = match user_agent {
+            Some(user_agent) if !user_agent.trim().is_empty() => user_agent.to_ascii_lowercase(),
+            _ => return Self::Cli,
+        };
+        if BOT_MARKERS.iter().any(|marker| user_agent.contains(marker)) {
+            Self::Bot
+        } else if CLI_MARKERS.iter().any(|marker| user_agent.contains(marker)) {
+            Self::Cli
+        } else if user_agent.starts_with("mozilla/") || user_agent.starts_with("opera/") {
+            Self::Browser
+        } else {
+            Self::Cli
+        }
+    }
+}
+
+/// Returns the lowercased host of a `Referer` header, if it is an absolute http(s) URL.
+pub fn referrer_host(referer: &str) -> Option<String> {
+    let uri = Absolute::parse(referer.trim()).ok()?;
+    if !["http", "https"]
+        .iter()
+        .any(|scheme| uri.scheme().eq_ignore_ascii_case(scheme))
+    {
+        return None;
+    }
+    let host = uri.authority()?.host().to_ascii_lowercase();
+    (!host.is_empty() && host.len() <= MAX_HOST_LENGTH && host != OTHER_REFERRER).then_some(host)
+}
+
+/// Moves views from hosts a clip has no room for under `OTHER_REFERRER`.
+///
+/// `known` holds the referrer hosts already recorded for each clip. New hosts are kept
+/// while their clip has fewer than `MAX_REFERRERS`, busiest first.
+pub fn fold_referrers(
+    views: &mut [(View, u32)],
+    mut known: HashMap<field::ShortCode, HashSet<String>>,
+) {
+    let mut order: Vec<usize> = (0..views.len()).collect();
+    order.sort_by(|a, b| views[*b].1.cmp(&views[*a].1));
+    for index in order {
+        let view = &mut views[index].0;
+        let kept = match view.referrer.as_deref() {
+            Some(host) if host != OTHER_REFERRER => {
+                let hosts = known.entry(view.shortcode.clone()).or_default();
+                hosts.contains(host)
+                    || (hosts.len() < MAX_REFERRERS && hosts.insert(host.to_owned()))
+            }
+            _ => true,
+        };
+        if !kept {
+            view.referrer = Some(OTHER_REFERRER.to_owned());
+        }
+    }
+}
+
+/// Returns the start of the bucket `seconds` falls in, in seconds since the epoch.
+pub fn bucket_of(seconds: i64) -> i64 {
+    seconds - seconds.rem_euclid(BUCKET_SECONDS)
+}
+
+/// A single view of a clip, waiting to be added to its view statistics.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct View {
+    pub shortcode: field::ShortCode,
+    /// The start of the hour the clip was viewed in, in seconds since the epoch.
+    pub hour: i64,
+    pub source: ViewSource,
+    pub agent: Agent,
+    /// The host of the page linking to the clip, if the client sent one.
+    pub referrer: Option<String>,
+}
+
+impl View {
+    /// A view of a clip happening now.
+    pub fn new(
+        shortcode: field::ShortCode,
+        source: ViewSource,
+        agent: Agent,
+        referrer: Option<String>,
+    ) -> Self {
+        Self {
+            shortcode,
+            hour: bucket_of(chrono::Utc::now().timestamp()),
+            source,
+            agent,
+            referrer,
+        }
+    }
+}
+
+/// The views of a clip within one hour, from one source, kind of client and referrer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewBucket {
+    /// The start of the hour, in seconds since the epoch.
+    pub hour: i64,
+    pub source: ViewSource,
+    pub agent: Agent,
+    pub referrer: Option<String>,
+    pub views: u64,
+}
+
+impl ViewBucket {
+    /// The bucket a number of views not yet recorded will be added to.
+    pub fn pending(view: &View, views: u32) -> Self {
+        Self {
+            hour: view.hour,
+            source: view.source,
+            agent: view.agent,
+            referrer: view.referrer.clone(),
+            views: u64::from(views),
+        }
+    }
+}
+
+/// Views counted by where the clip was viewed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SourceViews {
+    pub web: u64,
+    pub raw: u64,
+    pub api: u64,
+}
+
+impl SourceViews {
+    pub fn total(&self) -> u64 {
+        self.web + self.raw + self.api
+    }
+
+    fn add(&mut self, source: ViewSource, views: u64) {
+        match source {
+            ViewSource::Web => self.web += views,
+            ViewSource::Raw => self.raw += views,
+            ViewSource::Api => self.api += views,
+        }
+    }
+}
+
+/// Views counted by the kind of client the clip was viewed with.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AgentViews {
+    pub browser: u64,
+    pub cli: u64,
+    pub bot: u64,
+}
+
+impl AgentViews {
+    fn add(&mut self, agent: Agent, views: u64) {
+        match agent {
+            Agent::Browser => self.browser += views,
+            Agent::Cli => self.cli += views,
+            Agent::Bot => self.bot += views,
+        }
+    }
+}
+
+/// The views of a clip within one hour.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HourlyViews {
+    pub hour: Time,
+    #[serde(flatten)]
+    pub views: SourceViews,
+}
+
+/// The views of a clip coming from links on one host.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReferrerViews {
+    pub host: String,
+    pub views: u64,
+}
+
+impl ReferrerViews {
+    /// Whether these views are listed first: more views, or as many from an earlier host.
+    fn ranks_before(&self, other: &ReferrerViews) -> bool {
+        self.views > other.views || (self.views == other.views && self.host < other.host)
+    }
+}
+
+/// The views of a clip over a span of time.
+///
+/// The span covers every hour starting between `from` and `to`, so the hour `from`
+/// falls in is counted in full.
+#[derive(Debug, Clone, Serialize)]
+pub struct ViewStats {
+    pub shortcode: field::ShortCode,
+    pub from: Time,
+    pub to: Time,
+    pub total: u64,
+    pub sources: SourceViews,
+    pub agents: AgentViews,
+    /// The hosts of the pages linking to the clip, most views first, at most
+    /// `MAX_REFERRERS` of them.
+    pub referrers: Vec<ReferrerViews>,
+    /// The views from pages on any other host.
+    pub other_referrers: u64,
+    /// The hours the clip was viewed in, oldest first.
+    pub hours: Vec<HourlyViews>,
+}
+
+impl ViewStats {
+    /// Statistics without any views yet.
+    pub fn new(shortcode: field::ShortCode, from: Time, to: Time) -> Self {
+        Self {
+            shortcode,
+            from,
+            to,
+            total: 0,
+            sources: SourceViews::default(),
+            agents: AgentViews::default(),
+            referrers: vec![],
+            other_referrers: 0,
+            hours: vec![],
+        }
+    }
+
+    /// The start of the first hour in the span, in seconds since the epoch.
+    pub fn first_hour(&self) -> i64 {
+        bucket_of(self.from.timestamp())
+    }
+
+    /// The start of the last hour in the span, in seconds since the epoch.
+    pub fn last_hour(&self) -> i64 {
+        bucket_of(self.to.timestamp())
+    }
+
+    /// Counts the views of a bucket, unless its hour falls outside the span.
+    pub fn add(&mut self, bucket: &ViewBucket) {
+        if bucket.hour < self.first_hour() || bucket.hour > self.last_hour() || bucket.views == 0 {
+            return;
+        }
+        self.total += bucket.views;
+        self.sources.add(bucket.source, bucket.views);
+        self.agents.add(bucket.agent, bucket.views);
+
+        match self
+            .hours
+            .binary_search_by_key(&bucket.hour, |hourly| hourly.hour.timestamp())
+        {
+            Ok(index) => self.hours[index].views.add(bucket.source, bucket.views),
+            Err(index) => {
+                let mut views = SourceViews::default();
+                views.add(bucket.source, bucket.views);
+                let hour = Time::from_seconds(bucket.hour);
+                self.hours.insert(index, HourlyViews { hour, views });
+            }
+        }
+
+        match bucket.referrer.as_deref() {
+            Some(OTHER_REFERRER) => self.other_referrers += bucket.views,
+            Some(host) => self.add_referrer(host, bucket.views),
+            None => (),
+        }
+    }
+
+    /// Counts views from a host, keeping the referrers in order and folding the host with
+    /// the fewest views into `other_referrers` once there are too many.
+    fn add_referrer(&mut self, host: &str, views: u64) {
+        let mut index = match self
+            .referrers
+            .iter()
+            .position(|referrer| referrer.host == host)
+        {
+            Some(index) => {
+                self.referrers[index].views += views;
+                index
+            }
+            None => {
+                self.referrers.push(ReferrerViews {
+                    host: host.to_owned(),
+                    views,
+                });
+                self.referrers.len() - 1
+            }
+        };
+        while index > 0 && self.referrers[index].ranks_before(&self.referrers[index - 1]) {
+            self.referrers.swap(index, index - 1);
+            index -= 1;
+        }
+        if self.referrers.len() > MAX_REFERRERS {
+            if let Some(fewest) = self.referrers.pop() {
+                self.other_referrers += fewest.views;
+            }
+        }
+    }
+
+    /// The views of every hour in the span, including the hours without any.
+    pub fn hourly_totals(&self) -> Vec<(i64, u64)> {
+        (self.first_hour()..=self.last_hour())
+            .step_by(BUCKET_SECONDS as usize)
+            .map(|hour| {
+                let views = self
+                    .hours
+                    .binary_search_by_key(&hour, |hourly| hourly.hour.timestamp())
+                    .map(|index| self.hours[index].views.total())
+                    .unwrap_or(0);
+                (hour, views)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(hour: i64, source: ViewSource, referrer: Option<&str>, views: u64) -> ViewBucket {
+        ViewBucket {
+            hour,
+            source,
+            agent: Agent::Browser,
+            referrer: referrer.map(str::to_owned),
+            views,
+        }
+    }
+
+    #[test]
+    fn test_classify_agent() {
+        let firefox = "Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/115.0";
+        assert_eq!(Agent::classify(Some(firefox)), Agent::Browser);
+        assert_eq!(Agent::classify(Some("curl/8.1.2")), Agent::Cli);
+        assert_eq!(Agent::classify(Some("python-requests/2.31")), Agent::Cli);
+        assert_eq!(Agent::classify(None), Agent::Cli);
+        assert_eq!(Agent::classify(Some(" ")), Agent::Cli);
+        assert_eq!(
+            Agent::classify(Some(
+                "Slackbot-LinkExpanding 1.0 (+https://api.slack.com/robots)"
+            )),
+            Agent::Bot
+        );
+        assert_eq!(
+            Agent::classify(Some("Mozilla/5.0 (compatible; Googlebot/2.1)")),
+            Agent::Bot
+        );
+    }
+
+    #[test]
+    fn test_referrer_host() {
+        assert_eq!(
+            referrer_host("https://Example.slack.com/archives/C01?thread=1"),
+            Some("example.slack.com".to_owned())
+        );
+        assert_eq!(
+            referrer_host("http://localhost:8000/recent"),
+            Some("localhost".to_owned())
+        );
+        assert_eq!(referrer_host("/clip/abc"), None);
+        assert_eq!(referrer_host("ftp://example.com/file"), None);
+        assert_eq!(referrer_host("not a url"), None);
+    }
+
+    #[test]
+    fn test_bucket_of() {
+        assert_eq!(bucket_of(7199), 3600);
+        assert_eq!(bucket_of(7200), 7200);
+        assert_eq!(bucket_of(-1), -3600);
+    }
+
+    #[test]
+    fn test_view_stats() {
+        let from = Time::from_seconds(3600 + 1800);
+        let to = Time::from_seconds(4 * 3600 + 10);
+        let mut stats = ViewStats::new(field::ShortCode::from("runbook"), from, to);
+        stats.add(&bucket(0, ViewSource::Web, None, 9));
+        stats.add(&bucket(3 * 3600, ViewSource::Raw, Some("b.example"), 2));
+        stats.add(&bucket(3600, ViewSource::Web, Some("a.example"), 2));
+        stats.add(&bucket(3 * 3600, ViewSource::Api, Some("c.example"), 3));
+        stats.add(&bucket(3600, ViewSource::Api, Some("b.example"), 1));
+        stats.add(&bucket(5 * 3600, ViewSource::Web, None, 9));
+
+        assert_eq!(stats.total, 8);
+        assert_eq!(
+            stats.sources,
+            SourceViews {
+                web: 2,
+                raw: 2,
+                api: 4
+            }
+        );
+        assert_eq!(stats.agents.browser, 8);
+        let hosts: Vec<_> = stats
+            .referrers
+            .iter()
+            .map(|referrer| (referrer.host.as_str(), referrer.views))
+            .collect();
+        assert_eq!(
+            hosts,
+            vec![("b.example", 3), ("c.example", 3), ("a.example", 2)]
+        );
+        assert_eq!(stats.hours.len(), 2);
+        assert_eq!(stats.hours[0].hour.timestamp(), 3600);
+        assert_eq!(stats.hours[0].views.total(), 3);
+        assert_eq!(
+            stats.hourly_totals(),
+            vec![(3600, 3), (2 * 3600, 0), (3 * 3600, 5), (4 * 3600, 0)]
+        );
+    }
+
+    #[test]
+    fn test_fold_referrers() {
+        let shortcode = field::ShortCode::from("linked");
+        let view = |referrer: Option<&str>| View {
+            shortcode: shortcode.clone(),
+            hour: 0,
+            source: ViewSource::Web,
+            agent: Agent::Browser,
+            referrer: referrer.map(str::to_owned),
+        };
+        let known: HashSet<String> = (1..MAX_REFERRERS)
+            .map(|n| format!("{}.example", n))
+            .collect();
+        let mut views = vec![
+            (view(Some("quiet.example")), 1),
+            (view(Some("busy.example")), 5),
+            (view(Some("1.example")), 1),
+            (view(None), 1),
+        ];
+        fold_referrers(&mut views, HashMap::from([(shortcode.clone(), known)]));
+
+        let referrers: Vec<_> = views
+            .iter()
+            .map(|(view, _)| view.referrer.as_deref())
+            .collect();
+        assert_eq!(
+            referrers,
+            vec![
+                Some(OTHER_REFERRER),
+                Some("busy.example"),
+                Some("1.example"),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_view_stats_referrers_are_capped() {
+        let mut stats = ViewStats::new(
+            field::ShortCode::from("linked"),
+            Time::from_seconds(0),
+            Time::from_seconds(0),
+        );
+        for n in 0..=MAX_REFERRERS {
+            let host = format!("{:02}.example", n);
+            stats.add(&bucket(0, ViewSource::Web, Some(&host), 1));
+        }
+        stats.add(&bucket(0, ViewSource::Web, Some(OTHER_REFERRER), 4));
+        stats.add(&bucket(0, ViewSource::Web, Some("05.example"), 2));
+
+        assert_eq!(stats.referrers.len(), MAX_REFERRERS);
+        assert_eq!(stats.referrers[0].host, "05.example");
+        assert_eq!(stats.referrers[1].host, "00.example");
+        assert_eq!(stats.other_referrers, 5);
+        assert_eq!(stats.total, MAX_REFERRERS as u64 + 7);
+    }
+}
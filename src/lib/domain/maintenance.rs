@@ -1,19 +1,22 @@
-use crate::data::DatabasePool;
+use crate::data::Store;
 use crate::service;
 use std::time::Duration;
 
 pub struct Maintenance;
 
 impl Maintenance {
-    pub fn spawn(pool: &DatabasePool) -> Self {
-        let pool = pool.clone();
+    pub fn spawn(store: &Store, interval: Duration) -> Self {
+        let store = store.clone();
         tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(10));
+            let mut interval = tokio::time::interval(interval);
             loop {
                 interval.tick().await;
-                if let Err(e) = service::action::delete_expired(&pool).await {
+                if let Err(e) = service::action::delete_expired(store.get()).await {
                     eprintln!("Failed to delete expired clips: {}", e);
                 }
+                if let Err(e) = service::action::delete_old_views(store.get()).await {
+                    eprintln!("Failed to delete old clip views: {}", e);
+                }
             }
         });
         Self
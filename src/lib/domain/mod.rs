@@ -1,5 +1,6 @@
 pub mod clip;
 pub mod maintenance;
+pub mod quota;
 pub mod time;
 
 pub use clip::Clip;
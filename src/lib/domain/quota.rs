@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// Limits on the clips a single API key may keep stored. Unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    /// The most live clips a key may own.
+    pub max_clips: Option<u64>,
+    /// The most bytes of content and attachments a key's live clips may store.
+    pub max_bytes: Option<u64>,
+}
+
+/// What the live clips created with an API key currently store.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// The number of clips that have not expired.
+    pub clips: u64,
+    /// The stored size of their content and attachments, in bytes.
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum QuotaError {
+    #[error("clip quota exceeded: {} of {} clips in use", .usage.clips, .limit)]
+    Clips { usage: Usage, limit: u64 },
+    #[error("storage quota exceeded: {} of {} bytes in use", .usage.bytes, .limit)]
+    Bytes { usage: Usage, limit: u64 },
+}
+
+impl QuotaError {
+    /// The usage at the time the quota was exceeded.
+    pub fn usage(&self) -> Usage {
+        match self {
+            Self::Clips { usage, .. } | Self::Bytes { usage, .. } => *usage,
+        }
+    }
+}
+
+impl Quota {
+    /// Checks that adding `clips` clips and `bytes` bytes to `usage` stays within the quota.
+    ///
+    /// Only the limits on what is added are checked, so a key already over a lowered
+    /// limit can still shrink its clips.
+    pub fn check(&self, usage: Usage, clips: u64, bytes: u64) -> Result<(), QuotaError> {
+        if let Some(limit) = self.max_clips {
+            if clips > 0 && usage.clips.saturating_add(clips) > limit {
+                return Err(QuotaError::Clips { usage, limit });
+            }
+        }
+        if let Some(limit) = self.max_bytes {
+            if bytes > 0 && usage.bytes.saturating_add(bytes) > limit {
+                return Err(QuotaError::Bytes { usage, limit });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let usage = Usage {
+            clips: 2,
+            bytes: 100,
+        };
+        assert!(Quota::default().check(usage, 100, 100_000).is_ok());
+
+        let quota = Quota {
+            max_clips: Some(3),
+            max_bytes: Some(150),
+        };
+        assert!(quota.check(usage, 1, 50).is_ok());
+        assert_eq!(
+            quota.check(usage, 2, 0),
+            Err(QuotaError::Clips { usage, limit: 3 })
+        );
+        assert_eq!(
+            quota.check(usage, 0, 51),
+            Err(QuotaError::Bytes { usage, limit: 150 })
+        );
+
+        let full = Usage {
+            clips: 5,
+            bytes: 500,
+        };
+        assert!(quota.check(full, 0, 0).is_ok());
+    }
+}
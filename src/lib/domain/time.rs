@@ -27,12 +27,14 @@ impl Time {
     }
 }
 
+/// Parses either a bare `YYYY-MM-DD` date, pinned to midnight UTC, or an RFC 3339
+/// timestamp with an offset, which is converted to UTC.
 impl FromStr for Time {
     type Err = chrono::ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match format!("{}T00:00:00Z", s).parse::<DateTime<Utc>>() {
             Ok(time) => Ok(time.into()),
-            Err(e) => Err(e),
+            Err(_) => Ok(DateTime::parse_from_rfc3339(s)?.with_timezone(&Utc).into()),
         }
     }
 }
@@ -78,6 +80,25 @@ mod tests {
         assert_eq!(time_str.parse::<Time>().unwrap(), expected_time);
     }
 
+    #[test]
+    fn test_from_str_rfc3339() {
+        let dt: NaiveDateTime = NaiveDate::from_ymd_opt(1997, 5, 1)
+            .unwrap()
+            .and_hms_opt(16, 0, 0)
+            .unwrap();
+        let expected_time = Time::from_naive_utc(dt);
+        assert_eq!(
+            "1997-05-01T17:00:00+01:00".parse::<Time>().unwrap(),
+            expected_time
+        );
+        assert_eq!(
+            "1997-05-01T16:00:00Z".parse::<Time>().unwrap(),
+            expected_time
+        );
+        assert!("1997-05-01T16:00:00".parse::<Time>().is_err());
+        assert!("tomorrow".parse::<Time>().is_err());
+    }
+
     #[test]
     fn test_time_serialize() {
         let datetime = Utc::now();
@@ -1,3 +1,4 @@
+pub mod config;
 pub mod data;
 pub mod domain;
 pub mod service;
@@ -7,27 +8,45 @@ pub use data::DataError;
 pub use domain::clip::field::ShortCode;
 pub use domain::clip::{Clip, ClipError};
 use domain::maintenance::Maintenance;
+use domain::quota::Quota;
 pub use domain::time::Time;
 pub use service::ServiceError;
 
-use data::AppDatabase;
+use data::Store;
+use rocket::fairing::AdHoc;
 use rocket::fs::FileServer;
 use rocket::{Build, Rocket};
-use web::{hitcounter::HitCounter, renderer::Renderer};
+use web::{
+    cookies::CookieNames, hitcounter::HitCounter, ratelimit::RateLimiter, renderer::Renderer,
+};
 
 pub struct RocketConfig {
     pub renderer: Renderer<'static>,
-    pub database: AppDatabase,
+    pub store: Store,
     pub hit_counter: HitCounter,
     pub maintenance: Maintenance,
+    pub quota: Quota,
+    pub rate_limiter: RateLimiter,
+    pub cookies: CookieNames,
 }
 
 pub fn rocket(config: RocketConfig) -> Rocket<Build> {
-    rocket::build()
-        .manage::<AppDatabase>(config.database)
+    let figment = rocket::Config::figment().merge(("limits", web::form::limits()));
+    rocket::custom(figment)
+        .manage::<Store>(config.store)
         .manage::<Renderer>(config.renderer)
         .manage::<HitCounter>(config.hit_counter)
         .manage::<Maintenance>(config.maintenance)
+        .manage::<Quota>(config.quota)
+        .manage::<RateLimiter>(config.rate_limiter)
+        .manage::<CookieNames>(config.cookies)
+        .attach(AdHoc::on_shutdown("Flush hit counts", |rocket| {
+            Box::pin(async move {
+                if let Some(hit_counter) = rocket.state::<HitCounter>() {
+                    hit_counter.commit().await;
+                }
+            })
+        }))
         .mount("/", web::http::routes())
         .mount("/api/clip", web::api::routes())
         .mount("/static", FileServer::from("static"))
@@ -1,7 +1,12 @@
-use crate::data::{query, DatabasePool, Transaction};
+use crate::data::{model, query, ClipStore, DatabasePool, Transaction};
+use crate::domain::clip::{
+    field, views, Attachment, AttachmentFile, ClipPage, ListCursor, Revision, SearchResults, View,
+    ViewStats,
+};
+use crate::domain::quota::{Quota, Usage};
 use crate::service::ask;
-use crate::web::api::ApiKey;
-use crate::{Clip, ServiceError, ShortCode};
+use crate::web::api::{ApiKey, KeyInfo};
+use crate::{Clip, ClipError, ServiceError, ShortCode, Time};
 use std::convert::TryInto;
 
 /// Begins a new database transaction using the provided database pool.
@@ -32,94 +37,542 @@ pub async fn end_transaction(transaction: Transaction<'_>) -> Result<(), Service
     Ok(transaction.commit().await?)
 }
 
-/// Increases the hit count for a given clip shortcode by the specified number of hits.
+/// Records views of several clips, adding them to their hit counts and view statistics.
 ///
 /// # Arguments
 ///
-/// * `shortcode` - A reference to the `ShortCode` representing the clip to increase the hit count for.
-/// * `hits` - The number of hits to increment the hit count by.
-/// * `pool` - A reference to the `DatabasePool` providing the database connection.
+/// * `views` - The views along with the number of times each was counted.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
 ///
 /// # Returns
 ///
-/// Returns `Ok(())` if the hit count is successfully increased. Otherwise, returns a `ServiceError`.
+/// Returns `Ok(())` if the views are successfully recorded. Otherwise, returns a `ServiceError`.
 ///
-pub async fn increase_hit_count(
-    shortcode: &ShortCode,
-    hits: u32,
-    pool: &DatabasePool,
+pub async fn record_views(
+    views: &[(View, u32)],
+    store: &dyn ClipStore,
 ) -> Result<(), ServiceError> {
-    Ok(query::increase_hit_count(shortcode, hits, pool).await?)
+    Ok(store.record_views(views).await?)
 }
 
+/// The most attachments a single clip can carry.
+pub const MAX_ATTACHMENTS: usize = 10;
+
 /// Creates a new clip based on the provided request and inserts it into the database.
 ///
+/// View-limited clips cannot carry attachments, as the clip is gone by the time its
+/// attachments would be downloaded. Clips created with an API key count towards the
+/// quota of that key, with new content counted at its uncompressed size.
+///
 /// # Arguments
 ///
 /// * `req` - The request object containing necessary information to create the clip.
-/// * `pool` - A reference to the database connection pool.
+/// * `quota` - The limits on what a single API key may store.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
 ///
 /// # Returns
 ///
 /// A `Result` indicating either the newly created `Clip` or a `ServiceError` if an error occurs.
 ///
-pub async fn new_clip(req: ask::NewClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
-    Ok(query::insert_clip(req, pool).await?.try_into()?)
+pub async fn new_clip(
+    req: ask::NewClip,
+    quota: &Quota,
+    store: &dyn ClipStore,
+) -> Result<Clip, ServiceError> {
+    if req.attachments.len() > MAX_ATTACHMENTS {
+        return Err(ClipError::InvalidAttachment(format!(
+            "a clip can have at most {} attachments",
+            MAX_ATTACHMENTS
+        ))
+        .into());
+    }
+    if !req.attachments.is_empty() && req.max_views.is_limited() {
+        return Err(ClipError::InvalidAttachment(
+            "view-limited clips cannot have attachments".to_owned(),
+        )
+        .into());
+    }
+    let model: model::NewClip = req.try_into()?;
+    Ok(store.insert_clip(model, quota).await?.try_into()?)
 }
 
 /// Updates an existing clip based on the provided request and updates it in the database.
 ///
+/// The request must carry the edit token issued when the clip was created. If the clip
+/// was created with an API key, the new content counts towards the quota of that key
+/// in place of the old.
+///
 /// # Arguments
 ///
 /// * `req` - The request object containing necessary information to update the clip.
-/// * `pool` - A reference to the database connection pool.
+/// * `quota` - The limits on what a single API key may store.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
 ///
 /// # Returns
 ///
 /// A `Result` indicating either the updated `Clip` or a `ServiceError` if an error occurs.
 ///
-pub async fn update_clip(req: ask::UpdateClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
-    Ok(query::update_clip(req, pool).await?.try_into()?)
+pub async fn update_clip(
+    req: ask::UpdateClip,
+    quota: &Quota,
+    store: &dyn ClipStore,
+) -> Result<Clip, ServiceError> {
+    let owned = ask::GetOwnedClip {
+        shortcode: req.shortcode.clone(),
+        edit_token: req.edit_token.clone(),
+    };
+    get_owned_clip(owned, store).await?;
+    let model: model::UpdateClip = req.try_into()?;
+    Ok(store.update_clip(model, quota).await?.try_into()?)
 }
 
-/// Retrieves a clip based on the provided request and database connection pool.
+/// Retrieves a clip based on the provided request.
+///
+/// If the clip is limited to a number of views, the view is recorded before the clip is
+/// returned and the clip is deleted once its last view has been consumed.
 ///
 /// # Arguments
 ///
 /// * `req` - The request object containing necessary information to retrieve the clip.
-/// * `pool` - A reference to the database connection pool.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
 ///
 /// # Returns
 ///
 /// A `Result` indicating either the retrieved `Clip` or a `ServiceError` if an error occurs.
 ///
-pub async fn get_clip(req: ask::GetClip, pool: &DatabasePool) -> Result<Clip, ServiceError> {
+pub async fn get_clip(req: ask::GetClip, store: &dyn ClipStore) -> Result<Clip, ServiceError> {
+    let mut clip = unlock_clip(req, store).await?;
+    if clip.max_views.is_limited() {
+        store.record_view(&clip.shortcode).await?;
+        clip.views = field::Views::new(clip.views.into_inner() + 1);
+    }
+    Ok(clip)
+}
+
+/// Retrieves the metadata of a clip without recording a view.
+///
+/// The clip password, if any, must still match the one supplied in the request.
+///
+/// # Arguments
+///
+/// * `req` - The request object containing necessary information to retrieve the clip.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the retrieved `Clip` or a `ServiceError` if an error occurs.
+/// Callers must not expose the content of the returned clip.
+///
+pub async fn get_clip_metadata(
+    req: ask::GetClip,
+    store: &dyn ClipStore,
+) -> Result<Clip, ServiceError> {
+    unlock_clip(req, store).await
+}
+
+/// Lists the attachments of a clip, without their contents.
+///
+/// # Arguments
+///
+/// * `clip` - The clip to list the attachments of, as returned once its password was checked.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the attachments of the clip or a `ServiceError` if an error occurs.
+///
+pub async fn list_attachments(
+    clip: &Clip,
+    store: &dyn ClipStore,
+) -> Result<Vec<Attachment>, ServiceError> {
+    Ok(store
+        .list_attachments(&clip.shortcode)
+        .await?
+        .into_iter()
+        .map(TryInto::try_into)
+        .collect::<Result<_, _>>()?)
+}
+
+/// The span of view statistics reported when the request does not set one, in seconds.
+pub const DEFAULT_VIEW_SPAN: i64 = 7 * 24 * 3600;
+
+/// Retrieves the view statistics of a clip over a span of time.
+///
+/// The span ends now and starts `DEFAULT_VIEW_SPAN` earlier unless the request sets either
+/// end, and may not be longer than `views::MAX_SPAN_SECONDS`. Views still buffered by the
+/// hit counter are not included.
+///
+/// # Arguments
+///
+/// * `clip` - The clip to report on, as returned once its password was checked.
+/// * `req` - The request object holding the span to report on.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the `ViewStats` of the clip or a `ServiceError` if the span
+/// is invalid or an error occurs.
+///
+pub async fn clip_views(
+    clip: &Clip,
+    req: ask::ClipViews,
+    store: &dyn ClipStore,
+) -> Result<ViewStats, ServiceError> {
+    let to = req.to.unwrap_or_else(|| chrono::Utc::now().into());
+    let from = req
+        .from
+        .unwrap_or_else(|| Time::from_seconds(to.timestamp() - DEFAULT_VIEW_SPAN));
+    if from.timestamp() > to.timestamp() {
+        return Err(ClipError::InvalidDate("from must not be after to".to_owned()).into());
+    }
+    if to.timestamp() - from.timestamp() > views::MAX_SPAN_SECONDS {
+        return Err(ClipError::InvalidDate(format!(
+            "view statistics cover at most {} days at once",
+            views::MAX_SPAN_SECONDS / (24 * 3600)
+        ))
+        .into());
+    }
+    let mut stats = ViewStats::new(clip.shortcode.clone(), from, to);
+    for bucket in store
+        .clip_views(&clip.shortcode, stats.first_hour(), stats.last_hour())
+        .await?
+    {
+        stats.add(&bucket.try_into()?);
+    }
+    Ok(stats)
+}
+
+/// Retrieves an attachment of a clip for download, without recording a view.
+///
+/// # Arguments
+///
+/// * `req` - The request object identifying the attachment, including the clip password if it has one.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the attachment with its contents or a `ServiceError` if an error occurs.
+///
+pub async fn get_attachment(
+    req: ask::GetAttachment,
+    store: &dyn ClipStore,
+) -> Result<AttachmentFile, ServiceError> {
+    let unlock = ask::GetClip {
+        shortcode: req.shortcode.clone(),
+        password: req.password.clone(),
+    };
+    unlock_clip(unlock, store).await?;
+    Ok(store.get_attachment(req.into()).await?.try_into()?)
+}
+
+/// Retrieves a clip after checking its password, without recording a view.
+async fn unlock_clip(req: ask::GetClip, store: &dyn ClipStore) -> Result<Clip, ServiceError> {
     let user_password = req.password.clone();
-    let clip: Clip = query::get_clip(req, pool).await?.try_into()?;
-    if clip.password.has_password() {
-        if clip.password == user_password {
-            Ok(clip)
-        } else {
-            Err(ServiceError::PermissionError("Invalid password".to_owned()))
-        }
+    let clip: Clip = store.get_clip(&req.shortcode).await?.try_into()?;
+    if clip.password.verify(&user_password) {
+        Ok(clip)
+    } else {
+        Err(ServiceError::PermissionError("Invalid password".to_owned()))
+    }
+}
+
+/// Lists the revisions of a clip, oldest first.
+///
+/// Revisions of view-limited clips are never exposed, as reading them would bypass the view limit.
+///
+/// # Arguments
+///
+/// * `req` - The request object identifying the clip, including its password if it has one.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the revisions of the clip or a `ServiceError` if an error occurs.
+///
+pub async fn list_revisions(
+    req: ask::GetClip,
+    store: &dyn ClipStore,
+) -> Result<Vec<Revision>, ServiceError> {
+    let clip = unlock_revisions(req, store).await?;
+    store
+        .list_revisions(&clip.shortcode)
+        .await?
+        .into_iter()
+        .map(|revision| Ok(revision.try_into()?))
+        .collect()
+}
+
+/// Retrieves a single revision of a clip.
+///
+/// Revisions of view-limited clips are never exposed, as reading them would bypass the view limit.
+///
+/// # Arguments
+///
+/// * `req` - The request object identifying the clip and revision, including the clip password if it has one.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the retrieved `Revision` or a `ServiceError` if an error occurs.
+///
+pub async fn get_revision(
+    req: ask::GetRevision,
+    store: &dyn ClipStore,
+) -> Result<Revision, ServiceError> {
+    let clip = unlock_revisions(
+        ask::GetClip {
+            shortcode: req.shortcode,
+            password: req.password,
+        },
+        store,
+    )
+    .await?;
+    Ok(store
+        .get_revision(&clip.shortcode, req.revision)
+        .await?
+        .try_into()?)
+}
+
+/// Retrieves a clip whose revisions may be read, without recording a view.
+async fn unlock_revisions(req: ask::GetClip, store: &dyn ClipStore) -> Result<Clip, ServiceError> {
+    let clip = unlock_clip(req, store).await?;
+    if clip.max_views.is_limited() {
+        Err(ServiceError::NotFound)
     } else {
         Ok(clip)
     }
 }
 
-/// Generates a new API key and saves it in the database, returning the generated key.
+/// Restores the content and title of an earlier revision as the current version of a clip.
+///
+/// The restored content is recorded as a new revision; the expiration and password of
+/// the clip are left untouched.
 ///
 /// # Arguments
 ///
-/// * `pool` - A reference to a `DatabasePool` object representing the database connection pool.
+/// * `req` - The request object identifying the clip and revision, including the edit token of the clip.
+/// * `quota` - The limits on what a single API key may store.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the updated `Clip` or a `ServiceError` if an error occurs.
+///
+pub async fn restore_revision(
+    req: ask::RestoreRevision,
+    quota: &Quota,
+    store: &dyn ClipStore,
+) -> Result<Clip, ServiceError> {
+    let owned = ask::GetOwnedClip {
+        shortcode: req.shortcode,
+        edit_token: req.edit_token,
+    };
+    let clip = get_owned_clip(owned, store).await?;
+    let revision: Revision = store
+        .get_revision(&clip.shortcode, req.revision)
+        .await?
+        .try_into()?;
+    let update = ask::UpdateClip {
+        content: revision.content,
+        title: revision.title,
+        expires: clip.expires,
+        password: field::Password::default(),
+        shortcode: clip.shortcode,
+        edit_token: clip.edit_token,
+        keep_password: true,
+    };
+    update_clip(update, quota, store).await
+}
+
+/// Retrieves a clip on behalf of its owner, bypassing the clip password.
+///
+/// # Arguments
+///
+/// * `req` - The request object containing the shortcode and the edit token of the clip.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either the retrieved `Clip` or a `ServiceError` if the clip does
+/// not exist or the edit token does not match.
+///
+pub async fn get_owned_clip(
+    req: ask::GetOwnedClip,
+    store: &dyn ClipStore,
+) -> Result<Clip, ServiceError> {
+    let clip: Clip = store.get_clip(&req.shortcode).await?.try_into()?;
+    if clip.edit_token.grants(&req.edit_token) {
+        Ok(clip)
+    } else {
+        Err(ServiceError::PermissionError(
+            "Invalid edit token".to_owned(),
+        ))
+    }
+}
+
+/// Deletes a clip based on the provided request.
+///
+/// The request must carry the edit token issued when the clip was created, and the
+/// clip password, if any, must match the one supplied in the request.
+///
+/// # Arguments
+///
+/// * `req` - The request object identifying the clip to delete.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating success, or a `ServiceError` if the clip does not exist,
+/// the password does not match, or a database error occurs.
+///
+pub async fn delete_clip(req: ask::DeleteClip, store: &dyn ClipStore) -> Result<(), ServiceError> {
+    let edit_token = req.edit_token.clone();
+    let clip = unlock_clip(req.into(), store).await?;
+    if !clip.edit_token.grants(&edit_token) {
+        return Err(ServiceError::PermissionError(
+            "Invalid edit token".to_owned(),
+        ));
+    }
+    Ok(store.delete_clip(&clip.shortcode).await?)
+}
+
+/// The largest number of clips listed on a single page.
+pub const MAX_LIST_LIMIT: u32 = 100;
+
+/// Lists the publicly readable clips, one page at a time.
+///
+/// Password-protected, view-limited and expired clips are never listed.
+///
+/// # Arguments
+///
+/// * `req` - The request object containing the sort order, the cursor to start after and the page size.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either a `ClipPage` or a `ServiceError` if an error occurs.
+///
+pub async fn list_clips(
+    req: ask::ListClips,
+    store: &dyn ClipStore,
+) -> Result<ClipPage, ServiceError> {
+    let limit = req.limit.clamp(1, MAX_LIST_LIMIT);
+    let mut clips = store
+        .list_clips(req.sort, req.cursor.as_ref(), limit + 1)
+        .await?
+        .into_iter()
+        .map(|clip| Ok(clip.try_into()?))
+        .collect::<Result<Vec<Clip>, ServiceError>>()?;
+    let has_more = clips.len() > limit as usize;
+    clips.truncate(limit as usize);
+    let next = if has_more {
+        clips.last().map(|clip| ListCursor::after(clip, req.sort))
+    } else {
+        None
+    };
+    Ok(ClipPage { clips, next })
+}
+
+/// The number of search results on each page.
+pub const SEARCH_PAGE_SIZE: u32 = 20;
+
+/// Searches the publicly readable clips, best matches first.
+///
+/// Password-protected and view-limited clips are never returned.
+///
+/// # Arguments
+///
+/// * `req` - The request object containing the search terms and the page to return.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// A `Result` indicating either a page of `SearchResults` or a `ServiceError` if an error occurs.
+///
+pub async fn search_clips(
+    req: ask::SearchClips,
+    store: &dyn ClipStore,
+) -> Result<SearchResults, ServiceError> {
+    let page = req.page.max(1);
+    let offset = (page - 1).saturating_mul(SEARCH_PAGE_SIZE);
+    let mut hits: Vec<_> = store
+        .search_clips(&req.query, SEARCH_PAGE_SIZE + 1, offset)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let has_more = hits.len() > SEARCH_PAGE_SIZE as usize;
+    hits.truncate(SEARCH_PAGE_SIZE as usize);
+    Ok(SearchResults {
+        hits,
+        page,
+        has_more,
+    })
+}
+
+/// Generates a new API key and saves its digest in the database, returning the generated key.
+///
+/// The returned key cannot be recovered later, so it must be handed to the caller right away.
+///
+/// # Arguments
+///
+/// * `req` - The name, scopes and expiration of the key.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
 ///
 /// # Returns
 ///
 /// Returns a `Result` containing the generated `ApiKey` if the key generation and saving process is successful,
 /// or a `ServiceError` if an error occurs during the process.
-pub async fn generate_api_key(pool: &DatabasePool) -> Result<ApiKey, ServiceError> {
+pub async fn generate_api_key(
+    req: ask::NewApiKey,
+    store: &dyn ClipStore,
+) -> Result<ApiKey, ServiceError> {
+    if req.scopes.is_empty() {
+        return Err(ServiceError::PermissionError(
+            "an API key needs at least one scope".to_owned(),
+        ));
+    }
+    req.expires.check_future()?;
     let api_key = ApiKey::default();
-    Ok(query::save_api_key(api_key, pool).await?)
+    store.save_api_key(&api_key, req.into()).await?;
+    Ok(api_key)
+}
+
+/// Retrieves the name, timestamps and scopes of an API key.
+///
+/// # Arguments
+///
+/// * `api_key` - A reference to the `ApiKey` to look up.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the details of the key, or a `ServiceError` if the key
+/// does not exist or could not be read.
+pub async fn get_api_key(api_key: &ApiKey, store: &dyn ClipStore) -> Result<KeyInfo, ServiceError> {
+    Ok(store.get_api_key(api_key).await?.into())
+}
+
+/// Authenticates a request made with an API key, recording that the key was used.
+///
+/// # Arguments
+///
+/// * `api_key` - A reference to the `ApiKey` the request was made with.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the details of the key if it exists and has not expired,
+/// `None` if it does not, or a `ServiceError` if the key could not be checked.
+pub async fn authenticate_api_key(
+    api_key: &ApiKey,
+    store: &dyn ClipStore,
+) -> Result<Option<KeyInfo>, ServiceError> {
+    if !store.api_key_is_valid(api_key).await? {
+        return Ok(None);
+    }
+    store.touch_api_key(api_key).await?;
+    Ok(Some(get_api_key(api_key, store).await?))
 }
 
 /// Revokes an API key, returning the revocation status.
@@ -127,7 +580,7 @@ pub async fn generate_api_key(pool: &DatabasePool) -> Result<ApiKey, ServiceErro
 /// # Arguments
 ///
 /// * `api_key` - An instance of the `ApiKey` struct representing the API key to be revoked.
-/// * `pool` - A reference to a `DatabasePool` object representing the database connection pool.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
 ///
 /// # Returns
 ///
@@ -135,9 +588,46 @@ pub async fn generate_api_key(pool: &DatabasePool) -> Result<ApiKey, ServiceErro
 /// or a `ServiceError` if an error occurs during the revocation process.
 pub async fn revoke_api_key(
     api_key: ApiKey,
-    pool: &DatabasePool,
+    store: &dyn ClipStore,
+) -> Result<query::RevocationStatus, ServiceError> {
+    Ok(store.revoke_api_key(&api_key).await?)
+}
+
+/// Revokes the API key with the given id, as listed by `list_api_keys`.
+///
+/// # Arguments
+///
+/// * `id` - The hex id of the key to revoke.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the `RevocationStatus` if the API key revocation is successful,
+/// or a `ServiceError` if an error occurs during the revocation process.
+pub async fn revoke_api_key_by_id(
+    id: &str,
+    store: &dyn ClipStore,
 ) -> Result<query::RevocationStatus, ServiceError> {
-    Ok(query::revoke_api_key(api_key, pool).await?)
+    Ok(store.revoke_api_key_by_id(id).await?)
+}
+
+/// Lists the details of every API key, oldest first.
+///
+/// # Arguments
+///
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the details of each key, or a `ServiceError` if the keys
+/// could not be read.
+pub async fn list_api_keys(store: &dyn ClipStore) -> Result<Vec<KeyInfo>, ServiceError> {
+    Ok(store
+        .list_api_keys()
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect())
 }
 
 /// Checks if an API key is valid in the database.
@@ -145,27 +635,216 @@ pub async fn revoke_api_key(
 /// # Arguments
 ///
 /// * `api_key` - An `ApiKey` representing the API key to check.
-/// * `pool` - A reference to a `DatabasePool` representing the connection pool to the database.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
 ///
 /// # Returns
 ///
 /// Returns a `Result<bool, ServiceError>` indicating success or an error if the check operation fails.
 /// If successful, it returns a boolean value indicating whether the API key is valid or not.
 ///
-pub async fn api_key_is_valid(api_key: ApiKey, pool: &DatabasePool) -> Result<bool, ServiceError> {
-    Ok(query::api_key_is_valid(api_key, pool).await?)
+pub async fn api_key_is_valid(
+    api_key: ApiKey,
+    store: &dyn ClipStore,
+) -> Result<bool, ServiceError> {
+    Ok(store.api_key_is_valid(&api_key).await?)
 }
 
-/// Deletes expired records from the database and returns the number of deleted records.
+/// Replaces any plaintext clip passwords left in the database with their Argon2 hash.
+///
+/// Passwords stored before hashing was introduced are migrated in place; passwords that
+/// are already hashed are left untouched, so this is safe to run on every startup.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` object representing the database connection pool.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the number of rehashed passwords if the migration is successful,
+/// or a `ServiceError` if an error occurs during the migration.
+pub async fn rehash_plaintext_passwords(pool: &DatabasePool) -> Result<u64, ServiceError> {
+    let mut rehashed = 0;
+    for stored in query::list_stored_passwords(pool).await? {
+        if !stored.is_plaintext() {
+            continue;
+        }
+        let password = field::Password::new(stored.password().to_owned())?;
+        let hash = field::PasswordHash::from_password(&password)?;
+        query::set_password_hash(&stored.shortcode(), hash, pool).await?;
+        rehashed += 1;
+    }
+    Ok(rehashed)
+}
+
+/// Replaces any API keys stored in plaintext with their SHA-256 digest.
+///
+/// Keys stored before digests were introduced are migrated in place; keys that are already
+/// stored as digests are left untouched, so this is safe to run on every startup.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` object representing the database connection pool.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the number of hashed keys if the migration is successful,
+/// or a `ServiceError` if an error occurs during the migration.
+pub async fn hash_plaintext_api_keys(pool: &DatabasePool) -> Result<u64, ServiceError> {
+    let mut hashed = 0;
+    for api_key in query::list_plaintext_api_keys(pool).await? {
+        query::replace_with_digest(&api_key, pool).await?;
+        hashed += 1;
+    }
+    Ok(hashed)
+}
+
+/// Rewrites the stored content of every clip and revision according to a compression
+/// threshold.
 ///
 /// # Arguments
 ///
+/// * `compress_above` - The size, in bytes, above which content is stored compressed.
 /// * `pool` - A reference to a `DatabasePool` object representing the database connection pool.
 ///
 /// # Returns
 ///
+/// Returns a `Result` containing the number of rewritten rows and the stored content size
+/// before and after, or a `ServiceError` if reading or rewriting the content fails.
+pub async fn recompress_clips(
+    compress_above: usize,
+    pool: &DatabasePool,
+) -> Result<model::Recompression, ServiceError> {
+    Ok(query::recompress_contents(compress_above, pool).await?)
+}
+
+/// Reports what the live clips created with an API key store.
+///
+/// # Arguments
+///
+/// * `api_key` - A reference to the `ApiKey` the clips were created with.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the number of clips and their stored bytes, or a
+/// `ServiceError` if the usage could not be read.
+pub async fn key_usage(api_key: &ApiKey, store: &dyn ClipStore) -> Result<Usage, ServiceError> {
+    Ok(store.key_usage(&api_key.digest()).await?.into())
+}
+
+/// Deletes expired records from the database and returns the number of deleted records.
+///
+/// # Arguments
+///
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
 /// Returns a `Result` containing the number of deleted records if the deletion process is successful,
 /// or a `ServiceError` if an error occurs during the deletion process.
-pub async fn delete_expired(pool: &DatabasePool) -> Result<u64, ServiceError> {
-    Ok(query::delete_expired(pool).await?)
+pub async fn delete_expired(store: &dyn ClipStore) -> Result<u64, ServiceError> {
+    Ok(store.delete_expired().await?)
+}
+
+/// Deletes the view statistics of hours too old to be requested, returning how many
+/// buckets were deleted.
+///
+/// # Arguments
+///
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the number of deleted buckets, or a `ServiceError` if they
+/// could not be deleted.
+pub async fn delete_old_views(store: &dyn ClipStore) -> Result<u64, ServiceError> {
+    let before = views::bucket_of(chrono::Utc::now().timestamp()) - views::MAX_SPAN_SECONDS;
+    Ok(store.delete_old_views(before).await?)
+}
+
+/// Retrieves a clip without checking its password or recording a view, for administration.
+///
+/// # Arguments
+///
+/// * `shortcode` - The shortcode of the clip.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the clip, or a `ServiceError` if it does not exist or could not be read.
+pub async fn find_clip(shortcode: ShortCode, store: &dyn ClipStore) -> Result<Clip, ServiceError> {
+    Ok(store.get_clip(&shortcode).await?.try_into()?)
+}
+
+/// Lists every stored clip, newest first, including protected and expired ones, for administration.
+///
+/// # Arguments
+///
+/// * `limit` - The maximum number of clips to list.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the clips, or a `ServiceError` if they could not be read.
+pub async fn list_all_clips(limit: u32, store: &dyn ClipStore) -> Result<Vec<Clip>, ServiceError> {
+    store
+        .list_all_clips(limit)
+        .await?
+        .into_iter()
+        .map(|clip| Ok(clip.try_into()?))
+        .collect()
+}
+
+/// Deletes a clip without checking its edit token, for administration.
+///
+/// # Arguments
+///
+/// * `shortcode` - The shortcode of the clip to delete.
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` indicating success, or a `ServiceError` if the clip does not exist or
+/// could not be deleted.
+pub async fn purge_clip(shortcode: ShortCode, store: &dyn ClipStore) -> Result<(), ServiceError> {
+    Ok(store.delete_clip(&shortcode).await?)
+}
+
+/// Counts the clips, stored bytes, hits and API keys in the database.
+///
+/// # Arguments
+///
+/// * `store` - A reference to the `ClipStore` the data is kept in.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the totals, or a `ServiceError` if they could not be read.
+pub async fn stats(store: &dyn ClipStore) -> Result<model::Stats, ServiceError> {
+    Ok(store.stats().await?)
+}
+
+/// Returns the size of the database file, in bytes.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` object representing the database connection pool.
+///
+/// # Returns
+///
+/// Returns a `Result` containing the size, or a `ServiceError` if it could not be read.
+pub async fn database_size(pool: &DatabasePool) -> Result<u64, ServiceError> {
+    Ok(query::database_size(pool).await?)
+}
+
+/// Rebuilds the database file, reclaiming the space left by deleted clips.
+///
+/// # Arguments
+///
+/// * `pool` - A reference to a `DatabasePool` object representing the database connection pool.
+///
+/// # Returns
+///
+/// Returns a `Result` indicating success, or a `ServiceError` if the database could not be rebuilt.
+pub async fn vacuum(pool: &DatabasePool) -> Result<(), ServiceError> {
+    Ok(query::vacuum(pool).await?)
 }
@@ -1,7 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use crate::domain::clip::field;
-use crate::ShortCode;
+use crate::domain::clip::{field, ClipSort, ListCursor};
+use crate::web::api::{scope::Scopes, ApiKey};
+use crate::{ShortCode, Time};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct NewClip {
@@ -9,6 +10,28 @@ pub struct NewClip {
     pub title: field::Title,
     pub expires: field::Expires,
     pub password: field::Password,
+    #[serde(default)]
+    pub max_views: field::MaxViews,
+    #[serde(default)]
+    pub language: field::Language,
+    #[serde(default)]
+    pub format: field::Format,
+    /// The shortcode to publish the clip under instead of a generated one.
+    #[serde(default)]
+    pub shortcode: field::VanityShortCode,
+    /// Files uploaded with the clip; only multipart requests carry attachments.
+    #[serde(skip)]
+    pub attachments: Vec<NewAttachment>,
+    /// The API key the clip is created with, which its quota is charged to.
+    #[serde(skip)]
+    pub api_key: Option<ApiKey>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NewAttachment {
+    pub filename: field::FileName,
+    pub mime_type: field::MimeType,
+    pub data: Vec<u8>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -18,6 +41,11 @@ pub struct UpdateClip {
     pub expires: field::Expires,
     pub password: field::Password,
     pub shortcode: field::ShortCode,
+    #[serde(skip)]
+    pub edit_token: field::EditToken,
+    /// Leaves the current password of the clip in place, ignoring `password`.
+    #[serde(skip)]
+    pub keep_password: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -26,6 +54,80 @@ pub struct GetClip {
     pub password: field::Password,
 }
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetAttachment {
+    pub shortcode: ShortCode,
+    pub password: field::Password,
+    pub attachment_id: field::AttachmentId,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeleteClip {
+    pub shortcode: ShortCode,
+    pub password: field::Password,
+    pub edit_token: field::EditToken,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetRevision {
+    pub shortcode: ShortCode,
+    pub password: field::Password,
+    pub revision: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RestoreRevision {
+    pub shortcode: ShortCode,
+    pub edit_token: field::EditToken,
+    pub revision: u32,
+}
+
+#[derive(Debug)]
+pub struct ListClips {
+    pub sort: ClipSort,
+    pub cursor: Option<ListCursor>,
+    pub limit: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SearchClips {
+    pub query: String,
+    /// The page of results to return, starting at 1.
+    pub page: u32,
+}
+
+/// The span of time to report the views of a clip over; see `action::clip_views`.
+#[derive(Debug, Default)]
+pub struct ClipViews {
+    pub from: Option<Time>,
+    pub to: Option<Time>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GetOwnedClip {
+    pub shortcode: ShortCode,
+    pub edit_token: field::EditToken,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct NewApiKey {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub scopes: Scopes,
+    #[serde(default)]
+    pub expires: field::Expires,
+}
+
+impl From<DeleteClip> for GetClip {
+    fn from(req: DeleteClip) -> Self {
+        Self {
+            shortcode: req.shortcode,
+            password: req.password,
+        }
+    }
+}
+
 impl GetClip {
     pub fn from_raw(shortcode: &str) -> Self {
         Self {
@@ -1,7 +1,7 @@
 pub mod action;
 pub mod ask;
 
-use crate::{ClipError, DataError};
+use crate::{domain::quota::QuotaError, ClipError, DataError};
 
 #[derive(Debug, thiserror::Error)]
 pub enum ServiceError {
@@ -11,8 +11,12 @@ pub enum ServiceError {
     Data(DataError),
     #[error("not found")]
     NotFound,
+    #[error("conflict: {0}")]
+    Conflict(String),
     #[error("permissions not met: {0}")]
     PermissionError(String),
+    #[error("quota exceeded: {0}")]
+    Quota(#[from] QuotaError),
 }
 
 impl From<DataError> for ServiceError {
@@ -22,6 +26,11 @@ impl From<DataError> for ServiceError {
                 sqlx::Error::RowNotFound => Self::NotFound,
                 other => Self::Data(DataError::Database(other)),
             },
+            DataError::ShortCodeTaken(shortcode) => {
+                Self::Conflict(format!("the shortcode {} is already taken", shortcode))
+            }
+            DataError::Quota(q) => Self::Quota(q),
+            other => Self::Data(other),
         }
     }
 }
@@ -2,6 +2,8 @@ use rocket::serde::json::Json;
 use rocket::Request;
 use rocket::{catch, catchers, Catcher};
 
+use crate::web::ratelimit::TooManyRequests;
+
 #[catch(default)]
 fn default(req: &Request) -> Json<&'static str> {
     eprintln!("General error: {:?}", req);
@@ -29,12 +31,24 @@ fn missing_api_key() -> Json<&'static str> {
     Json("API key missing or invalid")
 }
 
+#[catch(403)]
+fn forbidden() -> Json<&'static str> {
+    Json("API key lacks the scope this request needs")
+}
+
+#[catch(429)]
+fn too_many_requests() -> TooManyRequests<Json<&'static str>> {
+    TooManyRequests(Json("too many requests"))
+}
+
 pub fn catchers() -> Vec<Catcher> {
     catchers![
         not_found,
         default,
         internal_error,
         missing_api_key,
-        request_error
+        request_error,
+        forbidden,
+        too_many_requests
     ]
 }
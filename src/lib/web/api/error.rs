@@ -1,7 +1,10 @@
 use rocket::{serde::json::Json, Responder};
 use serde::Serialize;
 
-use crate::ServiceError;
+use crate::{
+    domain::quota::{QuotaError, Usage},
+    ClipError, ServiceError,
+};
 
 #[derive(Responder, Debug, thiserror::Error, Serialize)]
 pub enum ApiKeyError {
@@ -15,6 +18,27 @@ pub enum ApiKeyError {
     DecodeError(String),
 }
 
+/// Body of the response to a request that would take an API key over its quota.
+#[derive(Debug, Serialize)]
+pub struct QuotaExceeded {
+    pub error: String,
+    pub usage: Usage,
+    pub limit: u64,
+}
+
+impl From<QuotaError> for QuotaExceeded {
+    fn from(err: QuotaError) -> Self {
+        let limit = match err {
+            QuotaError::Clips { limit, .. } | QuotaError::Bytes { limit, .. } => limit,
+        };
+        Self {
+            error: err.to_string(),
+            usage: err.usage(),
+            limit,
+        }
+    }
+}
+
 #[derive(Responder, Debug, thiserror::Error)]
 pub enum ApiError {
     #[error("not found")]
@@ -32,6 +56,32 @@ pub enum ApiError {
     #[error("key error")]
     #[response(status = 400, content_type = "json")]
     KeyError(Json<ApiKeyError>),
+
+    #[error("clip quota exceeded")]
+    #[response(status = 429, content_type = "json")]
+    TooManyClips(Json<QuotaExceeded>),
+
+    #[error("storage quota exceeded")]
+    #[response(status = 413, content_type = "json")]
+    TooLarge(Json<QuotaExceeded>),
+
+    #[error("conflict")]
+    #[response(status = 409, content_type = "json")]
+    Conflict(Json<String>),
+
+    #[error("forbidden")]
+    #[response(status = 403, content_type = "json")]
+    Forbidden(Json<String>),
+
+    #[error("rate limited")]
+    #[response(status = 429, content_type = "json")]
+    RateLimited(Json<String>),
+}
+
+impl From<ClipError> for ApiError {
+    fn from(err: ClipError) -> Self {
+        ServiceError::from(err).into()
+    }
 }
 
 impl From<ServiceError> for ApiError {
@@ -39,8 +89,11 @@ impl From<ServiceError> for ApiError {
         match err {
             ServiceError::Clip(c) => Self::User(Json(format!("clip parsing error: {}", c))),
             ServiceError::NotFound => Self::NotFound(Json("entity not found".to_owned())),
+            ServiceError::Conflict(msg) => Self::Conflict(Json(msg)),
             ServiceError::Data(_) => Self::Server(Json("a server error occurred".to_owned())),
             ServiceError::PermissionError(msg) => Self::User(Json(msg)),
+            ServiceError::Quota(q @ QuotaError::Clips { .. }) => Self::TooManyClips(Json(q.into())),
+            ServiceError::Quota(q @ QuotaError::Bytes { .. }) => Self::TooLarge(Json(q.into())),
         }
     }
 }
@@ -1,6 +1,7 @@
 mod catcher;
 mod error;
 mod routes;
+pub mod scope;
 
 pub use catcher::catchers;
 pub use routes::routes;
@@ -14,12 +15,68 @@ use rocket::{
     serde::json::Json,
     Request, State,
 };
-
-use crate::{data::AppDatabase, service::action, web::api::error::ApiError};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    data::Store,
+    domain::{
+        clip::field::EditToken,
+        quota::{Quota, Usage},
+    },
+    service::action,
+    web::api::error::ApiError,
+    web::api::scope::Scopes,
+    web::{
+        ratelimit::{self, RateLimiter},
+        PublicClip,
+    },
+    Time,
+};
 
 use self::error::ApiKeyError;
 
 pub const API_KEY_HEADER: &str = "x-api-key";
+pub const AUTHORIZATION_HEADER: &str = "authorization";
+pub const EDIT_TOKEN_HEADER: &str = "x-edit-token";
+
+/// Response to a clip creation request, carrying the edit token required to
+/// update or delete the clip later on.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewClipResponse {
+    #[serde(flatten)]
+    pub clip: PublicClip,
+    pub edit_token: String,
+}
+
+/// What an API key currently stores, alongside the limits of its quota.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyUsage {
+    #[serde(flatten)]
+    pub usage: Usage,
+    #[serde(flatten)]
+    pub quota: Quota,
+}
+
+/// The name, timestamps and scopes of an API key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyInfo {
+    /// The start of the key's digest in hex, naming the key without revealing it.
+    pub id: String,
+    pub name: Option<String>,
+    pub created_at: Time,
+    pub last_used_at: Option<Time>,
+    pub expires_at: Option<Time>,
+    pub scopes: Scopes,
+}
+
+/// Response to an API key creation request. This is the only time the key is shown.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewKeyResponse {
+    pub api_key: String,
+    #[serde(flatten)]
+    pub info: KeyInfo,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ApiKey(Vec<u8>);
@@ -32,6 +89,35 @@ impl ApiKey {
     pub fn into_inner(self) -> Vec<u8> {
         self.0
     }
+
+    pub fn digest(&self) -> KeyDigest {
+        KeyDigest(Sha256::digest(&self.0).to_vec())
+    }
+}
+
+/// The SHA-256 digest of an API key, which is all the database keeps of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyDigest(Vec<u8>);
+
+impl KeyDigest {
+    /// The length of a digest, in bytes.
+    pub const LEN: usize = 32;
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+impl From<Vec<u8>> for KeyDigest {
+    fn from(digest: Vec<u8>) -> Self {
+        Self(digest)
+    }
+}
+
+impl From<Vec<u8>> for ApiKey {
+    fn from(key: Vec<u8>) -> Self {
+        Self(key)
+    }
 }
 
 impl Default for ApiKey {
@@ -65,29 +151,57 @@ impl<'r> FromRequest<'r> for ApiKey {
         fn key_error(e: ApiKeyError) -> Outcome<ApiKey, ApiError> {
             Outcome::Failure((Status::BadRequest, ApiError::KeyError(Json(e))))
         }
-        match req.headers().get_one(API_KEY_HEADER) {
+        let bearer = req
+            .headers()
+            .get_one(AUTHORIZATION_HEADER)
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+        match req.headers().get_one(API_KEY_HEADER).or(bearer) {
             None => key_error(ApiKeyError::NotFound("API key not found".to_string())),
             Some(key) => {
-                let db = match req.guard::<&State<AppDatabase>>().await {
-                    Outcome::Success(db) => db,
+                let store = match req.guard::<&State<Store>>().await {
+                    Outcome::Success(store) => store,
                     _ => return server_error(),
                 };
                 let api_key = match ApiKey::from_str(key) {
                     Ok(key) => key,
                     Err(e) => return key_error(e),
                 };
-                match action::api_key_is_valid(api_key.clone(), db.get_pool()).await {
-                    Ok(valid) if valid => Outcome::Success(api_key),
-                    Ok(valid) if !valid => {
-                        key_error(ApiKeyError::NotFound("API key not found".to_owned()))
+                let info = match action::authenticate_api_key(&api_key, store.get()).await {
+                    Ok(Some(info)) => info,
+                    Ok(None) => {
+                        return key_error(ApiKeyError::NotFound("API key not found".to_owned()))
+                    }
+                    Err(_) => return server_error(),
+                };
+                // Only keys that authenticated are limited, so made-up keys cannot each
+                // claim a bucket of their own.
+                if let Outcome::Success(limiter) = req.guard::<&State<RateLimiter>>().await {
+                    if let Err(retry_after) = limiter.check_api_key(&api_key.digest()) {
+                        return ratelimit::reject(
+                            req,
+                            retry_after,
+                            ApiError::RateLimited(Json("too many requests".to_owned())),
+                        );
                     }
-                    _ => server_error(),
                 }
+                req.local_cache(|| Some(info));
+                Outcome::Success(api_key)
             }
         }
     }
 }
 
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for EditToken {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = req.headers().get_one(EDIT_TOKEN_HEADER).map(str::to_owned);
+        Outcome::Success(EditToken::new(token))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -112,6 +226,15 @@ mod tests {
         assert_eq!(key.0.len(), 16);
     }
 
+    #[test]
+    fn test_digest() {
+        let api_key = ApiKey(vec![1, 2, 3]);
+        let digest = api_key.digest();
+        assert_eq!(digest.clone().into_inner().len(), KeyDigest::LEN);
+        assert_eq!(digest, ApiKey(vec![1, 2, 3]).digest());
+        assert_ne!(digest, ApiKey(vec![1, 2, 4]).digest());
+    }
+
     #[test]
     fn test_from_str() {
         let key_str = "AQIDBAUGBwgJCgsMDQ4PEA==";
@@ -1,65 +1,364 @@
-use rocket::{http::CookieJar, serde::json::Json, State};
+use std::str::FromStr;
+
+use rocket::{
+    form::{Contextual, Form},
+    response::status,
+    serde::json::Json,
+    State,
+};
 
 use crate::{
-    data::AppDatabase,
+    data::Store,
+    domain::clip::{
+        field::{AttachmentId, EditToken},
+        views::ViewSource,
+        ClipSort, ListCursor, Revision, ViewStats,
+    },
+    domain::quota::Quota,
     service::{self, action},
     web::{
-        api::{error::ApiError, ApiKey},
-        HitCounter, PASSWORD_COOKIE,
+        api::{
+            error::ApiError,
+            scope::{AdminKey, ReadKey, WriteKey},
+            ApiKey, KeyInfo, KeyUsage, NewClipResponse, NewKeyResponse,
+        },
+        attachment::Download,
+        form, BaseUrl, ClipCookies, ClipList, HitCounter, PublicClip, SearchPage, Visitor,
     },
+    ClipError, ShortCode, Time,
 };
 
-#[rocket::get("/<shortcode>")]
+/// The number of clips listed when the request does not set a limit.
+const DEFAULT_LIST_LIMIT: u32 = 20;
+
+#[rocket::get("/<shortcode>?<metadata>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn get_clip(
-    shortcode: &str,
-    database: &State<AppDatabase>,
-    cookies: &CookieJar<'_>,
+    shortcode: Result<ShortCode, ClipError>,
+    metadata: bool,
+    store: &State<Store>,
+    cookies: ClipCookies<'_>,
     hit_counter: &State<HitCounter>,
-    _api_key: ApiKey,
-) -> Result<Json<crate::Clip>, ApiError> {
-    use crate::domain::clip::field::Password;
-
+    visitor: Visitor,
+    base_url: BaseUrl,
+    _api_key: ReadKey,
+) -> Result<Json<PublicClip>, ApiError> {
+    let shortcode = shortcode?;
     let req = service::ask::GetClip {
-        shortcode: shortcode.into(),
-        password: cookies
-            .get(PASSWORD_COOKIE)
-            .map(|cookie| cookie.value())
-            .map(|raw_password| Password::new(raw_password.to_string()).ok())
-            .flatten()
-            .unwrap_or_else(Password::default),
+        shortcode: shortcode.clone(),
+        password: cookies.password(),
+    };
+    if metadata {
+        let clip = action::get_clip_metadata(req, store.get()).await?;
+        let attachments = action::list_attachments(&clip, store.get()).await?;
+        return Ok(Json(
+            PublicClip::metadata(clip, &base_url).with_attachments(attachments, &base_url),
+        ));
+    }
+    let mut clip = action::get_clip(req, store.get()).await?;
+    hit_counter.hit(visitor.view(shortcode, ViewSource::Api));
+    hit_counter.merge(&mut clip);
+    let attachments = action::list_attachments(&clip, store.get()).await?;
+    Ok(Json(
+        PublicClip::new(clip, &base_url).with_attachments(attachments, &base_url),
+    ))
+}
+
+#[rocket::get("/<shortcode>/stats?<from>&<to>")]
+pub async fn clip_stats(
+    shortcode: Result<ShortCode, ClipError>,
+    from: Option<&str>,
+    to: Option<&str>,
+    store: &State<Store>,
+    cookies: ClipCookies<'_>,
+    hit_counter: &State<HitCounter>,
+    _api_key: ReadKey,
+) -> Result<Json<ViewStats>, ApiError> {
+    let shortcode = shortcode?;
+    let parse = |time: Option<&str>| time.map(Time::from_str).transpose();
+    let req = service::ask::ClipViews {
+        from: parse(from).map_err(ClipError::from)?,
+        to: parse(to).map_err(ClipError::from)?,
+    };
+    let unlock = service::ask::GetClip {
+        shortcode,
+        password: cookies.password(),
+    };
+    let clip = action::get_clip_metadata(unlock, store.get()).await?;
+    let mut stats = action::clip_views(&clip, req, store.get()).await?;
+    hit_counter.merge_views(&mut stats);
+    Ok(Json(stats))
+}
+
+#[rocket::get("/<shortcode>/attachments/<attachment_id>")]
+pub async fn get_attachment(
+    shortcode: Result<ShortCode, ClipError>,
+    attachment_id: &str,
+    store: &State<Store>,
+    cookies: ClipCookies<'_>,
+    _api_key: ReadKey,
+) -> Result<Download, ApiError> {
+    let shortcode = shortcode?;
+    let attachment_id = AttachmentId::from_str(attachment_id)
+        .map_err(|_| ApiError::NotFound(Json("entity not found".to_owned())))?;
+    let req = service::ask::GetAttachment {
+        shortcode: shortcode.clone(),
+        password: cookies.password(),
+        attachment_id,
+    };
+    let file = action::get_attachment(req, store.get()).await?;
+    Ok(Download(file))
+}
+
+#[rocket::get("/?<limit>&<cursor>&<sort>")]
+pub async fn list_clips(
+    limit: Option<u32>,
+    cursor: Option<&str>,
+    sort: Option<&str>,
+    store: &State<Store>,
+    hit_counter: &State<HitCounter>,
+    base_url: BaseUrl,
+    _api_key: ReadKey,
+) -> Result<Json<ClipList>, ApiError> {
+    let sort = match sort {
+        Some(sort) => ClipSort::from_str(sort).map_err(|_| {
+            ApiError::User(Json(
+                "sort must be one of posted, hits or expires".to_owned(),
+            ))
+        })?,
+        None => ClipSort::default(),
+    };
+    let cursor = cursor
+        .map(ListCursor::from_str)
+        .transpose()
+        .map_err(service::ServiceError::from)?;
+    let req = service::ask::ListClips {
+        sort,
+        cursor,
+        limit: limit.unwrap_or(DEFAULT_LIST_LIMIT),
+    };
+    let mut page = action::list_clips(req, store.get()).await?;
+    page.clips
+        .iter_mut()
+        .for_each(|clip| hit_counter.merge(clip));
+    Ok(Json(ClipList::new(page, &base_url)))
+}
+
+#[rocket::get("/search?<q>&<page>")]
+pub async fn search_clips(
+    q: String,
+    page: Option<u32>,
+    store: &State<Store>,
+    base_url: BaseUrl,
+    _api_key: ReadKey,
+) -> Result<Json<SearchPage>, ApiError> {
+    let req = service::ask::SearchClips {
+        query: q.clone(),
+        page: page.unwrap_or(1),
+    };
+    let results = action::search_clips(req, store.get()).await?;
+    Ok(Json(SearchPage::new(q, results, &base_url)))
+}
+
+async fn create_clip(
+    req: service::ask::NewClip,
+    api_key: ApiKey,
+    quota: &Quota,
+    store: &Store,
+    base_url: &BaseUrl,
+) -> Result<Json<NewClipResponse>, ApiError> {
+    let req = service::ask::NewClip {
+        api_key: Some(api_key),
+        ..req
     };
-    let clip = action::get_clip(req, database.get_pool()).await?;
-    hit_counter.hit(shortcode.into(), 1);
-    Ok(Json(clip))
+    let clip = action::new_clip(req, quota, store.get()).await?;
+    let edit_token = clip.edit_token.clone().into_inner().unwrap_or_default();
+    let attachments = action::list_attachments(&clip, store.get()).await?;
+    let clip = PublicClip::new(clip, base_url).with_attachments(attachments, base_url);
+    Ok(Json(NewClipResponse { clip, edit_token }))
 }
 
-#[rocket::post("/", data = "<req>")]
+#[rocket::post("/", data = "<req>", rank = 2)]
 pub async fn new_clip(
     req: Json<service::ask::NewClip>,
-    database: &State<AppDatabase>,
-    _api_key: ApiKey,
-) -> Result<Json<crate::Clip>, ApiError> {
-    let clip = action::new_clip(req.into_inner(), database.get_pool()).await?;
-    Ok(Json(clip))
+    store: &State<Store>,
+    quota: &State<Quota>,
+    base_url: BaseUrl,
+    api_key: WriteKey,
+) -> Result<Json<NewClipResponse>, ApiError> {
+    create_clip(
+        req.into_inner(),
+        api_key.into_key(),
+        quota,
+        store,
+        &base_url,
+    )
+    .await
+}
+
+/// Creates a clip from a multipart upload, which can carry attachments.
+#[rocket::post("/", data = "<form>", format = "multipart/form-data", rank = 1)]
+pub async fn upload_clip(
+    form: Form<Contextual<'_, form::NewClip>>,
+    store: &State<Store>,
+    quota: &State<Quota>,
+    base_url: BaseUrl,
+    api_key: WriteKey,
+) -> Result<Json<NewClipResponse>, ApiError> {
+    let form = form.into_inner();
+    match form.value {
+        Some(value) => {
+            let req = value.into_request().map_err(service::ServiceError::from)?;
+            create_clip(req, api_key.into_key(), quota, store, &base_url).await
+        }
+        None => {
+            let errors: Vec<String> = form.context.errors().map(|e| e.to_string()).collect();
+            Err(ApiError::User(Json(errors.join(", "))))
+        }
+    }
 }
 
 #[rocket::put("/", data = "<req>")]
 pub async fn update_clip(
     req: Json<service::ask::UpdateClip>,
-    database: &State<AppDatabase>,
-    _api_key: ApiKey,
-) -> Result<Json<crate::Clip>, ApiError> {
-    let clip = action::update_clip(req.into_inner(), database.get_pool()).await?;
-    Ok(Json(clip))
+    store: &State<Store>,
+    quota: &State<Quota>,
+    edit_token: EditToken,
+    base_url: BaseUrl,
+    _api_key: WriteKey,
+) -> Result<Json<PublicClip>, ApiError> {
+    let req = service::ask::UpdateClip {
+        edit_token,
+        ..req.into_inner()
+    };
+    let clip = action::update_clip(req, quota, store.get()).await?;
+    Ok(Json(PublicClip::new(clip, &base_url)))
+}
+
+#[rocket::delete("/<shortcode>")]
+pub async fn delete_clip(
+    shortcode: Result<ShortCode, ClipError>,
+    store: &State<Store>,
+    cookies: ClipCookies<'_>,
+    edit_token: EditToken,
+    _api_key: WriteKey,
+) -> Result<status::NoContent, ApiError> {
+    let shortcode = shortcode?;
+    let req = service::ask::DeleteClip {
+        shortcode: shortcode.clone(),
+        password: cookies.password(),
+        edit_token,
+    };
+    action::delete_clip(req, store.get()).await?;
+    Ok(status::NoContent)
+}
+
+#[rocket::get("/<shortcode>/revisions")]
+pub async fn list_revisions(
+    shortcode: Result<ShortCode, ClipError>,
+    store: &State<Store>,
+    cookies: ClipCookies<'_>,
+    _api_key: ReadKey,
+) -> Result<Json<Vec<Revision>>, ApiError> {
+    let shortcode = shortcode?;
+    let req = service::ask::GetClip {
+        shortcode: shortcode.clone(),
+        password: cookies.password(),
+    };
+    let revisions = action::list_revisions(req, store.get()).await?;
+    Ok(Json(revisions))
+}
+
+#[rocket::get("/<shortcode>/revisions/<revision>")]
+pub async fn get_revision(
+    shortcode: Result<ShortCode, ClipError>,
+    revision: u32,
+    store: &State<Store>,
+    cookies: ClipCookies<'_>,
+    _api_key: ReadKey,
+) -> Result<Json<Revision>, ApiError> {
+    let shortcode = shortcode?;
+    let req = service::ask::GetRevision {
+        shortcode: shortcode.clone(),
+        password: cookies.password(),
+        revision,
+    };
+    let revision = action::get_revision(req, store.get()).await?;
+    Ok(Json(revision))
+}
+
+#[rocket::post("/<shortcode>/revisions/<revision>/restore")]
+pub async fn restore_revision(
+    shortcode: Result<ShortCode, ClipError>,
+    revision: u32,
+    store: &State<Store>,
+    quota: &State<Quota>,
+    edit_token: EditToken,
+    base_url: BaseUrl,
+    _api_key: WriteKey,
+) -> Result<Json<PublicClip>, ApiError> {
+    let shortcode = shortcode?;
+    let req = service::ask::RestoreRevision {
+        shortcode: shortcode.clone(),
+        edit_token,
+        revision,
+    };
+    let clip = action::restore_revision(req, quota, store.get()).await?;
+    Ok(Json(PublicClip::new(clip, &base_url)))
+}
+
+/// Creates an API key with the requested name, scopes and expiration.
+///
+/// Only the digest of the key is stored, so this response is the only time the key is shown.
+#[rocket::post("/key", data = "<req>")]
+pub async fn create_api_key(
+    req: Json<service::ask::NewApiKey>,
+    store: &State<Store>,
+    _api_key: AdminKey,
+) -> Result<Json<NewKeyResponse>, ApiError> {
+    let api_key = action::generate_api_key(req.into_inner(), store.get()).await?;
+    let info = action::get_api_key(&api_key, store.get()).await?;
+    Ok(Json(NewKeyResponse {
+        api_key: api_key.to_base64(),
+        info,
+    }))
+}
+
+#[rocket::get("/key/info")]
+pub async fn key_info(store: &State<Store>, api_key: ApiKey) -> Result<Json<KeyInfo>, ApiError> {
+    let info = action::get_api_key(&api_key, store.get()).await?;
+    Ok(Json(info))
 }
 
-#[rocket::get("/key")]
-pub async fn new_api_key(database: &State<AppDatabase>) -> Result<Json<&str>, ApiError> {
-    let api_key = action::generate_api_key(database.get_pool()).await?;
-    println!("Api Key: {}", api_key.to_base64());
-    Ok(Json("Api key generated. See logs for details."))
+#[rocket::get("/key/usage")]
+pub async fn key_usage(
+    store: &State<Store>,
+    quota: &State<Quota>,
+    api_key: ApiKey,
+) -> Result<Json<KeyUsage>, ApiError> {
+    let usage = action::key_usage(&api_key, store.get()).await?;
+    Ok(Json(KeyUsage {
+        usage,
+        quota: *quota.inner(),
+    }))
 }
 
 pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes!(get_clip, new_clip, update_clip, new_api_key)
+    rocket::routes!(
+        get_clip,
+        clip_stats,
+        list_clips,
+        search_clips,
+        new_clip,
+        upload_clip,
+        get_attachment,
+        update_clip,
+        delete_clip,
+        list_revisions,
+        get_revision,
+        restore_revision,
+        create_api_key,
+        key_info,
+        key_usage
+    )
 }
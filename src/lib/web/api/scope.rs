@@ -0,0 +1,188 @@
+use std::{collections::BTreeSet, fmt, marker::PhantomData, str::FromStr};
+
+use rocket::{
+    http::Status,
+    outcome::try_outcome,
+    request::{FromRequest, Outcome},
+    serde::json::Json,
+    Request,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{error::ApiError, ApiKey, KeyInfo};
+
+/// What an API key is allowed to do.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Serialize,
+    Deserialize,
+    strum::Display,
+    strum::EnumString,
+    strum::AsRefStr,
+)]
+pub enum Scope {
+    /// Read clips, their revisions and attachments.
+    #[serde(rename = "clip:read")]
+    #[strum(serialize = "clip:read")]
+    ClipRead,
+    /// Create, update, delete and restore clips.
+    #[serde(rename = "clip:write")]
+    #[strum(serialize = "clip:write")]
+    ClipWrite,
+    /// Manage API keys.
+    #[serde(rename = "key:admin")]
+    #[strum(serialize = "key:admin")]
+    KeyAdmin,
+}
+
+/// The set of scopes granted to an API key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Scopes(BTreeSet<Scope>);
+
+impl Scopes {
+    pub fn new<I: IntoIterator<Item = Scope>>(scopes: I) -> Self {
+        Self(scopes.into_iter().collect())
+    }
+
+    pub fn contains(&self, scope: Scope) -> bool {
+        self.0.contains(&scope)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Keys can read and write clips unless given other scopes.
+impl Default for Scopes {
+    fn default() -> Self {
+        Self::new([Scope::ClipRead, Scope::ClipWrite])
+    }
+}
+
+/// Formats the scopes separated by spaces, as they are stored.
+impl fmt::Display for Scopes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scopes: Vec<&str> = self.0.iter().map(Scope::as_ref).collect();
+        write!(f, "{}", scopes.join(" "))
+    }
+}
+
+/// Parses scopes separated by spaces or commas.
+impl FromStr for Scopes {
+    type Err = String;
+
+    fn from_str(scopes: &str) -> Result<Self, Self::Err> {
+        scopes
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|scope| !scope.is_empty())
+            .map(|scope| Scope::from_str(scope).map_err(|_| format!("unknown scope: {}", scope)))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// A scope a route can require of the API key used to call it.
+pub trait RequiredScope {
+    const SCOPE: Scope;
+}
+
+#[derive(Debug)]
+pub struct ClipRead;
+
+impl RequiredScope for ClipRead {
+    const SCOPE: Scope = Scope::ClipRead;
+}
+
+#[derive(Debug)]
+pub struct ClipWrite;
+
+impl RequiredScope for ClipWrite {
+    const SCOPE: Scope = Scope::ClipWrite;
+}
+
+#[derive(Debug)]
+pub struct KeyAdmin;
+
+impl RequiredScope for KeyAdmin {
+    const SCOPE: Scope = Scope::KeyAdmin;
+}
+
+/// A valid API key that was granted the scope `S`.
+///
+/// Requests with a valid key lacking the scope are rejected with `403 Forbidden`.
+#[derive(Debug)]
+pub struct Scoped<S> {
+    api_key: ApiKey,
+    scope: PhantomData<S>,
+}
+
+/// A key allowed to read clips.
+pub type ReadKey = Scoped<ClipRead>;
+/// A key allowed to write clips.
+pub type WriteKey = Scoped<ClipWrite>;
+/// A key allowed to manage API keys.
+pub type AdminKey = Scoped<KeyAdmin>;
+
+impl<S> Scoped<S> {
+    pub fn into_key(self) -> ApiKey {
+        self.api_key
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, S: RequiredScope + Send> FromRequest<'r> for Scoped<S> {
+    type Error = ApiError;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let api_key = try_outcome!(req.guard::<ApiKey>().await);
+        let granted = req
+            .local_cache(|| None::<KeyInfo>)
+            .as_ref()
+            .map(|info| info.scopes.contains(S::SCOPE))
+            .unwrap_or(false);
+        if granted {
+            Outcome::Success(Self {
+                api_key,
+                scope: PhantomData,
+            })
+        } else {
+            Outcome::Failure((
+                Status::Forbidden,
+                ApiError::Forbidden(Json(format!("API key lacks the {} scope", S::SCOPE))),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes() {
+        let scopes = Scopes::from_str("clip:write, key:admin clip:read").unwrap();
+        assert!(scopes.contains(Scope::KeyAdmin));
+        assert_eq!(scopes.to_string(), "clip:read clip:write key:admin");
+        assert_eq!(Scopes::from_str("").unwrap(), Scopes::new([]));
+        assert!(Scopes::from_str("clip:delete").is_err());
+        assert!(!Scopes::default().contains(Scope::KeyAdmin));
+    }
+
+    #[test]
+    fn test_serde() {
+        let scopes = Scopes::new([Scope::ClipRead]);
+        assert_eq!(serde_json::to_string(&scopes).unwrap(), r#"["clip:read"]"#);
+        assert_eq!(
+            serde_json::from_str::<Scopes>(r#"["key:admin"]"#).unwrap(),
+            Scopes::new([Scope::KeyAdmin])
+        );
+    }
+}
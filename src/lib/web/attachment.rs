@@ -0,0 +1,69 @@
+use std::io::Cursor;
+
+use rocket::http::{ContentType, Header};
+use rocket::response::{self, Responder, Response};
+use rocket::Request;
+
+use crate::domain::clip::AttachmentFile;
+
+/// Serves an attachment as a download under the name it was uploaded with.
+///
+/// Attachments are always sent as `attachment` with sniffing disabled, so that an
+/// uploaded HTML page or script is never rendered by the browser on this origin.
+#[derive(Debug)]
+pub struct Download(pub AttachmentFile);
+
+impl<'r> Responder<'r, 'static> for Download {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let AttachmentFile { attachment, data } = self.0;
+        let content_type = ContentType::parse_flexible(attachment.mime_type.as_str())
+            .unwrap_or(ContentType::Binary);
+        Response::build()
+            .header(content_type)
+            .header(Header::new(
+                "Content-Disposition",
+                content_disposition(attachment.filename.as_str()),
+            ))
+            .header(Header::new("X-Content-Type-Options", "nosniff"))
+            .sized_body(data.len(), Cursor::new(data))
+            .ok()
+    }
+}
+
+/// Builds a `Content-Disposition` value for `filename`, with an ASCII fallback for
+/// clients that do not support RFC 5987 encoded names.
+fn content_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| if c.is_ascii() && c != '\\' { c } else { '_' })
+        .collect();
+    let mut encoded = String::with_capacity(filename.len());
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("shot.png"),
+            "attachment; filename=\"shot.png\"; filename*=UTF-8''shot.png"
+        );
+        assert_eq!(
+            content_disposition("résumé 1.pdf"),
+            "attachment; filename=\"r_sum_ 1.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%201.pdf"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use rocket::http::{Cookie, CookieJar, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::clip::field;
+use crate::ShortCode;
+
+/// The password cookie name unless configured otherwise.
+pub const DEFAULT_PASSWORD_COOKIE: &str = "password";
+
+/// The edit token cookie name unless configured otherwise.
+pub const DEFAULT_EDIT_TOKEN_COOKIE: &str = "edit_token";
+
+/// The names of the cookies the web pages remember clip passwords and edit tokens in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CookieNames {
+    pub password: String,
+    pub edit_token: String,
+}
+
+impl Default for CookieNames {
+    fn default() -> Self {
+        Self {
+            password: DEFAULT_PASSWORD_COOKIE.to_owned(),
+            edit_token: DEFAULT_EDIT_TOKEN_COOKIE.to_owned(),
+        }
+    }
+}
+
+/// The request's cookies, read and written under the configured [`CookieNames`].
+pub struct ClipCookies<'r> {
+    jar: &'r CookieJar<'r>,
+    names: &'r CookieNames,
+}
+
+impl<'r> ClipCookies<'r> {
+    /// The clip password remembered from the last password form submitted.
+    pub fn password(&self) -> field::Password {
+        self.jar
+            .get(&self.names.password)
+            .and_then(|cookie| field::Password::new(cookie.value().to_owned()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Remembers a clip password for later requests.
+    pub fn set_password(&self, password: String) {
+        self.jar
+            .add(Cookie::new(self.names.password.clone(), password));
+    }
+
+    /// The edit token sent for the clip being requested.
+    pub fn edit_token(&self) -> field::EditToken {
+        field::EditToken::new(
+            self.jar
+                .get(&self.names.edit_token)
+                .map(|cookie| cookie.value().to_owned()),
+        )
+    }
+
+    /// Remembers the edit token of a clip, sent back only with requests for that clip.
+    pub fn set_edit_token(&self, shortcode: &ShortCode, token: String) {
+        let cookie = Cookie::build(self.names.edit_token.clone(), token)
+            .path(format!("/clip/{}", shortcode.as_str()))
+            .http_only(true)
+            .permanent()
+            .finish();
+        self.jar.add(cookie);
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClipCookies<'r> {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match req.rocket().state::<CookieNames>() {
+            Some(names) => Outcome::Success(Self {
+                jar: req.cookies(),
+                names,
+            }),
+            None => Outcome::Failure((Status::InternalServerError, ())),
+        }
+    }
+}
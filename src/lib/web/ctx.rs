@@ -28,9 +28,62 @@ impl PageContext for Home {
     }
 }
 
+/// The number of hours drawn in the view chart on the clip page.
+pub const CHART_HOURS: i64 = 24;
+
+/// The views of a clip over the last day, hour by hour, as drawn on the clip page.
+#[derive(Debug, Default, Serialize)]
+pub struct ViewChart {
+    pub total: u64,
+    pub bars: Vec<ChartBar>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChartBar {
+    /// The hour the bar covers, in UTC.
+    pub label: String,
+    pub views: u64,
+    /// The height of the bar as a percentage of the busiest hour.
+    pub height: u64,
+}
+
+impl ViewChart {
+    /// The span of view statistics the chart is drawn from.
+    pub fn span() -> crate::service::ask::ClipViews {
+        let now = chrono::Utc::now().timestamp();
+        let from = now - (CHART_HOURS - 1) * crate::domain::clip::views::BUCKET_SECONDS;
+        crate::service::ask::ClipViews {
+            from: Some(crate::Time::from_seconds(from)),
+            to: Some(crate::Time::from_seconds(now)),
+        }
+    }
+
+    pub fn new(stats: &crate::domain::clip::ViewStats) -> Self {
+        let hours = stats.hourly_totals();
+        let busiest = hours.iter().map(|(_, views)| *views).max().unwrap_or(0);
+        let bars = hours
+            .into_iter()
+            .map(|(hour, views)| ChartBar {
+                label: crate::Time::from_seconds(hour)
+                    .into_inner()
+                    .format("%H:00 UTC")
+                    .to_string(),
+                views,
+                height: (views * 100).checked_div(busiest).unwrap_or(0),
+            })
+            .collect();
+        Self {
+            total: stats.total,
+            bars,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Constructor)]
 pub struct ViewClip {
-    pub clip: crate::Clip,
+    pub clip: crate::web::PublicClip,
+    pub owner: bool,
+    pub views: ViewChart,
 }
 
 impl PageContext for ViewClip {
@@ -45,6 +98,245 @@ impl PageContext for ViewClip {
     }
 }
 
+/// A Markdown clip, rendered to sanitized HTML with its source available alongside.
+#[derive(Debug, Serialize)]
+pub struct ViewMarkdownClip {
+    pub clip: crate::web::PublicClip,
+    pub owner: bool,
+    pub views: ViewChart,
+    pub html: String,
+}
+
+impl ViewMarkdownClip {
+    pub fn new(clip: crate::web::PublicClip, owner: bool, views: ViewChart) -> Self {
+        let html = crate::web::markdown::to_html(
+            clip.content
+                .as_ref()
+                .map(|c| c.as_str())
+                .unwrap_or_default(),
+        );
+        Self {
+            clip,
+            owner,
+            views,
+            html,
+        }
+    }
+}
+
+impl PageContext for ViewMarkdownClip {
+    fn template_path(&self) -> &str {
+        "clip_markdown"
+    }
+    fn title(&self) -> &str {
+        "View Clip"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct EditClip {
+    pub clip: crate::web::PublicClip,
+    pub expires: Option<String>,
+}
+
+impl EditClip {
+    pub fn new(clip: crate::web::PublicClip) -> Self {
+        let expires = clip.expires.clone().into_inner().map(|time| {
+            time.into_inner()
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        });
+        Self { clip, expires }
+    }
+}
+
+impl PageContext for EditClip {
+    fn template_path(&self) -> &str {
+        "clip_edit"
+    }
+    fn title(&self) -> &str {
+        "Edit Clip"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiffLine {
+    pub kind: &'static str,
+    pub text: String,
+}
+
+impl DiffLine {
+    fn parse(line: &str) -> Self {
+        let kind = if line.starts_with("+++") || line.starts_with("---") {
+            "header"
+        } else if line.starts_with("@@") || line.starts_with('\\') {
+            "hunk"
+        } else if line.starts_with('+') {
+            "added"
+        } else if line.starts_with('-') {
+            "removed"
+        } else {
+            "context"
+        };
+        Self {
+            kind,
+            text: line.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClipHistory {
+    pub shortcode: crate::ShortCode,
+    pub revisions: Vec<crate::domain::clip::Revision>,
+    pub from: u32,
+    pub to: u32,
+    pub diff: Vec<DiffLine>,
+    pub owner: bool,
+}
+
+impl ClipHistory {
+    /// Builds the history page, diffing revision `from` against revision `to`.
+    ///
+    /// Returns `None` if either revision is not part of `revisions`.
+    pub fn new(
+        shortcode: crate::ShortCode,
+        revisions: Vec<crate::domain::clip::Revision>,
+        from: u32,
+        to: u32,
+        owner: bool,
+    ) -> Option<Self> {
+        let find = |number| revisions.iter().find(|rev| rev.revision == number);
+        let diff = find(from)?
+            .diff(find(to)?)
+            .lines()
+            .map(DiffLine::parse)
+            .collect();
+        Some(Self {
+            shortcode,
+            revisions,
+            from,
+            to,
+            diff,
+            owner,
+        })
+    }
+}
+
+impl PageContext for ClipHistory {
+    fn template_path(&self) -> &str {
+        "clip_history"
+    }
+    fn title(&self) -> &str {
+        "Clip History"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Recent {
+    pub clips: Vec<crate::web::PublicClip>,
+    pub sort: crate::domain::clip::ClipSort,
+    pub next_cursor: Option<String>,
+}
+
+impl Recent {
+    pub fn new(
+        page: crate::domain::clip::ClipPage,
+        sort: crate::domain::clip::ClipSort,
+        base_url: &crate::web::BaseUrl,
+    ) -> Self {
+        let list = crate::web::ClipList::new(page, base_url);
+        Self {
+            clips: list.clips,
+            sort,
+            next_cursor: list.next_cursor,
+        }
+    }
+}
+
+impl PageContext for Recent {
+    fn template_path(&self) -> &str {
+        "recent"
+    }
+    fn title(&self) -> &str {
+        "Recent Clips"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub matched: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub shortcode: crate::ShortCode,
+    pub title: crate::domain::clip::field::Title,
+    pub snippet: Vec<SnippetPart>,
+}
+
+impl From<crate::domain::clip::SearchHit> for SearchResult {
+    fn from(hit: crate::domain::clip::SearchHit) -> Self {
+        let snippet = hit
+            .snippet_parts()
+            .into_iter()
+            .map(|(matched, text)| SnippetPart {
+                text: text.to_owned(),
+                matched,
+            })
+            .collect();
+        Self {
+            shortcode: hit.shortcode,
+            title: hit.title,
+            snippet,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Search {
+    pub query: String,
+    pub results: Vec<SearchResult>,
+    pub prev_page: Option<u32>,
+    pub next_page: Option<u32>,
+}
+
+impl Search {
+    pub fn new(query: String, results: crate::domain::clip::SearchResults) -> Self {
+        let page = results.page;
+        Self {
+            query,
+            results: results.hits.into_iter().map(Into::into).collect(),
+            prev_page: (page > 1).then(|| page - 1),
+            next_page: results.has_more.then_some(page + 1),
+        }
+    }
+}
+
+impl PageContext for Search {
+    fn template_path(&self) -> &str {
+        "search"
+    }
+    fn title(&self) -> &str {
+        "Search"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
 #[derive(Debug, Serialize, Constructor)]
 pub struct PasswordRequired {
     shortcode: crate::ShortCode,
@@ -62,6 +354,22 @@ impl PageContext for PasswordRequired {
     }
 }
 
+/// Shown to clients rejected by the rate limiter.
+#[derive(Debug, Default, Serialize)]
+pub struct TooManyRequests {}
+
+impl PageContext for TooManyRequests {
+    fn template_path(&self) -> &str {
+        "too_many_requests"
+    }
+    fn title(&self) -> &str {
+        "Too Many Requests"
+    }
+    fn parent(&self) -> &str {
+        "base"
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -78,6 +386,24 @@ mod tests {
         assert_eq!(home.parent(), "base");
     }
 
+    fn clip() -> Clip {
+        Clip {
+            clip_id: field::ClipId::new(DbId::new()),
+            shortcode: field::ShortCode::from("abc123"),
+            content: field::Content::new("Hello, world!").unwrap(),
+            title: field::Title::new("My Clip".to_string()),
+            posted: field::Posted::new(Time::from_str("1997-05-01").unwrap()),
+            expires: field::Expires::new(Some(Time::from_str("1997-05-02").unwrap())),
+            password: field::PasswordHash::default(),
+            hits: field::Hits::new(0),
+            edit_token: field::EditToken::generate(),
+            max_views: field::MaxViews::default(),
+            views: field::Views::default(),
+            language: field::Language::default(),
+            format: field::Format::default(),
+        }
+    }
+
     #[test]
     fn test_view_clip_page_context() {
         let clip_id = field::ClipId::new(DbId::new());
@@ -86,7 +412,7 @@ mod tests {
         let title = field::Title::new("My Clip".to_string());
         let posted = field::Posted::new(Time::from_str("1997-05-01").unwrap());
         let expires = field::Expires::new(Some(Time::from_seconds(3600)));
-        let password = field::Password::new("password123".to_string()).unwrap();
+        let password = field::PasswordHash::new("hash".to_string());
         let hits = field::Hits::new(0);
 
         let clip = Clip {
@@ -98,14 +424,154 @@ mod tests {
             expires,
             password,
             hits,
+            edit_token: field::EditToken::default(),
+            max_views: field::MaxViews::default(),
+            views: field::Views::default(),
+            language: field::Language::default(),
+            format: field::Format::default(),
         };
 
-        let view_clip = ViewClip::new(clip);
+        let base_url = crate::web::BaseUrl::new("http://localhost:8000");
+        let view_clip = ViewClip::new(
+            crate::web::PublicClip::new(clip, &base_url),
+            false,
+            ViewChart::default(),
+        );
         assert_eq!(view_clip.template_path(), "clip");
         assert_eq!(view_clip.title(), "View Clip");
         assert_eq!(view_clip.parent(), "base");
     }
 
+    #[test]
+    fn test_view_markdown_clip_page_context() {
+        let mut clip = clip();
+        clip.content = field::Content::new("# Runbook\n\n<script>x</script>").unwrap();
+        clip.format = field::Format::Markdown;
+        let base_url = crate::web::BaseUrl::new("http://localhost:8000");
+        let view_clip = ViewMarkdownClip::new(
+            crate::web::PublicClip::new(clip, &base_url),
+            true,
+            ViewChart::default(),
+        );
+        assert_eq!(view_clip.template_path(), "clip_markdown");
+        assert_eq!(view_clip.title(), "View Clip");
+        assert_eq!(view_clip.parent(), "base");
+        assert_eq!(view_clip.html, "<h1>Runbook</h1>\n");
+    }
+
+    #[test]
+    fn test_view_chart() {
+        use crate::domain::clip::{views, ViewBucket, ViewStats};
+
+        let shortcode = field::ShortCode::from("chart");
+        let mut stats = ViewStats::new(
+            shortcode.clone(),
+            Time::from_seconds(0),
+            Time::from_seconds(7200),
+        );
+        let quiet = ViewStats::new(shortcode, Time::from_seconds(0), Time::from_seconds(0));
+        assert_eq!(ViewChart::new(&quiet).bars[0].height, 0);
+
+        stats.add(&ViewBucket {
+            hour: 3600,
+            source: views::ViewSource::Web,
+            agent: views::Agent::Browser,
+            referrer: None,
+            views: 4,
+        });
+        let chart = ViewChart::new(&stats);
+        assert_eq!(chart.total, 4);
+        let bars: Vec<_> = chart
+            .bars
+            .iter()
+            .map(|bar| (bar.label.as_str(), bar.views, bar.height))
+            .collect();
+        assert_eq!(
+            bars,
+            vec![
+                ("00:00 UTC", 0, 0),
+                ("01:00 UTC", 4, 100),
+                ("02:00 UTC", 0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edit_clip_page_context() {
+        let base_url = crate::web::BaseUrl::new("http://localhost:8000");
+        let edit_clip = EditClip::new(crate::web::PublicClip::new(clip(), &base_url));
+        assert_eq!(edit_clip.template_path(), "clip_edit");
+        assert_eq!(edit_clip.title(), "Edit Clip");
+        assert_eq!(edit_clip.parent(), "base");
+        assert_eq!(edit_clip.expires, Some("1997-05-02T00:00:00Z".to_string()));
+    }
+
+    #[test]
+    fn test_recent_page_context() {
+        let base_url = crate::web::BaseUrl::new("http://localhost:8000");
+        let page = crate::domain::clip::ClipPage {
+            clips: vec![clip()],
+            next: None,
+        };
+        let recent = Recent::new(page, crate::domain::clip::ClipSort::Hits, &base_url);
+        assert_eq!(recent.template_path(), "recent");
+        assert_eq!(recent.title(), "Recent Clips");
+        assert_eq!(recent.parent(), "base");
+        assert_eq!(recent.clips.len(), 1);
+        assert!(recent.clips[0].content.is_none());
+        assert_eq!(recent.next_cursor, None);
+    }
+
+    #[test]
+    fn test_search_page_context() {
+        let hit = crate::domain::clip::SearchHit {
+            shortcode: field::ShortCode::from("abc123"),
+            title: field::Title::default(),
+            snippet: "an \u{2}error\u{3} log".to_owned(),
+            rank: -1.0,
+        };
+        let results = crate::domain::clip::SearchResults {
+            hits: vec![hit],
+            page: 2,
+            has_more: false,
+        };
+        let search = Search::new("error".to_owned(), results);
+        assert_eq!(search.template_path(), "search");
+        assert_eq!(search.title(), "Search");
+        assert_eq!(search.parent(), "base");
+        assert_eq!(search.prev_page, Some(1));
+        assert_eq!(search.next_page, None);
+        let snippet = &search.results[0].snippet;
+        assert_eq!(snippet.len(), 3);
+        assert!(snippet[1].matched);
+        assert_eq!(snippet[1].text, "error");
+    }
+
+    #[test]
+    fn test_clip_history_page_context() {
+        let revision = |revision, content| crate::domain::clip::Revision {
+            revision,
+            content: field::Content::new(content).unwrap(),
+            title: field::Title::default(),
+            posted: field::Posted::new(Time::from_seconds(0)),
+        };
+        let revisions = vec![revision(1, "a\nb\n"), revision(2, "a\nc\n")];
+        let shortcode = crate::ShortCode::from("abc123");
+
+        let history = ClipHistory::new(shortcode.clone(), revisions, 1, 2, false).unwrap();
+        assert_eq!(history.template_path(), "clip_history");
+        assert_eq!(history.title(), "Clip History");
+        assert_eq!(history.parent(), "base");
+        let kinds: Vec<_> = history.diff.iter().map(|line| line.kind).collect();
+        assert_eq!(
+            kinds,
+            vec!["header", "header", "hunk", "context", "removed", "added"]
+        );
+
+        let revisions = vec![revision(1, "a\n")];
+        assert!(ClipHistory::new(shortcode, revisions, 1, 2, false).is_none());
+    }
+
     #[test]
     fn test_password_required_page_context() {
         let shortcode = crate::ShortCode::from("abcd1234ef");
@@ -1,9 +1,103 @@
 use crate::domain::clip::field;
-use rocket::form::FromForm;
+use crate::service::ask;
+use crate::ClipError;
+use rocket::data::{Limits, ToByteUnit};
+use rocket::form::{self, DataField, FromForm, FromFormField};
 use serde::Serialize;
 
 #[derive(Debug, Serialize, FromForm)]
 pub struct NewClip {
+    /// Optional when attachments are uploaded, see [`NewClip::into_request`].
+    pub content: Option<field::Content>,
+    pub title: field::Title,
+    pub expires: field::Expires,
+    pub password: field::Password,
+    pub max_views: field::MaxViews,
+    pub language: field::Language,
+    pub format: field::Format,
+    pub shortcode: field::VanityShortCode,
+    #[serde(skip)]
+    pub attachments: Vec<Attachment>,
+}
+
+impl NewClip {
+    /// Turns the form into a request to create the clip.
+    ///
+    /// A clip uploaded with attachments but no content lists the names of its
+    /// attachments as its content instead.
+    pub fn into_request(self) -> Result<ask::NewClip, ClipError> {
+        let attachments: Vec<ask::NewAttachment> =
+            self.attachments.into_iter().filter_map(|a| a.0).collect();
+        let content = match self.content {
+            Some(content) => content,
+            None => {
+                let names: Vec<&str> = attachments.iter().map(|a| a.filename.as_str()).collect();
+                field::Content::new(&names.join("\n"))?
+            }
+        };
+        Ok(ask::NewClip {
+            content,
+            title: self.title,
+            expires: self.expires,
+            password: self.password,
+            max_views: self.max_views,
+            language: self.language,
+            format: self.format,
+            shortcode: self.shortcode,
+            attachments,
+            api_key: None,
+        })
+    }
+}
+
+/// A file uploaded with a multipart form; empty when the file input was left blank.
+#[derive(Debug)]
+pub struct Attachment(Option<ask::NewAttachment>);
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for Attachment {
+    async fn from_data(field: DataField<'r, '_>) -> form::Result<'r, Self> {
+        let limit = field.request.limits().get("file").unwrap_or(Limits::FILE);
+        let data = field.data.open(limit).into_bytes().await?;
+        if !data.is_complete() {
+            return Err(form::Error::validation(format!(
+                "attachments must be smaller than {}",
+                limit
+            ))
+            .into());
+        }
+        let data = data.into_inner();
+        let name = field
+            .file_name
+            .map(|name| name.dangerous_unsafe_unsanitized_raw().as_str())
+            .unwrap_or_default();
+        if name.is_empty() && data.is_empty() {
+            return Ok(Self(None));
+        }
+        let filename = if name.is_empty() { "attachment" } else { name };
+        let to_validation = |e: ClipError| form::Error::validation(format!("{}", e));
+        Ok(Self(Some(ask::NewAttachment {
+            filename: field::FileName::new(filename).map_err(to_validation)?,
+            mime_type: field::MimeType::new(&field.content_type.to_string())
+                .map_err(to_validation)?,
+            data,
+        })))
+    }
+}
+
+/// The largest attachment accepted by default, when no `file` limit is configured.
+pub const DEFAULT_ATTACHMENT_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// Raises the form limits so that attachments up to [`DEFAULT_ATTACHMENT_LIMIT`] can be uploaded.
+pub fn limits() -> Limits {
+    let limit = DEFAULT_ATTACHMENT_LIMIT.bytes();
+    Limits::default()
+        .limit("file", limit)
+        .limit("data-form", limit * 2)
+}
+
+#[derive(Debug, Serialize, FromForm)]
+pub struct EditClip {
     pub content: field::Content,
     pub title: field::Title,
     pub expires: field::Expires,
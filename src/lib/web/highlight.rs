@@ -0,0 +1,115 @@
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError,
+};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::html::{styled_line_to_highlighted_html, IncludeBackground};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::LinesWithEndings;
+
+/// Renders clip content as syntax-highlighted HTML with line numbers.
+///
+/// Registered with the templates as the `highlight` helper:
+/// `{{{highlight clip.content clip.language}}}`. The output is safe to embed
+/// unescaped, as all content is escaped while highlighting.
+pub struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+impl Highlighter {
+    pub fn new() -> Self {
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes
+            .themes
+            .remove("InspiredGitHub")
+            .expect("default themes should include InspiredGitHub");
+        Self {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme,
+        }
+    }
+
+    fn syntax(&self, language: Option<&str>) -> &SyntaxReference {
+        language
+            .and_then(|language| self.syntaxes.find_syntax_by_token(language))
+            .unwrap_or_else(|| self.syntaxes.find_syntax_plain_text())
+    }
+
+    /// Highlights `content` as `language`, falling back to plain text for unknown languages.
+    pub fn to_html(&self, content: &str, language: Option<&str>) -> Result<String, syntect::Error> {
+        let mut lines = HighlightLines::new(self.syntax(language), &self.theme);
+        let mut html = String::from("<table class=\"highlight\"><tbody>");
+        for (number, line) in LinesWithEndings::from(content).enumerate() {
+            let regions: Vec<_> = lines
+                .highlight_line(line, &self.syntaxes)?
+                .into_iter()
+                .map(|(style, text)| (style, text.trim_end_matches(['\r', '\n'])))
+                .collect();
+            let code = styled_line_to_highlighted_html(&regions, IncludeBackground::No)?;
+            html.push_str(&format!(
+                "<tr><td class=\"line-number\" data-line=\"{}\"></td><td class=\"line\">{}</td></tr>",
+                number + 1,
+                code
+            ));
+        }
+        html.push_str("</tbody></table>");
+        Ok(html)
+    }
+}
+
+impl Default for Highlighter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HelperDef for Highlighter {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'reg, 'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let content = h
+            .param(0)
+            .and_then(|param| param.value().as_str())
+            .ok_or_else(|| RenderError::new("highlight expects the content to highlight"))?;
+        let language = h.param(1).and_then(|param| param.value().as_str());
+        let html = self
+            .to_html(content, language)
+            .map_err(|e| RenderError::new(format!("failed to highlight: {}", e)))?;
+        out.write(&html)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        let highlighter = Highlighter::new();
+        let html = highlighter
+            .to_html("fn main() {}\nlet x = \"<b>\";\n", Some("rust"))
+            .unwrap();
+        assert_eq!(html.matches("<tr>").count(), 2);
+        assert!(html.contains("data-line=\"2\""));
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("style="));
+        assert!(!html.contains('\n'));
+    }
+
+    #[test]
+    fn test_unknown_language() {
+        let highlighter = Highlighter::new();
+        let html = highlighter.to_html("a <b>", Some("nope")).unwrap();
+        assert!(html.contains("a &lt;b&gt;"));
+        let html = highlighter.to_html("a", None).unwrap();
+        assert_eq!(html.matches("<tr>").count(), 1);
+    }
+}
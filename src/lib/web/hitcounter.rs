@@ -1,101 +1,331 @@
 use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use crossbeam_channel::{unbounded, Sender, TryRecvError};
 use parking_lot::Mutex;
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use tokio::sync::{mpsc, oneshot};
 
-use crate::{data::DatabasePool, service, ServiceError, ShortCode};
+use crate::domain::clip::views::{self, Agent, ViewSource};
+use crate::domain::clip::{field, View, ViewBucket, ViewStats};
+use crate::{data::Store, service, Clip, ShortCode};
 
-type HitStore = Arc<Mutex<HashMap<ShortCode, u32>>>;
+/// The number of flush requests that can wait for the background task.
+const QUEUE_SIZE: usize = 16;
 
-#[derive(Debug, thiserror::Error)]
-enum HitCountError {
-    #[error("service error: {0}")]
-    Service(#[from] ServiceError),
-    #[error("communication error: {0}")]
-    Channel(#[from] crossbeam_channel::SendError<HitCountMsg>),
+/// The most distinct views kept waiting for a flush. Filling up requests an early flush,
+/// and views that would add to a full map are dropped until it is written.
+const MAX_PENDING: usize = 10_000;
+
+/// Views counted in memory: those waiting for the next flush and those being written.
+#[derive(Debug, Default)]
+struct Counts {
+    pending: HashMap<View, u32>,
+    in_flight: HashMap<View, u32>,
 }
 
-enum HitCountMsg {
-    Commit,
-    Hit(ShortCode, u32),
+impl Counts {
+    /// Adds a view, returning `false` if it was dropped because the pending views are full.
+    fn add(&mut self, view: View) -> bool {
+        let full = self.is_full();
+        match self.pending.get_mut(&view) {
+            Some(count) => *count += 1,
+            None if full => return false,
+            None => {
+                self.pending.insert(view, 1);
+            }
+        }
+        true
+    }
+
+    fn is_full(&self) -> bool {
+        self.pending.len() >= MAX_PENDING
+    }
+
+    /// Moves the pending views in flight and returns them for writing.
+    fn start_flush(&mut self) -> Vec<(View, u32)> {
+        self.in_flight = std::mem::take(&mut self.pending);
+        self.in_flight
+            .iter()
+            .map(|(view, count)| (view.clone(), *count))
+            .collect()
+    }
+
+    /// Forgets the views in flight once they are written, or puts them back with the
+    /// pending ones if the write failed.
+    fn finish_flush(&mut self, written: bool) {
+        let in_flight = std::mem::take(&mut self.in_flight);
+        if !written {
+            for (view, count) in in_flight {
+                *self.pending.entry(view).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// Every view not yet written to the store, with its count.
+    fn unwritten(&self) -> impl Iterator<Item = (&View, &u32)> {
+        self.pending.iter().chain(self.in_flight.iter())
+    }
 }
 
+type Pending = Arc<Mutex<Counts>>;
+
+/// Counts clip views in memory and writes them to the store in batches.
+///
+/// Views are summed as they happen, and a background task records them all with one
+/// batched update every flush interval, on request over a bounded channel, when the
+/// pending views fill up, and once more when Rocket shuts down. Each view adds to the
+/// hit count of its clip and to its hourly view statistics. Counts that are not written
+/// yet, including those being written, are merged into clips as they are read.
 pub struct HitCounter {
-    tx: Sender<HitCountMsg>,
+    tx: mpsc::Sender<oneshot::Sender<()>>,
+    pending: Pending,
 }
 
 impl HitCounter {
-    async fn commit_hits(hits: HitStore, pool: DatabasePool) -> Result<(), HitCountError> {
-        let hits = Arc::clone(&hits);
-        let hits: Vec<(ShortCode, u32)> = {
-            let mut hits = hits.lock();
-            let hits_vec = hits.iter().map(|(k, v)| (k.clone(), *v)).collect();
-            hits.clear();
-            hits_vec
-        };
-
-        let transaction = service::action::begin_transaction(&pool).await?;
-        for (shortcode, hits) in hits {
-            if let Err(e) = service::action::increase_hit_count(&shortcode, hits, &pool).await {
-                eprintln!("error increasing hit count: {}", e);
-            }
+    /// Writes the pending counts, putting them back for the next flush if the write fails.
+    ///
+    /// The counts stay visible in flight until the write finishes, and views counted
+    /// meanwhile wait for the next flush instead of being written twice.
+    async fn flush(pending: &Pending, store: &Store) {
+        let views = pending.lock().start_flush();
+        if views.is_empty() {
+            return;
         }
-        Ok(service::action::end_transaction(transaction).await?)
-    }
-
-    async fn process_msg(
-        msg: HitCountMsg,
-        hits: HitStore,
-        pool: DatabasePool,
-    ) -> Result<(), HitCountError> {
-        match msg {
-            HitCountMsg::Commit => Self::commit_hits(hits, pool).await?,
-            HitCountMsg::Hit(shortcode, count) => {
-                let mut hitcount = hits.lock();
-                let hitcount = hitcount.entry(shortcode).or_insert(0);
-                *hitcount += count;
-            }
+        let result = service::action::record_views(&views, store.get()).await;
+        if let Err(e) = &result {
+            eprintln!("error recording views: {}", e);
         }
-        Ok(())
+        pending.lock().finish_flush(result.is_ok());
     }
+    pub fn new(store: &Store, flush_interval: Duration) -> Self {
+        let (tx, mut rx) = mpsc::channel::<oneshot::Sender<()>>(QUEUE_SIZE);
+        let pending = Pending::default();
 
-    pub fn new(pool: &DatabasePool) -> Self {
-        let (tx, rx) = unbounded();
-        let tx_clone = tx.clone();
-
-        let pool_clone = pool.clone();
-        let _ = tokio::spawn(async move {
-            println!("HitCounter thread spawned");
-            let store: HitStore = Arc::new(Mutex::new(HashMap::new()));
-
+        let store = store.clone();
+        let counts = Arc::clone(&pending);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(flush_interval);
             loop {
-                match rx.try_recv() {
-                    Ok(msg) => {
-                        if let Err(e) =
-                            Self::process_msg(msg, store.clone(), pool_clone.clone()).await
-                        {
-                            eprintln!("message processing error: {}", e);
+                tokio::select! {
+                    msg = rx.recv() => match msg {
+                        Some(done) => {
+                            Self::flush(&counts, &store).await;
+                            let _ = done.send(());
                         }
-                    }
-                    Err(e) => match e {
-                        TryRecvError::Empty => {
-                            std::thread::sleep(Duration::from_secs(5));
-                            if let Err(e) = tx_clone.send(HitCountMsg::Commit) {
-                                eprintln!("error sending commit msg to hits channel: {}", e);
-                            }
+                        None => {
+                            Self::flush(&counts, &store).await;
+                            break;
                         }
-                        _ => break,
                     },
+                    _ = interval.tick() => Self::flush(&counts, &store).await,
                 }
             }
         });
 
-        Self { tx }
+        Self { tx, pending }
+    }
+
+    /// Counts a view of a clip, so that it is merged into the clip right away.
+    ///
+    /// Asks for an early flush once the pending views fill up.
+    pub fn hit(&self, view: View) {
+        let mut counts = self.pending.lock();
+        if !counts.add(view) {
+            eprintln!("dropped a clip view: too many views are waiting to be recorded");
+        }
+        if counts.is_full() {
+            let (done, _) = oneshot::channel();
+            let _ = self.tx.try_send(done);
+        }
+    }
+
+    /// Writes every view counted so far to the store, waiting for the write to finish.
+    pub async fn commit(&self) {
+        let (done, written) = oneshot::channel();
+        if self.tx.send(done).await.is_ok() {
+            let _ = written.await;
+        }
+    }
+
+    /// The views of a clip counted but not yet written to the store.
+    pub fn pending(&self, shortcode: &ShortCode) -> u32 {
+        self.pending
+            .lock()
+            .unwritten()
+            .filter(|(view, _)| &view.shortcode == shortcode)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    /// Adds the hits not yet written to the store to the hit count of a clip.
+    pub fn merge(&self, clip: &mut Clip) {
+        let pending = self.pending(&clip.shortcode);
+        if pending > 0 {
+            let hits = clip.hits.clone().into_inner() + u64::from(pending);
+            clip.hits = field::Hits::new(hits);
+        }
+    }
+
+    /// Adds the views not yet written to the store to the view statistics of a clip.
+    pub fn merge_views(&self, stats: &mut ViewStats) {
+        let pending: Vec<ViewBucket> = self
+            .pending
+            .lock()
+            .unwritten()
+            .filter(|(view, _)| view.shortcode == stats.shortcode)
+            .map(|(view, count)| ViewBucket::pending(view, *count))
+            .collect();
+        for bucket in &pending {
+            stats.add(bucket);
+        }
+    }
+}
+
+/// Who is viewing a clip: the kind of client and the host of the page linking to it.
+pub struct Visitor {
+    agent: Agent,
+    referrer: Option<String>,
+}
+
+impl Visitor {
+    /// A view of a clip by this visitor, happening now.
+    pub fn view(&self, shortcode: ShortCode, source: ViewSource) -> View {
+        View::new(shortcode, source, self.agent, self.referrer.clone())
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Visitor {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let headers = req.headers();
+        Outcome::Success(Self {
+            agent: Agent::classify(headers.get_one("User-Agent")),
+            referrer: headers.get_one("Referer").and_then(views::referrer_host),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::MemoryStore;
+    use crate::domain::quota::Quota;
+    use crate::service::{action, ask};
+
+    async fn store_with_clip(shortcode: &str) -> Store {
+        let store = Store::new(MemoryStore::new());
+        let req: ask::NewClip = serde_json::from_value(serde_json::json!({
+            "content": "counted",
+            "title": null,
+            "expires": null,
+            "password": null,
+            "shortcode": shortcode,
+        }))
+        .unwrap();
+        action::new_clip(req, &Quota::default(), store.get())
+            .await
+            .unwrap();
+        store
+    }
+
+    async fn get_clip(shortcode: &str, store: &Store) -> Clip {
+        let req = ask::GetClip {
+            shortcode: ShortCode::from(shortcode),
+            password: field::Password::default(),
+        };
+        action::get_clip(req, store.get()).await.unwrap()
+    }
+
+    fn view(shortcode: &str, source: ViewSource) -> View {
+        View::new(ShortCode::from(shortcode), source, Agent::Browser, None)
+    }
+
+    #[tokio::test]
+    async fn test_commit_writes_views() {
+        let store = store_with_clip("counted").await;
+        let hit_counter = HitCounter::new(&store, Duration::from_secs(3600));
+        hit_counter.hit(view("counted", ViewSource::Web));
+        hit_counter.hit(view("counted", ViewSource::Web));
+        hit_counter.hit(view("counted", ViewSource::Raw));
+        hit_counter.commit().await;
+
+        assert_eq!(hit_counter.pending(&ShortCode::from("counted")), 0);
+        let clip = get_clip("counted", &store).await;
+        assert_eq!(clip.hits.clone().into_inner(), 3);
+        let stats = action::clip_views(&clip, ask::ClipViews::default(), store.get())
+            .await
+            .unwrap();
+        assert_eq!(stats.total, 3);
+        assert_eq!((stats.sources.web, stats.sources.raw), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn test_merge_pending_views() {
+        let store = store_with_clip("pending").await;
+        let hit_counter = HitCounter::new(&store, Duration::from_secs(3600));
+        let shortcode = ShortCode::from("pending");
+        for _ in 0..4 {
+            hit_counter.hit(view("pending", ViewSource::Api));
+        }
+        hit_counter.hit(view("other", ViewSource::Api));
+
+        let mut clip = get_clip("pending", &store).await;
+        assert_eq!(clip.hits.clone().into_inner(), 0);
+        hit_counter.merge(&mut clip);
+        assert_eq!(clip.hits.clone().into_inner(), 4);
+        assert_eq!(hit_counter.pending(&shortcode), 4);
+
+        let mut stats = action::clip_views(&clip, ask::ClipViews::default(), store.get())
+            .await
+            .unwrap();
+        assert_eq!(stats.total, 0);
+        hit_counter.merge_views(&mut stats);
+        assert_eq!((stats.total, stats.sources.api), (4, 4));
+    }
+
+    #[tokio::test]
+    async fn test_hit_is_merged_at_once() {
+        let store = store_with_clip("served").await;
+        let hit_counter = HitCounter::new(&store, Duration::from_secs(3600));
+        hit_counter.hit(view("served", ViewSource::Web));
+
+        let mut clip = get_clip("served", &store).await;
+        hit_counter.merge(&mut clip);
+        assert_eq!(clip.hits.clone().into_inner(), 1);
+
+        hit_counter.commit().await;
+        let mut clip = get_clip("served", &store).await;
+        hit_counter.merge(&mut clip);
+        assert_eq!(clip.hits.clone().into_inner(), 1);
+    }
+
+    #[test]
+    fn test_in_flight_views_stay_pending() {
+        let mut counts = Counts::default();
+        counts.add(view("flushed", ViewSource::Web));
+        let views = counts.start_flush();
+        assert_eq!(views.len(), 1);
+        counts.add(view("flushed", ViewSource::Web));
+        assert_eq!(counts.unwritten().map(|(_, count)| count).sum::<u32>(), 2);
+
+        counts.finish_flush(false);
+        assert_eq!(counts.pending.values().sum::<u32>(), 2);
+        counts.start_flush();
+        counts.finish_flush(true);
+        assert_eq!(counts.unwritten().count(), 0);
     }
 
-    pub fn hit(&self, shortcode: ShortCode, count: u32) {
-        if let Err(e) = self.tx.send(HitCountMsg::Hit(shortcode, count)) {
-            eprintln!("hit count error: {}", e);
+    #[test]
+    fn test_pending_views_are_bounded() {
+        let mut counts = Counts::default();
+        for n in 0..MAX_PENDING {
+            assert!(counts.add(view(&n.to_string(), ViewSource::Web)));
         }
+        assert!(counts.is_full());
+        assert!(!counts.add(view("dropped", ViewSource::Web)));
+        assert!(counts.add(view("0", ViewSource::Web)));
+        assert_eq!(counts.pending.len(), MAX_PENDING);
     }
 }
@@ -1,6 +1,9 @@
+use rocket::response::content::RawHtml;
 use rocket::Request;
 use rocket::{catch, catchers, Catcher};
 
+use crate::web::{ctx, ratelimit::TooManyRequests, renderer::Renderer};
+
 /// Catch unhandled errors.
 #[catch(default)]
 fn default(req: &Request) -> &'static str {
@@ -21,7 +24,17 @@ fn not_found() -> &'static str {
     "404"
 }
 
+/// Catch requests rejected by the rate limiter.
+#[catch(429)]
+fn too_many_requests(req: &Request) -> TooManyRequests<RawHtml<String>> {
+    let page = match req.rocket().state::<Renderer>() {
+        Some(renderer) => renderer.render(ctx::TooManyRequests::default(), &[]),
+        None => "too many requests".to_owned(),
+    };
+    TooManyRequests(RawHtml(page))
+}
+
 /// The [`catchers`](rocket::Catcher) which can be registered by [`rocket`].
 pub fn catchers() -> Vec<Catcher> {
-    catchers![not_found, default, internal_error]
+    catchers![not_found, default, internal_error, too_many_requests]
 }
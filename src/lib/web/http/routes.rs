@@ -1,19 +1,75 @@
+use std::str::FromStr;
+
 use crate::{
-    data::AppDatabase,
-    domain::clip::field,
+    data::Store,
+    domain::{
+        clip::{field, views::ViewSource, ClipSort, ListCursor},
+        quota::Quota,
+    },
     service::{action, ask},
-    web::{ctx, form, hitcounter::HitCounter, renderer::Renderer, PageError, PASSWORD_COOKIE},
-    ServiceError, ShortCode,
+    web::{
+        attachment::Download,
+        ctx, form,
+        hitcounter::{HitCounter, Visitor},
+        ratelimit::WebLimit,
+        renderer::Renderer,
+        BaseUrl, ClipCookies, PageError, PublicClip,
+    },
+    Clip, ServiceError, ShortCode,
 };
 
 use rocket::{
     form::{Contextual, Form},
     get,
-    http::{Cookie, CookieJar, Status},
+    http::Status,
     response::{content::RawHtml, status, Redirect},
     uri, State,
 };
 
+/// The number of clips on each page of the recent clips listing.
+const RECENT_PAGE_SIZE: u32 = 25;
+
+fn form_errors<'a>(context: &'a rocket::form::Context<'_>) -> Vec<&'a str> {
+    context
+        .errors()
+        .map(|err| {
+            use rocket::form::error::ErrorKind;
+            if let ErrorKind::Validation(msg) = &err.kind {
+                msg.as_ref()
+            } else {
+                eprintln!("unhandled error: {}", err);
+                "An error occurred, please try again"
+            }
+        })
+        .collect()
+}
+
+/// Renders the page for viewing `clip`, as Markdown or as source depending on its format,
+/// with a chart of its views over the last day.
+async fn render_clip(
+    renderer: &Renderer<'_>,
+    store: &Store,
+    hit_counter: &HitCounter,
+    clip: Clip,
+    owner: bool,
+    base_url: &BaseUrl,
+) -> Result<String, PageError> {
+    let attachments = action::list_attachments(&clip, store.get())
+        .await
+        .map_err(|_| PageError::Internal("Server Error".to_owned()))?;
+    let mut views = action::clip_views(&clip, ctx::ViewChart::span(), store.get())
+        .await
+        .map_err(|_| PageError::Internal("Server Error".to_owned()))?;
+    hit_counter.merge_views(&mut views);
+    let views = ctx::ViewChart::new(&views);
+    let clip = PublicClip::new(clip, base_url).with_attachments(attachments, base_url);
+    Ok(if clip.format.is_markdown() {
+        renderer.render(ctx::ViewMarkdownClip::new(clip, owner, views), &[])
+    } else {
+        renderer.render(ctx::ViewClip::new(clip, owner, views), &[])
+    })
+}
+
 #[get("/")]
 fn home(renderer: &State<Renderer<'_>>) -> RawHtml<String> {
     let context = ctx::Home::default();
@@ -22,18 +78,22 @@ fn home(renderer: &State<Renderer<'_>>) -> RawHtml<String> {
 
 #[get("/clip/<shortcode>")]
 pub async fn get_clip(
+    cookies: ClipCookies<'_>,
     shortcode: ShortCode,
-    database: &State<AppDatabase>,
+    store: &State<Store>,
     hit_counter: &State<HitCounter>,
+    visitor: Visitor,
+    base_url: BaseUrl,
     renderer: &State<Renderer<'_>>,
 ) -> Result<status::Custom<RawHtml<String>>, PageError> {
-    match action::get_clip(shortcode.clone().into(), database.get_pool()).await {
-        Ok(clip) => {
-            hit_counter.hit(shortcode.clone(), 1);
-            let context = ctx::ViewClip::new(clip);
+    match action::get_clip(shortcode.clone().into(), store.get()).await {
+        Ok(mut clip) => {
+            hit_counter.hit(visitor.view(shortcode.clone(), ViewSource::Web));
+            hit_counter.merge(&mut clip);
+            let owner = clip.edit_token.grants(&cookies.edit_token());
             Ok(status::Custom(
                 Status::Ok,
-                RawHtml(renderer.render(context, &[])),
+                RawHtml(render_clip(renderer, store, hit_counter, clip, owner, &base_url).await?),
             ))
         }
         Err(e) => match e {
@@ -50,22 +110,51 @@ pub async fn get_clip(
     }
 }
 
+/// Renders the home page again with the submitted values and what was wrong with them.
+fn rejected_clip(
+    renderer: &Renderer<'_>,
+    context: &rocket::form::Context<'_>,
+    status: Status,
+    errors: &[&str],
+) -> (Status, RawHtml<String>) {
+    let page = renderer.render_with_data(ctx::Home::default(), ("clip", context), errors);
+    (status, RawHtml(page))
+}
+
 #[rocket::post("/", data = "<form>")]
 pub async fn new_clip(
+    cookies: ClipCookies<'_>,
     form: Form<Contextual<'_, form::NewClip>>,
-    database: &State<AppDatabase>,
+    store: &State<Store>,
+    quota: &State<Quota>,
     renderer: &State<Renderer<'_>>,
+    _limit: WebLimit,
 ) -> Result<Redirect, (Status, RawHtml<String>)> {
     let form = form.into_inner();
     if let Some(value) = form.value {
-        let req = ask::NewClip {
-            content: value.content,
-            title: value.title,
-            expires: value.expires,
-            password: value.password,
+        let result = match value.into_request() {
+            Ok(req) => action::new_clip(req, quota, store.get()).await,
+            Err(e) => Err(e.into()),
         };
-        match action::new_clip(req, database.get_pool()).await {
-            Ok(clip) => Ok(Redirect::to(uri!(get_clip(shortcode = clip.shortcode)))),
+        match result {
+            Ok(clip) => {
+                if let Some(token) = clip.edit_token.into_inner() {
+                    cookies.set_edit_token(&clip.shortcode, token);
+                }
+                Ok(Redirect::to(uri!(get_clip(shortcode = clip.shortcode))))
+            }
+            Err(ServiceError::Clip(e)) => Err(rejected_clip(
+                renderer,
+                &form.context,
+                Status::BadRequest,
+                &[e.to_string().as_str()],
+            )),
+            Err(ServiceError::Conflict(msg)) => Err(rejected_clip(
+                renderer,
+                &form.context,
+                Status::Conflict,
+                &[msg.as_str()],
+            )),
             Err(e) => {
                 eprintln!("internal error: {}", e);
                 Err((
@@ -78,53 +167,43 @@ pub async fn new_clip(
             }
         }
     } else {
-        let errors = form
-            .context
-            .errors()
-            .map(|err| {
-                use rocket::form::error::ErrorKind;
-                if let ErrorKind::Validation(msg) = &err.kind {
-                    msg.as_ref()
-                } else {
-                    eprintln!("unhandled error: {}", err);
-                    "An error occurred, please try again"
-                }
-            })
-            .collect::<Vec<_>>();
-        Err((
+        let errors = form_errors(&form.context);
+        Err(rejected_clip(
+            renderer,
+            &form.context,
             Status::BadRequest,
-            RawHtml(renderer.render_with_data(
-                ctx::Home::default(),
-                ("clip", &form.context),
-                &errors,
-            )),
+            &errors,
         ))
     }
 }
 
 #[rocket::post("/clip/<shortcode>", data = "<form>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn submit_clip_password(
-    cookies: &CookieJar<'_>,
+    cookies: ClipCookies<'_>,
     form: Form<Contextual<'_, form::GetPasswordProtectedClip>>,
     shortcode: ShortCode,
     hit_counter: &State<HitCounter>,
-    database: &State<AppDatabase>,
+    visitor: Visitor,
+    store: &State<Store>,
+    base_url: BaseUrl,
     renderer: &State<Renderer<'_>>,
+    _limit: WebLimit,
 ) -> Result<RawHtml<String>, PageError> {
     if let Some(form) = &form.value {
         let req = ask::GetClip {
             shortcode: shortcode.clone(),
             password: form.password.clone(),
         };
-        match action::get_clip(req, database.get_pool()).await {
-            Ok(clip) => {
-                hit_counter.hit(shortcode.clone(), 1);
-                let context = ctx::ViewClip::new(clip);
-                cookies.add(Cookie::new(
-                    PASSWORD_COOKIE,
-                    form.password.clone().into_inner().unwrap_or_default(),
-                ));
-                Ok(RawHtml(renderer.render(context, &[])))
+        match action::get_clip(req, store.get()).await {
+            Ok(mut clip) => {
+                hit_counter.hit(visitor.view(shortcode.clone(), ViewSource::Web));
+                hit_counter.merge(&mut clip);
+                let owner = clip.edit_token.grants(&cookies.edit_token());
+                cookies.set_password(form.password.clone().into_inner().unwrap_or_default());
+                Ok(RawHtml(
+                    render_clip(renderer, store, hit_counter, clip, owner, &base_url).await?,
+                ))
             }
             Err(e) => match e {
                 ServiceError::PermissionError(e) => {
@@ -144,25 +223,276 @@ pub async fn submit_clip_password(
     }
 }
 
+#[rocket::get("/clip/<shortcode>/edit", rank = 2)]
+pub async fn edit_clip(
+    cookies: ClipCookies<'_>,
+    shortcode: ShortCode,
+    store: &State<Store>,
+    base_url: BaseUrl,
+    renderer: &State<Renderer<'_>>,
+) -> Result<RawHtml<String>, PageError> {
+    let req = ask::GetOwnedClip {
+        shortcode,
+        edit_token: cookies.edit_token(),
+    };
+    match action::get_owned_clip(req, store.get()).await {
+        Ok(clip) => Ok(RawHtml(
+            renderer.render(ctx::EditClip::new(PublicClip::new(clip, &base_url)), &[]),
+        )),
+        Err(e) => match e {
+            ServiceError::PermissionError(_) => Err(PageError::Forbidden(
+                "Only the author of this clip can edit it".to_owned(),
+            )),
+            ServiceError::NotFound => Err(PageError::NotFound("Clip not found".to_owned())),
+            _ => Err(PageError::Internal("Server Error".to_owned())),
+        },
+    }
+}
+
+#[rocket::post("/clip/<shortcode>/edit", data = "<form>")]
+pub async fn update_clip(
+    cookies: ClipCookies<'_>,
+    form: Form<Contextual<'_, form::EditClip>>,
+    shortcode: ShortCode,
+    store: &State<Store>,
+    quota: &State<Quota>,
+    base_url: BaseUrl,
+    renderer: &State<Renderer<'_>>,
+) -> Result<Redirect, PageError> {
+    let req = ask::GetOwnedClip {
+        shortcode: shortcode.clone(),
+        edit_token: cookies.edit_token(),
+    };
+    let clip = match action::get_owned_clip(req, store.get()).await {
+        Ok(clip) => clip,
+        Err(e) => {
+            return Err(match e {
+                ServiceError::PermissionError(_) => {
+                    PageError::Forbidden("Only the author of this clip can edit it".to_owned())
+                }
+                ServiceError::NotFound => PageError::NotFound("Clip not found".to_owned()),
+                _ => PageError::Internal("Server Error".to_owned()),
+            })
+        }
+    };
+    let form = form.into_inner();
+    if let Some(value) = form.value {
+        let keep_password = !value.password.has_password();
+        let req = ask::UpdateClip {
+            content: value.content,
+            title: value.title,
+            expires: value.expires,
+            password: value.password,
+            shortcode: shortcode.clone(),
+            edit_token: clip.edit_token.clone(),
+            keep_password,
+        };
+        match action::update_clip(req, quota, store.get()).await {
+            Ok(clip) => Ok(Redirect::to(uri!(get_clip(shortcode = clip.shortcode)))),
+            Err(ServiceError::Quota(e)) => {
+                let error = e.to_string();
+                Err(PageError::Page(status::Custom(
+                    Status::PayloadTooLarge,
+                    RawHtml(renderer.render(
+                        ctx::EditClip::new(PublicClip::new(clip, &base_url)),
+                        &[error.as_str()],
+                    )),
+                )))
+            }
+            Err(e) => {
+                eprintln!("internal error: {}", e);
+                Err(PageError::Page(status::Custom(
+                    Status::InternalServerError,
+                    RawHtml(renderer.render(
+                        ctx::EditClip::new(PublicClip::new(clip, &base_url)),
+                        &["A server error occurred. Please try again"],
+                    )),
+                )))
+            }
+        }
+    } else {
+        let errors = form_errors(&form.context);
+        Err(PageError::Page(status::Custom(
+            Status::BadRequest,
+            RawHtml(renderer.render(
+                ctx::EditClip::new(PublicClip::new(clip, &base_url)),
+                &errors,
+            )),
+        )))
+    }
+}
+
+#[rocket::get("/clip/<shortcode>/history?<from>&<to>", rank = 2)]
+pub async fn clip_history(
+    cookies: ClipCookies<'_>,
+    shortcode: ShortCode,
+    from: Option<u32>,
+    to: Option<u32>,
+    store: &State<Store>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<status::Custom<RawHtml<String>>, PageError> {
+    let req = ask::GetClip {
+        shortcode: shortcode.clone(),
+        password: cookies.password(),
+    };
+    let revisions = match action::list_revisions(req, store.get()).await {
+        Ok(revisions) => revisions,
+        Err(e) => {
+            return match e {
+                ServiceError::PermissionError(_) => {
+                    let context = ctx::PasswordRequired::new(shortcode);
+                    Ok(status::Custom(
+                        Status::Unauthorized,
+                        RawHtml(renderer.render(context, &[])),
+                    ))
+                }
+                ServiceError::NotFound => Err(PageError::NotFound("Clip not found".to_owned())),
+                _ => Err(PageError::Internal("Server Error".to_owned())),
+            }
+        }
+    };
+    let owned = ask::GetOwnedClip {
+        shortcode: shortcode.clone(),
+        edit_token: cookies.edit_token(),
+    };
+    let owner = action::get_owned_clip(owned, store.get()).await.is_ok();
+    let latest = revisions.last().map(|rev| rev.revision).unwrap_or_default();
+    let to = to.unwrap_or(latest);
+    let from = from.unwrap_or_else(|| to.saturating_sub(1).max(1));
+    let context = ctx::ClipHistory::new(shortcode, revisions, from, to, owner)
+        .ok_or_else(|| PageError::NotFound("Revision not found".to_owned()))?;
+    Ok(status::Custom(
+        Status::Ok,
+        RawHtml(renderer.render(context, &[])),
+    ))
+}
+
+#[rocket::post("/clip/<shortcode>/history/<revision>/restore")]
+pub async fn restore_revision(
+    cookies: ClipCookies<'_>,
+    shortcode: ShortCode,
+    revision: u32,
+    store: &State<Store>,
+    quota: &State<Quota>,
+    base_url: BaseUrl,
+    renderer: &State<Renderer<'_>>,
+) -> Result<Redirect, PageError> {
+    let req = ask::RestoreRevision {
+        shortcode: shortcode.clone(),
+        edit_token: cookies.edit_token(),
+        revision,
+    };
+    match action::restore_revision(req, quota, store.get()).await {
+        Ok(clip) => Ok(Redirect::to(uri!(get_clip(shortcode = clip.shortcode)))),
+        Err(e) => match e {
+            ServiceError::PermissionError(_) => Err(PageError::Forbidden(
+                "Only the author of this clip can restore revisions".to_owned(),
+            )),
+            ServiceError::Quota(e) => {
+                let req = ask::GetOwnedClip {
+                    shortcode,
+                    edit_token: cookies.edit_token(),
+                };
+                let clip = action::get_owned_clip(req, store.get())
+                    .await
+                    .map_err(|_| PageError::Internal("Server Error".to_owned()))?;
+                let error = e.to_string();
+                Err(PageError::Page(status::Custom(
+                    Status::PayloadTooLarge,
+                    RawHtml(renderer.render(
+                        ctx::EditClip::new(PublicClip::new(clip, &base_url)),
+                        &[error.as_str()],
+                    )),
+                )))
+            }
+            ServiceError::NotFound => Err(PageError::NotFound("Revision not found".to_owned())),
+            _ => Err(PageError::Internal("Server Error".to_owned())),
+        },
+    }
+}
+
+#[rocket::post("/clip/<shortcode>/delete")]
+pub async fn delete_clip(
+    cookies: ClipCookies<'_>,
+    shortcode: ShortCode,
+    store: &State<Store>,
+) -> Result<Redirect, PageError> {
+    let req = ask::DeleteClip {
+        shortcode: shortcode.clone(),
+        password: cookies.password(),
+        edit_token: cookies.edit_token(),
+    };
+    match action::delete_clip(req, store.get()).await {
+        Ok(()) => Ok(Redirect::to(uri!(home))),
+        Err(e) => match e {
+            ServiceError::PermissionError(_) => {
+                Ok(Redirect::to(uri!(get_clip(shortcode = &shortcode))))
+            }
+            ServiceError::NotFound => Err(PageError::NotFound("Clip not found".to_owned())),
+            _ => Err(PageError::Internal("Server Error".to_owned())),
+        },
+    }
+}
+
+#[rocket::get("/recent?<sort>&<cursor>")]
+pub async fn recent(
+    sort: Option<ClipSort>,
+    cursor: Option<ListCursor>,
+    store: &State<Store>,
+    hit_counter: &State<HitCounter>,
+    base_url: BaseUrl,
+    renderer: &State<Renderer<'_>>,
+) -> Result<RawHtml<String>, PageError> {
+    let sort = sort.unwrap_or_default();
+    let req = ask::ListClips {
+        sort,
+        cursor,
+        limit: RECENT_PAGE_SIZE,
+    };
+    let mut page = action::list_clips(req, store.get())
+        .await
+        .map_err(|_| PageError::Internal("Server Error".to_owned()))?;
+    page.clips
+        .iter_mut()
+        .for_each(|clip| hit_counter.merge(clip));
+    let context = ctx::Recent::new(page, sort, &base_url);
+    Ok(RawHtml(renderer.render(context, &[])))
+}
+
+#[rocket::get("/search?<q>&<page>")]
+pub async fn search(
+    q: Option<String>,
+    page: Option<u32>,
+    store: &State<Store>,
+    renderer: &State<Renderer<'_>>,
+) -> Result<RawHtml<String>, PageError> {
+    let query = q.unwrap_or_default();
+    let req = ask::SearchClips {
+        query: query.clone(),
+        page: page.unwrap_or(1),
+    };
+    let results = action::search_clips(req, store.get())
+        .await
+        .map_err(|_| PageError::Internal("Server Error".to_owned()))?;
+    let context = ctx::Search::new(query, results);
+    Ok(RawHtml(renderer.render(context, &[])))
+}
+
 #[rocket::get("/clip/raw/<shortcode>")]
 pub async fn get_raw_clip(
-    cookies: &CookieJar<'_>,
+    cookies: ClipCookies<'_>,
     shortcode: ShortCode,
     hit_counter: &State<HitCounter>,
-    database: &State<AppDatabase>,
+    visitor: Visitor,
+    store: &State<Store>,
 ) -> Result<status::Custom<String>, Status> {
     let req = ask::GetClip {
         shortcode: shortcode.clone(),
-        password: cookies
-            .get(PASSWORD_COOKIE)
-            .map(|cookie| cookie.value())
-            .map(|raw_password| field::Password::new(raw_password.to_string()).ok())
-            .flatten()
-            .unwrap_or_else(field::Password::default),
+        password: cookies.password(),
     };
-    match action::get_clip(req, database.get_pool()).await {
+    match action::get_clip(req, store.get()).await {
         Ok(clip) => {
-            hit_counter.hit(shortcode.clone(), 1);
+            hit_counter.hit(visitor.view(shortcode.clone(), ViewSource::Raw));
             Ok(status::Custom(Status::Ok, clip.content.into_inner()))
         }
         Err(e) => match e {
@@ -173,6 +503,46 @@ pub async fn get_raw_clip(
     }
 }
 
+#[rocket::get("/clip/<shortcode>/attachments/<attachment_id>", rank = 2)]
+pub async fn get_attachment(
+    cookies: ClipCookies<'_>,
+    shortcode: ShortCode,
+    attachment_id: &str,
+    store: &State<Store>,
+) -> Result<Download, status::Custom<String>> {
+    let attachment_id = field::AttachmentId::from_str(attachment_id)
+        .map_err(|_| status::Custom(Status::NotFound, "Attachment not found".to_owned()))?;
+    let req = ask::GetAttachment {
+        shortcode,
+        password: cookies.password(),
+        attachment_id,
+    };
+    match action::get_attachment(req, store.get()).await {
+        Ok(file) => Ok(Download(file)),
+        Err(e) => Err(match e {
+            ServiceError::PermissionError(msg) => status::Custom(Status::Unauthorized, msg),
+            ServiceError::NotFound => {
+                status::Custom(Status::NotFound, "Attachment not found".to_owned())
+            }
+            _ => status::Custom(Status::InternalServerError, "Server Error".to_owned()),
+        }),
+    }
+}
+
 pub fn routes() -> Vec<rocket::Route> {
-    rocket::routes![home, get_clip, new_clip, submit_clip_password, get_raw_clip]
+    rocket::routes![
+        home,
+        get_clip,
+        new_clip,
+        submit_clip_password,
+        edit_clip,
+        update_clip,
+        clip_history,
+        restore_revision,
+        delete_clip,
+        recent,
+        search,
+        get_raw_clip,
+        get_attachment
+    ]
 }
@@ -0,0 +1,79 @@
+use std::borrow::Cow;
+
+use pulldown_cmark::{html, Options, Parser};
+
+/// Renders Markdown `source` to HTML that is safe to embed in a page.
+///
+/// Tables, fenced code, task lists, strikethrough and footnotes are supported. Any
+/// raw HTML in the source is sanitized: scripts, event handlers, styles and unsafe
+/// URLs are removed, while the markup produced by the Markdown extensions is kept.
+pub fn to_html(source: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES;
+    let mut unsafe_html = String::with_capacity(source.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(source, options));
+
+    ammonia::Builder::default()
+        .add_tags(["input"])
+        .add_tag_attributes("input", ["type", "checked", "disabled"])
+        .add_tag_attributes("code", ["class"])
+        .attribute_filter(filter_attribute)
+        .clean(&unsafe_html)
+        .to_string()
+}
+
+/// Restricts the attributes allowed for task lists and fenced code to the values
+/// the Markdown renderer produces.
+fn filter_attribute<'u>(element: &str, attribute: &str, value: &'u str) -> Option<Cow<'u, str>> {
+    match (element, attribute) {
+        ("input", "type") => (value == "checkbox").then_some(value.into()),
+        ("input", "disabled") => Some("".into()),
+        ("code", "class") => value
+            .strip_prefix("language-")
+            .filter(|language| {
+                !language.is_empty()
+                    && language
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "+#._-".contains(c))
+            })
+            .map(|_| value.into()),
+        _ => Some(value.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extensions() {
+        let html = to_html(
+            "| a | b |\n|---|---|\n| 1 | 2 |\n\n- [x] done\n- [ ] todo\n\n```rust\nfn main() {}\n```\n",
+        );
+        assert!(html.contains("<table>"));
+        assert!(html.contains("<td>2</td>"));
+        assert!(html.contains(r#"type="checkbox""#));
+        assert!(html.contains("checked"));
+        assert!(html.contains(r#"<code class="language-rust">"#));
+    }
+
+    #[test]
+    fn test_sanitizes_html() {
+        let html = to_html(
+            "<script>alert(1)</script>\n\n<img src=x onerror=alert(1)>\n\n[x](javascript:alert(1))\n\n<input type=\"text\">",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("onerror"));
+        assert!(!html.contains("javascript:"));
+        assert!(!html.contains(r#"type="text""#));
+    }
+
+    #[test]
+    fn test_code_class() {
+        let html = to_html("```x\" onclick=\"y\nz\n```\n");
+        assert!(!html.contains("onclick"));
+        assert!(!html.contains("class="));
+    }
+}
@@ -1,13 +1,19 @@
 pub mod api;
+pub mod attachment;
+pub mod cookies;
 pub mod ctx;
 pub mod form;
+pub mod highlight;
 pub mod hitcounter;
 pub mod http;
+pub mod markdown;
+pub mod public;
+pub mod ratelimit;
 pub mod renderer;
 
-pub use hitcounter::HitCounter;
-
-pub const PASSWORD_COOKIE: &str = "password";
+pub use cookies::{ClipCookies, CookieNames};
+pub use hitcounter::{HitCounter, Visitor};
+pub use public::{BaseUrl, ClipList, PublicAttachment, PublicClip, SearchPage, SearchResult};
 
 #[derive(rocket::Responder)]
 pub enum PageError {
@@ -15,10 +21,14 @@ pub enum PageError {
     Serialization(String),
     #[response(status = 500)]
     Render(String),
+    #[response(status = 403)]
+    Forbidden(String),
     #[response(status = 404)]
     NotFound(String),
     #[response(status = 500)]
     Internal(String),
+    /// A rendered page served with its own status, such as a form shown again with errors.
+    Page(rocket::response::status::Custom<rocket::response::content::RawHtml<String>>),
 }
 
 impl From<handlebars::RenderError> for PageError {
@@ -0,0 +1,277 @@
+use rocket::request::{FromRequest, Outcome};
+use rocket::Request;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::clip::{field, Attachment, ClipPage, SearchHit, SearchResults};
+use crate::{Clip, ShortCode};
+
+/// The scheme and authority a request was addressed to, used to build absolute clip URLs.
+///
+/// The scheme is taken from the `X-Forwarded-Proto` header when running behind a proxy,
+/// and the authority from the `Host` header, falling back to the configured address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BaseUrl(String);
+
+impl BaseUrl {
+    pub fn new<T: Into<String>>(base: T) -> Self {
+        Self(base.into().trim_end_matches('/').to_owned())
+    }
+
+    pub fn clip_url(&self, shortcode: &ShortCode) -> String {
+        format!("{}/clip/{}", self.0, shortcode.as_str())
+    }
+
+    pub fn attachment_url(&self, shortcode: &ShortCode, id: &field::AttachmentId) -> String {
+        format!(
+            "{}/attachments/{}",
+            self.clip_url(shortcode),
+            id.clone().into_inner()
+        )
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BaseUrl {
+    type Error = ();
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let scheme = req.headers().get_one("X-Forwarded-Proto").unwrap_or("http");
+        let authority = match req.host() {
+            Some(host) => host.to_string(),
+            None => {
+                let config = req.rocket().config();
+                format!("{}:{}", config.address, config.port)
+            }
+        };
+        Outcome::Success(Self::new(format!("{}://{}", scheme, authority)))
+    }
+}
+
+/// A clip as shown to API clients and rendered in pages.
+///
+/// Unlike [`Clip`], this never carries the password or edit token of the clip. The
+/// content is left out of metadata-only responses.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicClip {
+    pub shortcode: ShortCode,
+    pub url: String,
+    pub title: field::Title,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<field::Content>,
+    pub posted: field::Posted,
+    pub expires: field::Expires,
+    pub has_password: bool,
+    pub size: usize,
+    pub hits: field::Hits,
+    pub max_views: field::MaxViews,
+    pub views: field::Views,
+    pub language: field::Language,
+    pub format: field::Format,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<PublicAttachment>,
+}
+
+impl PublicClip {
+    pub fn new(clip: Clip, base_url: &BaseUrl) -> Self {
+        Self {
+            url: base_url.clip_url(&clip.shortcode),
+            has_password: clip.password.has_password(),
+            size: clip.content.as_str().len(),
+            shortcode: clip.shortcode,
+            title: clip.title,
+            content: Some(clip.content),
+            posted: clip.posted,
+            expires: clip.expires,
+            hits: clip.hits,
+            max_views: clip.max_views,
+            views: clip.views,
+            language: clip.language,
+            format: clip.format,
+            attachments: vec![],
+        }
+    }
+
+    /// Lists `attachments` with the clip, along with the URLs they can be downloaded from.
+    pub fn with_attachments(self, attachments: Vec<Attachment>, base_url: &BaseUrl) -> Self {
+        let attachments = attachments
+            .into_iter()
+            .map(|attachment| PublicAttachment::new(attachment, &self.shortcode, base_url))
+            .collect();
+        Self {
+            attachments,
+            ..self
+        }
+    }
+
+    /// Builds the public view of a clip without its content.
+    pub fn metadata(clip: Clip, base_url: &BaseUrl) -> Self {
+        Self {
+            content: None,
+            ..Self::new(clip, base_url)
+        }
+    }
+}
+
+/// An attachment of a clip, as listed with the clip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublicAttachment {
+    pub attachment_id: field::AttachmentId,
+    pub filename: field::FileName,
+    pub mime_type: field::MimeType,
+    pub size: u64,
+    pub url: String,
+}
+
+impl PublicAttachment {
+    pub fn new(attachment: Attachment, shortcode: &ShortCode, base_url: &BaseUrl) -> Self {
+        Self {
+            url: base_url.attachment_url(shortcode, &attachment.attachment_id),
+            attachment_id: attachment.attachment_id,
+            filename: attachment.filename,
+            mime_type: attachment.mime_type,
+            size: attachment.size,
+        }
+    }
+}
+
+/// A page of listed clips as returned to API clients.
+///
+/// Listed clips only carry their metadata; their content is fetched separately.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipList {
+    pub clips: Vec<PublicClip>,
+    /// The cursor to pass to fetch the next page, if there is one.
+    pub next_cursor: Option<String>,
+}
+
+impl ClipList {
+    pub fn new(page: ClipPage, base_url: &BaseUrl) -> Self {
+        Self {
+            clips: page
+                .clips
+                .into_iter()
+                .map(|clip| PublicClip::metadata(clip, base_url))
+                .collect(),
+            next_cursor: page.next.map(|cursor| cursor.encode()),
+        }
+    }
+}
+
+/// A single search result as returned to API clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub shortcode: ShortCode,
+    pub url: String,
+    pub title: field::Title,
+    pub snippet: String,
+    pub rank: f64,
+}
+
+impl SearchResult {
+    pub fn new(hit: SearchHit, base_url: &BaseUrl) -> Self {
+        Self {
+            url: base_url.clip_url(&hit.shortcode),
+            snippet: hit.plain_snippet(),
+            shortcode: hit.shortcode,
+            title: hit.title,
+            rank: hit.rank,
+        }
+    }
+}
+
+/// A page of search results as returned to API clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub query: String,
+    pub page: u32,
+    pub next_page: Option<u32>,
+    pub results: Vec<SearchResult>,
+}
+
+impl SearchPage {
+    pub fn new(query: String, results: SearchResults, base_url: &BaseUrl) -> Self {
+        Self {
+            query,
+            page: results.page,
+            next_page: results.has_more.then_some(results.page + 1),
+            results: results
+                .hits
+                .into_iter()
+                .map(|hit| SearchResult::new(hit, base_url))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::DbId;
+    use crate::Time;
+
+    fn clip() -> Clip {
+        Clip {
+            clip_id: field::ClipId::new(DbId::new()),
+            shortcode: ShortCode::from("abc123"),
+            content: field::Content::new("Hello, world!").unwrap(),
+            title: field::Title::new("My Clip".to_string()),
+            posted: field::Posted::new(Time::from_seconds(0)),
+            expires: field::Expires::default(),
+            password: field::PasswordHash::new("hash".to_string()),
+            hits: field::Hits::new(3),
+            edit_token: field::EditToken::generate(),
+            max_views: field::MaxViews::default(),
+            views: field::Views::default(),
+            language: field::Language::default(),
+            format: field::Format::default(),
+        }
+    }
+
+    #[test]
+    fn test_public_clip() {
+        let base_url = BaseUrl::new("https://clip.example/");
+        let public = PublicClip::new(clip(), &base_url);
+        assert_eq!(public.url, "https://clip.example/clip/abc123");
+        assert!(public.has_password);
+        assert_eq!(public.size, 13);
+        assert_eq!(public.hits, field::Hits::new(3));
+
+        let json = serde_json::to_value(&public).unwrap();
+        assert_eq!(json["content"], "Hello, world!");
+        assert!(json.get("password").is_none());
+        assert!(json.get("edit_token").is_none());
+    }
+
+    #[test]
+    fn test_metadata() {
+        let base_url = BaseUrl::new("http://localhost:8000");
+        let public = PublicClip::metadata(clip(), &base_url);
+        assert!(public.content.is_none());
+        assert_eq!(public.size, 13);
+
+        let json = serde_json::to_value(&public).unwrap();
+        assert!(json.get("content").is_none());
+    }
+
+    #[test]
+    fn test_with_attachments() {
+        let base_url = BaseUrl::new("http://localhost:8000");
+        let json = serde_json::to_value(PublicClip::new(clip(), &base_url)).unwrap();
+        assert!(json.get("attachments").is_none());
+
+        let id = DbId::new();
+        let attachment = Attachment {
+            attachment_id: field::AttachmentId::new(id.clone()),
+            filename: field::FileName::new("shot.png").unwrap(),
+            mime_type: field::MimeType::new("image/png").unwrap(),
+            size: 42,
+        };
+        let public =
+            PublicClip::new(clip(), &base_url).with_attachments(vec![attachment], &base_url);
+        assert_eq!(public.attachments.len(), 1);
+        assert_eq!(
+            public.attachments[0].url,
+            format!("http://localhost:8000/clip/abc123/attachments/{}", id)
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    time::Instant,
+};
+
+use parking_lot::Mutex;
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome},
+    response::{self, Responder},
+    Request, Response, State,
+};
+
+use crate::web::api::KeyDigest;
+
+/// The number of clients tracked before the longest-tracked one is forgotten.
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// A sustained request rate, with bursts of up to `burst` requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+impl Rate {
+    /// Returns the rate, or `None` if `per_minute` is zero and requests are not limited.
+    pub fn new(per_minute: u32, burst: u32) -> Option<Self> {
+        (per_minute > 0).then_some(Self {
+            per_minute,
+            burst: burst.max(1),
+        })
+    }
+
+    fn per_second(&self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+/// How long a rejected client should wait before sending another request, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryAfter(pub u64);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    ApiKey(Vec<u8>),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(rate: Rate, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(rate.burst),
+            updated: now,
+        }
+    }
+
+    fn take(&mut self, rate: Rate, now: Instant) -> Result<(), RetryAfter> {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second()).min(f64::from(rate.burst));
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - self.tokens) / rate.per_second();
+            Err(RetryAfter(wait.ceil() as u64))
+        }
+    }
+}
+
+/// The buckets of the tracked clients, and the order they started being tracked in.
+#[derive(Debug, Default)]
+struct Buckets {
+    buckets: HashMap<Client, Bucket>,
+    tracked: VecDeque<Client>,
+}
+
+/// Token bucket rate limiter for web posting, per client IP, and API requests, per key.
+///
+/// At most `MAX_TRACKED_CLIENTS` clients are tracked; beyond that, the client tracked the
+/// longest is forgotten and starts over with a full bucket.
+#[derive(Debug)]
+pub struct RateLimiter {
+    web: Option<Rate>,
+    api: Option<Rate>,
+    trust_proxy_headers: bool,
+    capacity: usize,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter; `None` leaves the corresponding requests unlimited.
+    ///
+    /// Web clients are told apart by the address they connect from, or, with
+    /// `trust_proxy_headers`, by the `X-Real-IP` header a reverse proxy sets.
+    pub fn new(web: Option<Rate>, api: Option<Rate>, trust_proxy_headers: bool) -> Self {
+        Self {
+            web,
+            api,
+            trust_proxy_headers,
+            capacity: MAX_TRACKED_CLIENTS,
+            buckets: Mutex::new(Buckets::default()),
+        }
+    }
+
+    /// The address web requests are limited by.
+    pub fn client_ip(&self, req: &Request<'_>) -> Option<IpAddr> {
+        if self.trust_proxy_headers {
+            req.client_ip()
+        } else {
+            req.remote().map(|addr| addr.ip())
+        }
+    }
+
+    /// Takes a web request from the bucket of the client at `ip`.
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), RetryAfter> {
+        self.check(Client::Ip(ip), Instant::now())
+    }
+
+    /// Takes an API request from the bucket of an authenticated API key.
+    pub fn check_api_key(&self, digest: &KeyDigest) -> Result<(), RetryAfter> {
+        self.check(Client::ApiKey(digest.clone().into_inner()), Instant::now())
+    }
+
+    fn rate(&self, client: &Client) -> Option<Rate> {
+        match client {
+            Client::Ip(_) => self.web,
+            Client::ApiKey(_) => self.api,
+        }
+    }
+
+    fn check(&self, client: Client, now: Instant) -> Result<(), RetryAfter> {
+        let rate = match self.rate(&client) {
+            Some(rate) => rate,
+            None => return Ok(()),
+        };
+        let mut state = self.buckets.lock();
+        let Buckets { buckets, tracked } = &mut *state;
+        if !buckets.contains_key(&client) {
+            while buckets.len() >= self.capacity {
+                match tracked.pop_front() {
+                    Some(oldest) => buckets.remove(&oldest),
+                    None => break,
+                };
+            }
+            tracked.push_back(client.clone());
+        }
+        buckets
+            .entry(client)
+            .or_insert_with(|| Bucket::full(rate, now))
+            .take(rate, now)
+    }
+}
+
+/// Rejects the request with `429 Too Many Requests`, remembering `retry_after` for the
+/// catcher that renders the response.
+pub(crate) fn reject<T, E>(req: &Request<'_>, retry_after: RetryAfter, error: E) -> Outcome<T, E> {
+    req.local_cache(|| retry_after);
+    Outcome::Failure((Status::TooManyRequests, error))
+}
+
+/// Request guard limiting web requests per client IP.
+#[derive(Debug)]
+pub struct WebLimit;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for WebLimit {
+    type Error = RetryAfter;
+
+    async fn from_request(req: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let limiter = match req.guard::<&State<RateLimiter>>().await {
+            Outcome::Success(limiter) => limiter,
+            _ => return Outcome::Success(WebLimit),
+        };
+        let ip = match limiter.client_ip(req) {
+            Some(ip) => ip,
+            None => return Outcome::Success(WebLimit),
+        };
+        match limiter.check_ip(ip) {
+            Ok(()) => Outcome::Success(WebLimit),
+            Err(retry_after) => reject(req, retry_after, retry_after),
+        }
+    }
+}
+
+/// A `429 Too Many Requests` response carrying a `Retry-After` header.
+#[derive(Debug)]
+pub struct TooManyRequests<R>(pub R);
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for TooManyRequests<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let RetryAfter(seconds) = *req.local_cache(|| RetryAfter(1));
+        Response::build_from(self.0.respond_to(req)?)
+            .status(Status::TooManyRequests)
+            .raw_header("Retry-After", seconds.to_string())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_rate() {
+        assert_eq!(Rate::new(0, 10), None);
+        assert_eq!(Rate::new(60, 0).unwrap().burst, 1);
+    }
+
+    #[test]
+    fn test_bucket() {
+        let rate = Rate::new(30, 2).unwrap();
+        let now = Instant::now();
+        let mut bucket = Bucket::full(rate, now);
+
+        assert!(bucket.take(rate, now).is_ok());
+        assert!(bucket.take(rate, now).is_ok());
+        assert_eq!(bucket.take(rate, now), Err(RetryAfter(2)));
+        assert_eq!(
+            bucket.take(rate, now + Duration::from_secs(1)),
+            Err(RetryAfter(1))
+        );
+        assert!(bucket.take(rate, now + Duration::from_secs(2)).is_ok());
+    }
+
+    #[test]
+    fn test_limiter() {
+        let limiter = RateLimiter::new(Rate::new(60, 1), None, false);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let now = Instant::now();
+
+        assert!(limiter.check(Client::Ip(ip), now).is_ok());
+        assert!(limiter.check(Client::Ip(ip), now).is_err());
+        assert!(limiter
+            .check(Client::Ip(IpAddr::from([127, 0, 0, 2])), now)
+            .is_ok());
+        for _ in 0..10 {
+            assert!(limiter.check(Client::ApiKey(vec![1]), now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_limiter_forgets_oldest_client() {
+        let limiter = RateLimiter {
+            capacity: 2,
+            ..RateLimiter::new(Rate::new(60, 1), None, false)
+        };
+        let now = Instant::now();
+        let client = |last| Client::Ip(IpAddr::from([127, 0, 0, last]));
+
+        assert!(limiter.check(client(1), now).is_ok());
+        assert!(limiter.check(client(2), now).is_ok());
+        assert!(limiter.check(client(2), now).is_err());
+        assert!(limiter.check(client(3), now).is_ok());
+        assert_eq!(limiter.buckets.lock().buckets.len(), 2);
+        assert!(limiter.check(client(1), now).is_ok());
+        assert!(limiter.check(client(3), now).is_err());
+    }
+}
@@ -2,7 +2,7 @@ use serde::Serialize;
 use std::path::PathBuf;
 use thiserror::Error;
 
-use super::ctx;
+use super::{ctx, highlight::Highlighter};
 
 #[derive(Error, Debug)]
 pub enum RendererError {
@@ -27,6 +27,9 @@ impl<'r> Renderer<'r> {
 
     fn new_inner(template_dir: PathBuf) -> Result<Self, RendererError> {
         let mut handlebars = handlebars::Handlebars::new();
+        // Indenting partials would add whitespace to the clip content inside `<pre>` blocks.
+        handlebars.set_prevent_indent(true);
+        handlebars.register_helper("highlight", Box::new(Highlighter::new()));
         handlebars
             .register_templates_directory(".hbs", &template_dir)
             .map_err(|e| RendererError::GetTemplateError {
@@ -127,6 +130,20 @@ mod tests {
         assert_eq!(result, "Hello, World!");
     }
 
+    #[test]
+    fn test_highlight_helper() {
+        let template_dir = tempdir().unwrap();
+        let template_file = template_dir.path().join("template.hbs");
+        let mut file = File::create(&template_file).unwrap();
+        file.write_all(b"{{{highlight content language}}}").unwrap();
+
+        let renderer = Renderer::new_inner(template_dir.path().to_path_buf()).unwrap();
+        let result = renderer
+            .do_render("template", &json!({"content": "{}", "language": "json"}))
+            .unwrap();
+        assert!(result.starts_with("<table class=\"highlight\">"));
+    }
+
     #[test]
     fn test_convert_to_value() {
         #[derive(Serialize)]